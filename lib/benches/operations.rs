@@ -0,0 +1,291 @@
+//! Benchmarks of the core operations, on a memory device and on a file-backed one.
+//!
+//! Wall time is measured by criterion. Each benchmark also runs once on a
+//! [`TracingDevice`], and every group ends with a table of the sectors read and
+//! written per layout region, so a drop in cache hits or more bitmap scanning shows
+//! up even when timings are noisy.
+
+use std::{
+    hint::black_box,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use criterion::{
+    BenchmarkGroup, Criterion, criterion_group, criterion_main, measurement::WallTime,
+};
+use ffs_lib::{
+    Addr, BlockDevice, Controller, Error, constants,
+    testutils::{FileDevice, MemoryDevice, Operation, Trace, TracingDevice},
+};
+
+/// Device behind a mutable reference, so that operations are written once for every
+/// kind of device.
+struct DynDevice<'a>(&'a mut dyn BlockDevice);
+
+impl BlockDevice for DynDevice<'_> {
+    fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.0.read(sector, buf)
+    }
+
+    fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        self.0.write(sector, buf)
+    }
+
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.0.read_sectors(sector, buf)
+    }
+
+    fn prefetch(&mut self, sector: Addr, count: usize) -> Result<(), Error> {
+        self.0.prefetch(sector, count)
+    }
+}
+
+type Ctrl<'a> = Controller<DynDevice<'a>>;
+
+/// Device the benchmarks run on, built from a prepared memory image.
+trait Device: BlockDevice + Sized + 'static {
+    const NAME: &'static str;
+
+    fn from_image(image: &MemoryDevice) -> Self;
+}
+
+impl Device for MemoryDevice {
+    const NAME: &'static str = "memory";
+
+    fn from_image(image: &MemoryDevice) -> Self {
+        image.clone()
+    }
+}
+
+/// File-backed device on a temporary copy of an image, removed once dropped.
+struct TempFileDevice {
+    device: FileDevice,
+    path: PathBuf,
+}
+
+impl Drop for TempFileDevice {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl BlockDevice for TempFileDevice {
+    fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.device.read(sector, buf)
+    }
+
+    fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        self.device.write(sector, buf)
+    }
+
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.device.read_sectors(sector, buf)
+    }
+}
+
+impl Device for TempFileDevice {
+    const NAME: &'static str = "file";
+
+    fn from_image(image: &MemoryDevice) -> Self {
+        let path = std::env::temp_dir().join(format!("ffs-bench-{}.img", std::process::id()));
+        let name = path.to_str().expect("temporary path should be valid UTF-8");
+        image.persist_to_file(name).expect("should write the image");
+        let device = FileDevice::new(name).expect("should open the image");
+        Self { device, path }
+    }
+}
+
+/// Returns a formatted image holding the entries created by `setup`.
+fn image(setup: impl FnOnce(&mut Controller<MemoryDevice>)) -> MemoryDevice {
+    let mut device = MemoryDevice::new(512, 8 * 1024 * 1024);
+    Controller::format(&mut device).expect("should format device");
+    let mut ctrl = Controller::mount(device).expect("should mount device");
+    setup(&mut ctrl);
+    ctrl.unmount()
+}
+
+/// Creates `count` small files, spread over two levels of directories which hold a
+/// limited number of entries.
+fn create_files(ctrl: &mut Controller<MemoryDevice>, count: usize) {
+    for i in 0..count {
+        let path = format!("dir{}/dir{}/file{}.bin", i / 400, i / 20 % 20, i % 20);
+        ctrl.create(&path, b"contents").expect("should create file");
+    }
+}
+
+/// Path of a file `depth` directories below the root.
+fn deep_path(depth: usize) -> String {
+    let mut path = "d/".repeat(depth);
+    path.push_str("file.bin");
+    path
+}
+
+/// Benchmarks of a group, and the I/O of a single run of each of them.
+struct Group<'c> {
+    group: BenchmarkGroup<'c, WallTime>,
+    io: Vec<Operation>,
+}
+
+impl<'c> Group<'c> {
+    fn new(c: &'c mut Criterion, name: &str) -> Self {
+        Self { group: c.benchmark_group(name), io: Vec::new() }
+    }
+
+    /// Benchmarks `operation` on every kind of device, mounted from `image`.
+    ///
+    /// `undo` runs untimed after each iteration, to bring the filesystem back to its
+    /// initial state.
+    fn bench<O, U>(&mut self, name: &str, image: &MemoryDevice, operation: O, undo: U)
+    where
+        O: Fn(&mut Ctrl<'_>),
+        U: Fn(&mut Ctrl<'_>),
+    {
+        let mut device = TracingDevice::new(image.clone());
+        let trace = device.trace();
+        let mut ctrl = Controller::mount(DynDevice(&mut device)).expect("should mount device");
+        trace.begin(name);
+        operation(&mut ctrl);
+        self.io.extend(trace.operation(name));
+
+        self.bench_on::<MemoryDevice, _, _>(name, image, &operation, &undo);
+        self.bench_on::<TempFileDevice, _, _>(name, image, &operation, &undo);
+    }
+
+    fn bench_on<D, O, U>(&mut self, name: &str, image: &MemoryDevice, operation: &O, undo: &U)
+    where
+        D: Device,
+        O: Fn(&mut Ctrl<'_>),
+        U: Fn(&mut Ctrl<'_>),
+    {
+        let mut device = D::from_image(image);
+        let mut ctrl = Controller::mount(DynDevice(&mut device)).expect("should mount device");
+        self.group.bench_function(format!("{name}/{}", D::NAME), |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    operation(&mut ctrl);
+                    elapsed += start.elapsed();
+                    undo(&mut ctrl);
+                }
+                elapsed
+            });
+        });
+    }
+
+    /// Ends the group, printing the sectors read and written by each benchmark.
+    fn finish(self) {
+        self.group.finish();
+        let io: Trace = self.io.into_iter().collect();
+        println!("\nsectors read/written per run:\n{io}");
+    }
+}
+
+fn nothing(_: &mut Ctrl<'_>) {}
+
+fn create(c: &mut Criterion) {
+    let mut group = Group::new(c, "create");
+    let image = image(|_| {});
+    for len in [100, 2000, constants::MAX_FILE_SIZE] {
+        let data = vec![7; len];
+        group.bench(
+            &format!("{len}B"),
+            &image,
+            |ctrl| ctrl.create("logs/file.bin", &data).unwrap(),
+            |ctrl| ctrl.delete("logs/file.bin").unwrap(),
+        );
+    }
+    group.finish();
+}
+
+fn open_read(c: &mut Criterion) {
+    let mut group = Group::new(c, "open_read");
+    for len in [100, 2000, constants::MAX_FILE_SIZE] {
+        let image = image(|ctrl| ctrl.create("logs/file.bin", &vec![7; len]).unwrap());
+        let read = |ctrl: &mut Ctrl<'_>| {
+            let mut buf = [0; constants::MAX_FILE_SIZE];
+            let len = ctrl.open("logs/file.bin").and_then(|mut file| file.readall(&mut buf));
+            black_box(len.unwrap());
+        };
+        group.bench(&format!("{len}B"), &image, read, nothing);
+    }
+    group.finish();
+}
+
+fn delete(c: &mut Criterion) {
+    let mut group = Group::new(c, "delete");
+    for len in [100, 2000, constants::MAX_FILE_SIZE] {
+        let data = vec![7; len];
+        let image = image(|ctrl| ctrl.create("logs/file.bin", &data).unwrap());
+        group.bench(
+            &format!("{len}B"),
+            &image,
+            |ctrl| ctrl.delete("logs/file.bin").unwrap(),
+            |ctrl| ctrl.create("logs/file.bin", &data).unwrap(),
+        );
+    }
+    group.finish();
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut group = Group::new(c, "lookup");
+    for depth in [1, 4, 16, constants::PATH_MAX_DEPTH - 1] {
+        let path = deep_path(depth);
+        let image = image(|ctrl| ctrl.create(&path, b"contents").unwrap());
+        let stat = |ctrl: &mut Ctrl<'_>| {
+            black_box(ctrl.stat(&path).unwrap());
+        };
+        group.bench(&format!("depth {depth}"), &image, stat, nothing);
+    }
+    group.finish();
+}
+
+fn dir_fill(c: &mut Criterion) {
+    let mut group = Group::new(c, "dir_fill");
+    let image = image(|_| {});
+    let paths: Vec<_> =
+        (0..constants::TREE_NODE_ENTRY_LEN).map(|i| format!("full/file{i}.bin")).collect();
+    group.bench(
+        &format!("{} files", paths.len()),
+        &image,
+        |ctrl| {
+            for path in &paths {
+                ctrl.create(path, b"contents").unwrap();
+            }
+            assert!(ctrl.create("full/extra.bin", b"contents").is_err(), "should be full");
+        },
+        |ctrl| paths.iter().for_each(|path| ctrl.delete(path).unwrap()),
+    );
+    group.finish();
+}
+
+fn count(c: &mut Criterion) {
+    let mut group = Group::new(c, "count");
+    for files in [10, 100, 1000] {
+        let image = image(|ctrl| create_files(ctrl, files));
+        let count_files = |ctrl: &mut Ctrl<'_>| {
+            black_box(ctrl.count_files().unwrap());
+        };
+        let count_dirs = |ctrl: &mut Ctrl<'_>| {
+            black_box(ctrl.count_dirs().unwrap());
+        };
+        let count_free_data_blocks = |ctrl: &mut Ctrl<'_>| {
+            black_box(ctrl.count_free_data_blocks().unwrap());
+        };
+        group.bench(&format!("files/{files}"), &image, count_files, nothing);
+        group.bench(&format!("dirs/{files}"), &image, count_dirs, nothing);
+        group.bench(&format!("free_data_blocks/{files}"), &image, count_free_data_blocks, nothing);
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default()
+        .sample_size(10)
+        .warm_up_time(Duration::from_millis(200))
+        .measurement_time(Duration::from_secs(1));
+    targets = create, open_read, delete, lookup, dir_fill, count
+}
+criterion_main!(benches);
@@ -0,0 +1,197 @@
+//! Extracts the contents of an image to a host directory or a tar archive.
+
+use std::{
+    collections::{HashMap, hash_map},
+    fs::{self, File, FileTimes, Permissions},
+    io::{self, Write},
+    os::unix::fs::{PermissionsExt, chown, symlink},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use ffs_lib::{Addr, Error, Metadata, Timestamp, constants};
+
+use crate::{Ctrl, Failure, read_file};
+
+/// Entry of the exported tree, with its path relative to the exported directory.
+struct Entry {
+    path: String,
+    metadata: Metadata,
+}
+
+/// Returns every entry below `base_path`, parents before their children.
+fn entries(ctrl: &mut Ctrl, base_path: &str) -> Result<Vec<Entry>, Failure> {
+    let mut entries = Vec::new();
+    ctrl.walk(base_path, |path, metadata| {
+        entries.push(Entry { path: path.to_string(), metadata: *metadata });
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// Path of `entry` in the image, `base_path` being the exported directory.
+fn image_path(base_path: &str, entry: &Entry) -> String {
+    match base_path.trim_end_matches('/') {
+        "" => entry.path.clone(),
+        base_path => format!("{base_path}/{}", entry.path),
+    }
+}
+
+/// Returns the path a file was first exported at when `entry` is another hard link to
+/// it, otherwise remembers the path of `entry` when the file has other links.
+fn exported_link<'e>(exported: &mut HashMap<Addr, &'e str>, entry: &'e Entry) -> Option<&'e str> {
+    if !entry.metadata.is_file() || entry.metadata.links() < 2 {
+        return None;
+    }
+    match exported.entry(entry.metadata.addr()) {
+        hash_map::Entry::Occupied(first) => Some(first.get()),
+        hash_map::Entry::Vacant(vacant) => {
+            vacant.insert(&entry.path);
+            None
+        }
+    }
+}
+
+fn read_link(ctrl: &mut Ctrl, path: &str) -> Result<String, Failure> {
+    let mut buf = [0; constants::BLOCK_SIZE];
+    let len = ctrl.read_link(path, &mut buf)?;
+    String::from_utf8(buf[..len].to_vec()).map_err(|_| Failure::Fs(Error::InvalidName))
+}
+
+/// Returns the extended attributes of the file at `path`, in storage order.
+fn read_xattrs(ctrl: &mut Ctrl, path: &str) -> Result<Vec<(String, Vec<u8>)>, Failure> {
+    let mut keys = [0; constants::INLINE_XATTR_LEN + constants::BLOCK_SIZE];
+    let len = ctrl.list_xattrs(path, &mut keys)?;
+    let mut xattrs = Vec::new();
+    for key in keys[..len].split(|byte| *byte == 0).filter(|key| !key.is_empty()) {
+        let key = String::from_utf8_lossy(key).into_owned();
+        let mut value = [0; constants::BLOCK_SIZE];
+        let len = ctrl.get_xattr(path, &key, &mut value)?;
+        xattrs.push((key, value[..len].to_vec()));
+    }
+    Ok(xattrs)
+}
+
+fn system_time(timestamp: Timestamp) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp.into())
+}
+
+/// Copies the tree below `base_path` into `host_dir`, which is created when missing,
+/// returning the number of exported entries.
+///
+/// Existing host files are never replaced. Modes, timestamps and extended attributes
+/// are preserved, ownership only when running with enough privileges. Directories
+/// get their metadata last, so restrictive modes do not prevent filling them. Files
+/// with several names below `base_path` are copied once, and hard linked to.
+pub fn to_dir(ctrl: &mut Ctrl, base_path: &str, host_dir: &Path) -> Result<usize, Failure> {
+    let entries = entries(ctrl, base_path)?;
+    fs::create_dir_all(host_dir)
+        .map_err(|err| Failure::Host(host_dir.display().to_string(), err))?;
+    let mut exported = HashMap::new();
+
+    for entry in &entries {
+        let path = image_path(base_path, entry);
+        let host_path = host_dir.join(&entry.path);
+        let host_err = |err| Failure::Host(host_path.display().to_string(), err);
+        let metadata = &entry.metadata;
+
+        if metadata.is_dir() {
+            fs::create_dir(&host_path).map_err(host_err)?;
+        } else if metadata.is_symlink() {
+            symlink(read_link(ctrl, &path)?, &host_path).map_err(host_err)?;
+        } else if let Some(first) = exported_link(&mut exported, entry) {
+            fs::hard_link(host_dir.join(first), &host_path).map_err(host_err)?;
+        } else {
+            let data = read_file(ctrl, &path)?;
+            let mut file = File::create_new(&host_path).map_err(host_err)?;
+            file.write_all(&data).map_err(host_err)?;
+            for (key, value) in read_xattrs(ctrl, &path)? {
+                if let Err(err) = xattr::set(&host_path, &key, &value) {
+                    eprintln!("ffs: {}: cannot set xattr {key}: {err}", host_path.display());
+                }
+            }
+            set_metadata(&file, &host_path, metadata).map_err(host_err)?;
+        }
+    }
+
+    for entry in entries.iter().rev().filter(|entry| entry.metadata.is_dir()) {
+        let host_path = host_dir.join(&entry.path);
+        File::open(&host_path)
+            .and_then(|dir| set_metadata(&dir, &host_path, &entry.metadata))
+            .map_err(|err| Failure::Host(host_path.display().to_string(), err))?;
+    }
+    Ok(entries.len())
+}
+
+/// Sets the timestamps, owner and mode of the host `file` at `host_path`.
+fn set_metadata(file: &File, host_path: &Path, metadata: &Metadata) -> io::Result<()> {
+    let permissions = metadata.permissions();
+    let times = FileTimes::new()
+        .set_accessed(system_time(metadata.accessed()))
+        .set_modified(system_time(metadata.modified()));
+    file.set_times(times)?;
+    // Only the superuser may give files away, like tar, keep the current owner otherwise.
+    match chown(host_path, Some(permissions.uid()), Some(permissions.gid())) {
+        Err(err) if err.kind() != io::ErrorKind::PermissionDenied => return Err(err),
+        _ => {}
+    }
+    file.set_permissions(Permissions::from_mode(permissions.mode().into()))
+}
+
+/// Writes the tree below `base_path` as a tar archive to the host file `dst`, or to
+/// the standard output when `dst` is `-`, returning the number of exported entries.
+///
+/// Entries record their mode, owner and modification time, extended attributes are
+/// stored as PAX records, as GNU tar does with `--xattrs`. Files with several names
+/// below `base_path` are stored once, their other names as hard link entries.
+pub fn to_tar(ctrl: &mut Ctrl, base_path: &str, dst: &str) -> Result<usize, Failure> {
+    let host_err = |err| Failure::Host(dst.to_string(), err);
+    // Walk first, so no archive is left behind when the base path does not exist.
+    let entries = entries(ctrl, base_path)?;
+    let mut exported = HashMap::new();
+    let out: Box<dyn Write> = if dst == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(dst).map_err(host_err)?)
+    };
+
+    let mut builder = tar::Builder::new(io::BufWriter::new(out));
+    for entry in &entries {
+        let path = image_path(base_path, entry);
+        let metadata = &entry.metadata;
+        let permissions = metadata.permissions();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(permissions.mode().into());
+        header.set_uid(permissions.uid().into());
+        header.set_gid(permissions.gid().into());
+        header.set_mtime(metadata.modified().into());
+        header.set_size(0);
+
+        if metadata.is_dir() {
+            header.set_entry_type(tar::EntryType::Directory);
+            builder.append_data(&mut header, &entry.path, io::empty()).map_err(host_err)?;
+        } else if metadata.is_symlink() {
+            let target = read_link(ctrl, &path)?;
+            header.set_entry_type(tar::EntryType::Symlink);
+            builder.append_link(&mut header, &entry.path, target).map_err(host_err)?;
+        } else if let Some(first) = exported_link(&mut exported, entry) {
+            header.set_entry_type(tar::EntryType::Link);
+            builder.append_link(&mut header, &entry.path, first).map_err(host_err)?;
+        } else {
+            let data = read_file(ctrl, &path)?;
+            let xattrs: Vec<_> = read_xattrs(ctrl, &path)?
+                .into_iter()
+                .map(|(key, value)| (format!("SCHILY.xattr.{key}"), value))
+                .collect();
+            if !xattrs.is_empty() {
+                let records = xattrs.iter().map(|(key, value)| (key.as_str(), value.as_slice()));
+                builder.append_pax_extensions(records).map_err(host_err)?;
+            }
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(data.len() as u64);
+            builder.append_data(&mut header, &entry.path, data.as_slice()).map_err(host_err)?;
+        }
+    }
+    builder.into_inner().and_then(|mut out| out.flush()).map_err(host_err)?;
+    Ok(entries.len())
+}
@@ -0,0 +1,123 @@
+//! Populates an image from a host directory, like `mke2fs -d`.
+
+use std::{fs, io, path::Path};
+
+use ffs_lib::{Error, constants};
+
+use crate::{Ctrl, Failure};
+
+/// Outcome of an import, entries that could not be imported are reported and skipped.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub files: usize,
+    pub dirs: usize,
+    /// Number of skipped entries.
+    pub skipped: usize,
+    /// First error an entry was skipped for, `None` when only host entries without an
+    /// equivalent in the image were skipped.
+    pub skip_error: Option<Error>,
+}
+
+impl Report {
+    fn skip(&mut self, path: &str, err: Error) {
+        eprintln!("ffs: skipping {path}: {err}");
+        self.skip_error.get_or_insert(err);
+        self.skipped += 1;
+    }
+
+    /// Skips a socket, fifo or device, which the image cannot hold.
+    fn skip_special(&mut self, path: &str) {
+        eprintln!("ffs: skipping {path}: not a regular file");
+        self.skipped += 1;
+    }
+}
+
+/// Whether `err` only concerns the entry being imported, so the import can go on.
+const fn is_entry_limit(err: Error) -> bool {
+    matches!(
+        err,
+        Error::NameTooLong
+            | Error::PathTooDeep
+            | Error::InvalidName
+            | Error::FileTooLarge
+            | Error::DirectoryFull
+    )
+}
+
+/// Copies the contents of `host_dir` into the root directory of the image, recursively.
+///
+/// Files, directories and symbolic links are imported in name order. Entries exceeding
+/// the filesystem limits, and other kinds of host entries, are reported with their path
+/// and skipped, while other errors stop the import.
+pub fn import(ctrl: &mut Ctrl, host_dir: &Path) -> Result<Report, Failure> {
+    let mut report = Report::default();
+    import_dir(ctrl, host_dir, "", &mut report)?;
+    Ok(report)
+}
+
+fn import_dir(
+    ctrl: &mut Ctrl,
+    host_dir: &Path,
+    image_dir: &str,
+    report: &mut Report,
+) -> Result<(), Failure> {
+    let host_err = |err| Failure::Host(host_dir.display().to_string(), err);
+    let mut entries = fs::read_dir(host_dir)
+        .and_then(|dir| dir.collect::<io::Result<Vec<_>>>())
+        .map_err(host_err)?;
+    entries.sort_by_key(fs::DirEntry::file_name);
+
+    for entry in entries {
+        let host_path = entry.path();
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str() else {
+            report.skip(&host_path.display().to_string(), Error::InvalidName);
+            continue;
+        };
+        let image_path =
+            if image_dir.is_empty() { name.to_string() } else { format!("{image_dir}/{name}") };
+
+        match import_entry(ctrl, &host_path, &image_path, report) {
+            Err(Failure::Fs(err)) if is_entry_limit(err) => report.skip(&image_path, err),
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+fn import_entry(
+    ctrl: &mut Ctrl,
+    host_path: &Path,
+    image_path: &str,
+    report: &mut Report,
+) -> Result<(), Failure> {
+    let host_err = |err| Failure::Host(host_path.display().to_string(), err);
+    let file_type = fs::symlink_metadata(host_path).map_err(host_err)?.file_type();
+
+    if file_type.is_dir() {
+        ctrl.mkdir(image_path)?;
+        report.dirs += 1;
+        return import_dir(ctrl, host_path, image_path, report);
+    }
+    if file_type.is_symlink() {
+        let target = fs::read_link(host_path).map_err(host_err)?;
+        let target = target.to_str().ok_or(Error::InvalidName)?;
+        ctrl.symlink(target, image_path)?;
+        report.files += 1;
+        return Ok(());
+    }
+
+    if !file_type.is_file() {
+        report.skip_special(image_path);
+        return Ok(());
+    }
+    // Check the size first, so large files are not read for nothing.
+    let len = fs::metadata(host_path).map_err(host_err)?.len();
+    if len > constants::MAX_FILE_SIZE as u64 {
+        return Err(Error::FileTooLarge.into());
+    }
+    let data = fs::read(host_path).map_err(host_err)?;
+    ctrl.create(image_path, &data)?;
+    report.files += 1;
+    Ok(())
+}
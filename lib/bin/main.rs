@@ -1,108 +1,299 @@
-use ffs_lib::{BlockDevice, Controller, testutils::MemoryDevice};
-
-fn ls_tree<D>(ctrl: &mut Controller<D>, base_path: &str, depth: usize)
-where
-    D: BlockDevice,
-{
-    println!("> Listing tree at {base_path}");
-    ctrl.print_tree_std(base_path, depth).unwrap();
-    println!();
-}
-
-fn rm_file<D>(ctrl: &mut Controller<D>, fname: &str)
-where
-    D: BlockDevice,
-{
-    println!("> Deleting {fname}");
-    ctrl.delete(fname).expect("failed");
-    println!();
-}
-
-fn ls_stats(ctrl: &mut Controller<impl BlockDevice>) {
-    let file_count = ctrl.count_files().expect("failed to count files");
-    let dirs_count = ctrl.count_dirs().expect("failed to count free blocks");
-    let free_blocks = ctrl.count_free_data_blocks().expect("failed to count free blocks");
-    println!("> Stats:");
-    println!("  files: {file_count}");
-    println!("  dir_nodes: {dirs_count}");
-    println!("  free_blocks: {free_blocks}");
-    println!();
-}
-
-fn main() {
-    // Too annoying to work on macOS with this:
-    // - it requires root privileges to access raw disk devices.
-    // - it requires the device to be unmounted manually after each execution.
-    //
-    // let device_path = "/dev/rdisk4";
-    // let mut sdcard= OpenOptions::new()
-    //     .read(true)
-    //     .write(true)
-    //     .open(device_path)?;
-
-    let data = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit,
-sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam,
-quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat.
-Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla
-pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt
-mollit anim id est laborum. Lorem ipsum dolor sit amet consectetur adipiscing elit.
-Vitae pellentesque sem placerat in id cursus mi. Tempus leo eu aenean sed diam urna tempor. Nec metus bibendum egestas iaculis massa nisl malesuada. Ut hendrerit semper vel class aptent
-taciti sociosqu. Conubia nostra inceptos himenaeos orci varius natoque penatibus.
-Montes nascetur ridiculus mus donec rhoncus eros lobortis. Maximus eget fermentum odio phasellus non purus est.
-Vestibulum fusce dictum risus blandit quis suspendisse aliquet. Ante condimentum neque at luctus nibh finibus facilisis.
-Ligula congue sollicitudin erat viverra ac tincidunt nam. Euismod quam justo lectus commodo augue arcu dignissim.";
-
-    let sdcard = MemoryDevice::load_from_file(512, "sdcard.img").map_or_else(
-        |_| {
-            println!("Formatting sdcard...");
-            let mut disk = MemoryDevice::new(512, 8 * 1024 * 1024);
-            Controller::format(&mut disk).expect("failed to format SD card");
-            disk
-        },
-        |disk| {
-            println!("Loaded sdcard.img");
-            disk
-        },
-    );
-
-    // let sdcard = FileDevice::new("/dev/sdb").expect("cannot open device");
-
-    // Controller::format(&mut sdcard).expect("failed to format SD card");
-    // println!("partitioned!");
-
-    let mut ctrl = Controller::mount(sdcard).expect("failed to read metadata");
+//! Command-line tool to inspect and modify a filesystem image or block device.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    process::ExitCode,
+};
+
+use ffs_lib::{Controller, Error, SystemClock, constants, testutils::FileDevice};
+
+mod export;
+mod import;
+
+const USAGE: &str = "usage: ffs <command> <image> [args...]
+
+commands:
+  mkfs   <image> [-d <dir>]       format the image, creating it when missing, and
+                                  populate it with the contents of the host dir
+  ls     <image> [path]           list the entries of a directory
+  tree   <image> [path] [depth]   print the tree below a directory
+  cat    <image> <path>           print the contents of a file
+  put    <image> <src> <path>     copy the host file src into the image
+  get    <image> <path> <dst>     copy a file out of the image to the host file dst
+  rm     <image> <path>...        delete files
+  mkdir  <image> <path>           create a directory
+  mv     <image> <from> <to>      move a file or directory
+  stat   <image> <path>           print the metadata of an entry
+  df     <image>                  print the usage of the image
+  layout <image>                  print the layout of the image
+  export <image> <dir> [path]     extract the tree below path into the host dir
+  export <image> --tar <file> [path]
+                                  write the tree below path as a tar archive,
+                                  to the standard output when file is -";
+
+/// Exit code for invalid command-line arguments, as in `sysexits.h`.
+const EXIT_USAGE: u8 = 64;
+/// Exit code for host entries the image has no equivalent for, as in `sysexits.h`.
+const EXIT_DATAERR: u8 = 65;
+/// Exit code for failures to access host files, as in `sysexits.h`.
+const EXIT_IO: u8 = 74;
+
+type Ctrl = Controller<FileDevice, SystemClock>;
+
+enum Failure {
+    Usage,
+    Host(String, io::Error),
+    Fs(Error),
+    /// Some entries were skipped and reported, along with the first error if any.
+    Skipped(Option<Error>, usize),
+}
+
+impl From<Error> for Failure {
+    fn from(err: Error) -> Self {
+        Self::Fs(err)
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage) => {
+            eprintln!("{USAGE}");
+            ExitCode::from(EXIT_USAGE)
+        }
+        Err(Failure::Host(path, err)) => {
+            eprintln!("ffs: {path}: {err}");
+            ExitCode::from(EXIT_IO)
+        }
+        Err(Failure::Fs(err)) => {
+            eprintln!("ffs: {err}");
+            ExitCode::from(exit_code(err))
+        }
+        Err(Failure::Skipped(err, count)) => {
+            eprintln!("ffs: {count} entries skipped");
+            ExitCode::from(err.map_or(EXIT_DATAERR, exit_code))
+        }
+    }
+}
+
+/// Maps filesystem errors to the closest `errno` value, so scripts can tell them apart.
+const fn exit_code(err: Error) -> u8 {
+    match err {
+        Error::FileNotFound | Error::DirectoryNotFound | Error::XattrNotFound => 2,
+        Error::Io => 5,
+        Error::PermissionDenied => 13,
+        Error::FileAlreadyExists => 17,
+        Error::UnsupportedDevice => 19,
+        Error::NotADirectory => 20,
+        Error::IsADirectory => 21,
+        Error::InvalidName | Error::NotASymlink | Error::InvalidSeek => 22,
+        Error::FileTooLarge | Error::XattrTooLarge => 27,
+        Error::StorageFull | Error::DirectoryFull => 28,
+        Error::NameTooLong | Error::PathTooDeep => 36,
+        Error::TooManyLinks => 40,
+        Error::BufferTooSmall { .. } | Error::Unexpected => 1,
+    }
+}
+
+fn run(args: &[&str]) -> Result<(), Failure> {
+    let [command, image, args @ ..] = args else {
+        return Err(Failure::Usage);
+    };
+    if *command == "mkfs" {
+        return mkfs(image, args);
+    }
+
+    // Report missing images as host errors, the device only knows it cannot be opened.
+    fs::metadata(image).map_err(|err| Failure::Host((*image).to_string(), err))?;
+    let device = FileDevice::new(image)?;
+    let mut ctrl = Controller::mount_with_clock(device, SystemClock)?;
+    let result = match *command {
+        "ls" => ls(&mut ctrl, args),
+        "tree" => tree(&mut ctrl, args),
+        "cat" => cat(&mut ctrl, args),
+        "put" => put(&mut ctrl, args),
+        "get" => get(&mut ctrl, args),
+        "rm" => rm(&mut ctrl, args),
+        "mkdir" => mkdir(&mut ctrl, args),
+        "mv" => mv(&mut ctrl, args),
+        "stat" => stat(&mut ctrl, args),
+        "df" => df(&mut ctrl, args),
+        "layout" => layout(&ctrl, args),
+        "export" => export(&mut ctrl, args),
+        _ => Err(Failure::Usage),
+    };
+    // The cache writes through, so every write already reached the image, even when the
+    // command failed halfway. Unmounting only hands back the device, closing the image.
+    ctrl.unmount();
+    result
+}
+
+fn mkfs(image: &str, args: &[&str]) -> Result<(), Failure> {
+    let host_dir = match args {
+        [] => None,
+        ["-d", dir] => Some(*dir),
+        _ => return Err(Failure::Usage),
+    };
+    let host_err = |err| Failure::Host(image.to_string(), err);
+    let file = OpenOptions::new().write(true).create(true).truncate(false).open(image);
+    let file = file.map_err(host_err)?;
+    // Block devices have a fixed size, only regular files are grown to fit the layout.
+    let metadata = file.metadata().map_err(host_err)?;
+    if metadata.is_file() && metadata.len() < constants::DEVICE_LEN as u64 {
+        file.set_len(constants::DEVICE_LEN as u64).map_err(host_err)?;
+    }
+
+    let mut device = FileDevice::new(image)?;
+    Controller::format(&mut device)?;
+    println!("formatted {image} ({} bytes)", constants::DEVICE_LEN);
+
+    let Some(host_dir) = host_dir else {
+        return Ok(());
+    };
+    let mut ctrl = Controller::mount_with_clock(device, SystemClock)?;
+    let report = import::import(&mut ctrl, Path::new(host_dir));
+    ctrl.unmount();
+    let report = report?;
+    println!("imported {} files and {} directories from {host_dir}", report.files, report.dirs);
+    match report.skipped {
+        0 => Ok(()),
+        count => Err(Failure::Skipped(report.skip_error, count)),
+    }
+}
+
+fn ls(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let path = match args {
+        [] => "",
+        [path] => path,
+        _ => return Err(Failure::Usage),
+    };
+    Ok(ctrl.print_tree_std(path, 1)?)
+}
+
+fn tree(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let (path, depth) = match args {
+        [] => ("", "0"),
+        [path] => (*path, "0"),
+        [path, depth] => (*path, *depth),
+        _ => return Err(Failure::Usage),
+    };
+    let depth = depth.parse().map_err(|_| Failure::Usage)?;
+    Ok(ctrl.print_tree_std(path, depth)?)
+}
+
+/// Reads the file at `path` without updating its access time, so that copying files
+/// out of the image leaves it unchanged.
+fn read_file(ctrl: &mut Ctrl, path: &str) -> Result<Vec<u8>, Failure> {
+    let mut buf = vec![0; constants::MAX_FILE_SIZE];
+    let len = ctrl.open_noatime(path)?.readall(&mut buf)?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+fn cat(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [path] = args else {
+        return Err(Failure::Usage);
+    };
+    let data = read_file(ctrl, path)?;
+    io::stdout().write_all(&data).map_err(|err| Failure::Host("stdout".to_string(), err))
+}
+
+fn put(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [src, path] = args else {
+        return Err(Failure::Usage);
+    };
+    let data = fs::read(src).map_err(|err| Failure::Host((*src).to_string(), err))?;
+    Ok(ctrl.create(path, &data)?)
+}
+
+fn get(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [path, dst] = args else {
+        return Err(Failure::Usage);
+    };
+    let data = read_file(ctrl, path)?;
+    fs::write(dst, data).map_err(|err| Failure::Host((*dst).to_string(), err))
+}
+
+fn rm(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    if args.is_empty() {
+        return Err(Failure::Usage);
+    }
+    for path in args {
+        ctrl.delete(path)?;
+    }
+    Ok(())
+}
+
+fn mkdir(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [path] = args else {
+        return Err(Failure::Usage);
+    };
+    Ok(ctrl.mkdir(path)?)
+}
+
+fn mv(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [from, to] = args else {
+        return Err(Failure::Usage);
+    };
+    Ok(ctrl.rename(from, to)?)
+}
+
+fn stat(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [path] = args else {
+        return Err(Failure::Usage);
+    };
+    let metadata = ctrl.stat(path)?;
+    let kind = if metadata.is_dir() { "directory" } else { "file" };
+    let permissions = metadata.permissions();
+    println!("  path: {path}");
+    println!("  kind: {kind}");
+    println!("  size: {}", metadata.len());
+    println!(" links: {}", metadata.links());
+    println!("  mode: {:04o}", permissions.mode());
+    println!(" owner: {}:{}", permissions.uid(), permissions.gid());
+    println!("create: {}", metadata.created());
+    println!("modify: {}", metadata.modified());
+    println!("access: {}", metadata.accessed());
+    Ok(())
+}
+
+fn df(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [] = args else {
+        return Err(Failure::Usage);
+    };
+    let free_blocks = ctrl.count_free_data_blocks()?;
+    println!("files: {}", ctrl.count_files()?);
+    println!("dirs: {}", ctrl.count_dirs()?);
+    println!("free blocks: {free_blocks}");
+    println!("free bytes: {}", free_blocks * constants::BLOCK_SIZE);
+    Ok(())
+}
+
+fn layout(ctrl: &Ctrl, args: &[&str]) -> Result<(), Failure> {
+    let [] = args else {
+        return Err(Failure::Usage);
+    };
     ctrl.print_disk_layout();
+    Ok(())
+}
+
+fn export(ctrl: &mut Ctrl, args: &[&str]) -> Result<(), Failure> {
+    match args {
+        ["--tar", dst] => export::to_tar(ctrl, "", dst).map(|_| ()),
+        ["--tar", dst, path] => export::to_tar(ctrl, path, dst).map(|_| ()),
+        [dst] | [dst, ""] => export_dir(ctrl, "", dst),
+        [dst, path] => export_dir(ctrl, path, dst),
+        _ => Err(Failure::Usage),
+    }
+}
 
-    println!("> Controller initialized");
-
-    ls_stats(&mut ctrl);
-    ls_tree(&mut ctrl, "", 0);
-
-    println!("> Creating file");
-    let fname = "dirA/dirB/lorem_ipsum8.txt";
-    ctrl.create(fname, data).expect("failed to create file");
-
-    let _ = ctrl.create("/dirC/first/one.txt", data);
-    let _ = ctrl.create("/dirC/first/two.txt", data);
-    let _ = ctrl.create("/dirC/second/three.txt", data);
-    let _ = ctrl.create("/dirC/third/four.txt", data);
-    let _ = ctrl.create("/dirD/boot/dev", data);
-
-    println!("> Reading file contents");
-    let mut fd = ctrl.open(fname).expect("failed to open file");
-    let mut buf = [0u8; ffs_lib::constants::MAX_FILE_SIZE];
-    fd.readall(&mut buf).expect("failed to read file");
-    println!("> Read {} bytes from {fname}", fd.file_len());
-    println!("> Contents:\n\n{}\n", str::from_utf8(&buf[..fd.file_len() as usize]).unwrap());
-
-    ls_stats(&mut ctrl);
-    ls_tree(&mut ctrl, "", 0);
-    rm_file(&mut ctrl, fname);
-    ls_tree(&mut ctrl, "", 0);
-    ls_tree(&mut ctrl, "dirC", 0);
-    ls_tree(&mut ctrl, "dirC", 1);
-    ls_tree(&mut ctrl, "dirC/first/", 1);
-
-    let _sdcard = ctrl.unmount();
-    // sdcard.persist_to_file("sdcard.img").expect("Failed to persist SD card image");
+fn export_dir(ctrl: &mut Ctrl, path: &str, dst: &str) -> Result<(), Failure> {
+    let count = export::to_dir(ctrl, path, Path::new(dst))?;
+    println!("exported {count} entries to {dst}");
+    Ok(())
 }
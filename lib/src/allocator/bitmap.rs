@@ -60,6 +60,29 @@ impl Bitmap {
         None
     }
 
+    /// Takes `n` consecutive addresses, starting at or after `from`.
+    ///
+    /// Returns the first address of the run, or `None` if no run is long enough.
+    pub fn take_run(&mut self, from: Addr, n: usize) -> Option<Addr> {
+        let mut start = from as usize;
+        for addr in from as usize..Self::SLOTS {
+            if self.is_taken(addr as Addr) {
+                start = addr + 1;
+            } else if addr + 1 - start == n {
+                for taken in start..start + n {
+                    self.block[taken / 8] |= 1 << (taken % 8);
+                }
+                return Some(start as Addr);
+            }
+        }
+        None
+    }
+
+    /// Whether the address is taken.
+    pub fn is_taken(&self, addr: Addr) -> bool {
+        self.block[(addr / 8) as usize] & (1 << (addr % 8)) != 0
+    }
+
     /// Releases an address and makes it available to be taken again.
     ///
     /// Updates the [`Self::last_free_pos`] heuristic.
@@ -155,6 +178,8 @@ mod tests {
         sut.release(512);
         sut.release(600);
         sut.release(700);
+        assert!(!sut.is_taken(600));
+        assert!(sut.is_taken(601));
 
         assert_eq!(3, sut.count_free_addresses());
         assert_eq!(Some(512), sut.take());
@@ -162,4 +187,19 @@ mod tests {
         assert_eq!(2, sut.count_free_addresses());
         assert_eq!(Some(600), sut.take());
     }
+
+    #[test]
+    fn test_take_run() {
+        let mut sut = Bitmap::new();
+        assert_eq!(Some(4), take_nth_blocks(&mut sut, 5));
+        sut.release(2);
+
+        assert_eq!(Some(5), sut.take_run(0, 3));
+        assert_eq!(Some(2), sut.take_run(0, 1));
+        assert_eq!(Some(20), sut.take_run(20, 2));
+        assert_eq!(Some(8), sut.take_run(0, 12));
+        assert_eq!(Some(22), sut.take());
+        assert_eq!(None, sut.take_run(4090, 7));
+        assert_eq!(4096 - 23, sut.count_free_addresses());
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{
     Addr, Block, BlockDevice, Deserializable, Error, Serializable, constants,
-    device_layout::DeviceLayout, node::Node,
+    device_layout::DeviceLayout,
+    node::{HOLE, Node},
 };
 pub use bitmap::Bitmap;
 
@@ -18,7 +19,10 @@ impl Allocator {
     }
 
     /// Counts the number of free addresses, counts each bitmap of the layout.
-    pub fn count_free_addresses<D: BlockDevice>(&self, device: &mut D) -> Result<usize, Error> {
+    pub fn count_free_addresses<D: BlockDevice + ?Sized>(
+        &self,
+        device: &mut D,
+    ) -> Result<usize, Error> {
         let mut total = 0;
         let mut block = Block::new();
         for sector in self.layout.iter_sectors() {
@@ -38,10 +42,10 @@ impl Allocator {
     /// # Returns
     /// - `Ok(())` if exactly `n` blocks were successfully allocated and stored in `buffer[0..n]`.
     /// - `Err(Error::BufferTooSmall)` if the buffer is too small to hold `n` indices.
-    /// - `Err(Error::StorageFull)` if fewer than `n` blocks could be allocated. In this case,
-    ///   allocated blocks will be automatically released.
+    /// - `Err(Error::StorageFull)` if fewer than `n` blocks could be allocated, or the device
+    ///   error that stopped the allocation. In both cases, allocated blocks are released.
     ///
-    pub fn allocate_n<D: BlockDevice>(
+    pub fn allocate_n<D: BlockDevice + ?Sized>(
         &mut self,
         device: &mut D,
         addrs: &mut [Addr],
@@ -51,16 +55,15 @@ impl Allocator {
             return Err(Error::BufferTooSmall { expected: n, found: addrs.len() });
         }
 
-        let mut current = 0;
-        while current < n {
-            if let Ok(addr) = self.allocate(device) {
-                addrs[current] = addr;
-                current += 1;
-            } else {
-                for addr in addrs.iter().take(current) {
-                    self.release(device, *addr)?;
+        for current in 0..n {
+            match self.allocate(device) {
+                Ok(addr) => addrs[current] = addr,
+                Err(err) => {
+                    for addr in addrs.iter().take(current) {
+                        self.release(device, *addr)?;
+                    }
+                    return Err(err);
                 }
-                return Err(Error::StorageFull);
             }
         }
         Ok(())
@@ -75,7 +78,7 @@ impl Allocator {
     /// # Notes
     /// - Uses a circular scan starting from `self.last_accessed` for improved allocation locality.
     /// - Updates `self.last_accessed` to the most recent allocation position to avoid always starting from 0.
-    pub fn allocate<D: BlockDevice>(&mut self, device: &mut D) -> Result<Addr, Error> {
+    pub fn allocate<D: BlockDevice + ?Sized>(&mut self, device: &mut D) -> Result<Addr, Error> {
         let mut block = Block::new();
 
         for (addr, sector) in self.layout.circular_iter(self.last_accessed) {
@@ -92,6 +95,40 @@ impl Allocator {
         Err(Error::StorageFull)
     }
 
+    /// Attempts to allocate `n` consecutive blocks, returning the first one.
+    ///
+    /// Runs starting at `near` are preferred, so that a file can keep growing
+    /// contiguously. A run never spans two bitmaps.
+    ///
+    /// # Returns
+    /// - `Ok(Addr)` if the blocks were successfully allocated.
+    /// - `Err(Error::StorageFull)` if no run of `n` free blocks is available.
+    pub fn allocate_contiguous<D: BlockDevice + ?Sized>(
+        &mut self,
+        device: &mut D,
+        n: usize,
+        near: Addr,
+    ) -> Result<Addr, Error> {
+        let mut block = Block::new();
+        let near_bitmap = to_bitmap_addr(near);
+
+        for (addr, sector) in self.layout.circular_iter(near_bitmap) {
+            device.read(sector, &mut block)?;
+            let mut bitmap = Bitmap::deserialize(&mut block.reader())?;
+
+            let from = if addr == near_bitmap { to_bitmap_offset(near) } else { 0 };
+            // Wrap around to the runs before `from`, unless the whole bitmap was searched.
+            let run = bitmap.take_run(from, n);
+            let run = if run.is_none() && from > 0 { bitmap.take_run(0, n) } else { run };
+            if let Some(bitmap_addr) = run {
+                bitmap.serialize(&mut block.writer())?;
+                device.write(sector, &block)?;
+                return Ok(to_addr(addr, bitmap_addr));
+            }
+        }
+        Err(Error::StorageFull)
+    }
+
     /// Releases an allocated block back into the pool.
     ///
     /// # Arguments
@@ -100,7 +137,11 @@ impl Allocator {
     /// # Notes
     /// - Safe to call multiple times on the same address, though redundant calls may have no effect.
     /// - May adjust `self.last_accessed` to improve future allocation locality.
-    pub fn release<D: BlockDevice>(&mut self, device: &mut D, addr: Addr) -> Result<(), Error> {
+    pub fn release<D: BlockDevice + ?Sized>(
+        &mut self,
+        device: &mut D,
+        addr: Addr,
+    ) -> Result<(), Error> {
         let bitmap_addr = to_bitmap_addr(addr);
         let bitmap_sector = self.layout.nth(bitmap_addr);
         let bitmap_offset = to_bitmap_offset(addr);
@@ -122,13 +163,13 @@ impl Allocator {
 
 /// Provides utility functions so the [`Allocator`] can work with [`Node`] and file data.
 pub trait DataAllocator {
-    fn allocate_node_data<D: BlockDevice>(
+    fn allocate_node_data<D: BlockDevice + ?Sized>(
         &mut self,
         device: &mut D,
-        file_size: usize,
+        data: &[u8],
     ) -> Result<Node, Error>;
 
-    fn release_node_data<D: BlockDevice>(
+    fn release_node_data<D: BlockDevice + ?Sized>(
         &mut self,
         device: &mut D,
         node: &Node,
@@ -136,27 +177,38 @@ pub trait DataAllocator {
 }
 
 impl DataAllocator for Allocator {
-    /// Attempts to allocate enough blocks to fit `file_size` bytes and returns a [`Node`] instance
-    /// with all the allocated addresses.
-    fn allocate_node_data<D: BlockDevice>(
+    /// Attempts to allocate enough blocks to fit `data` and returns a [`Node`] instance
+    /// with all the allocated addresses. Blocks made only of zeros are left as holes.
+    fn allocate_node_data<D: BlockDevice + ?Sized>(
         &mut self,
         device: &mut D,
-        file_size: usize,
+        data: &[u8],
     ) -> Result<Node, Error> {
-        let mut block_addrs = [0; constants::NODE_DATA_BLOCKS_LEN];
-        self.allocate_n(device, &mut block_addrs, file_size.div_ceil(Block::LEN))?;
-        Ok(Node::new(file_size as u16, block_addrs))
+        let mut allocated = [HOLE; constants::NODE_DATA_BLOCKS_LEN];
+        let chunks = data.chunks(Block::LEN).filter(|chunk| chunk.iter().any(|byte| *byte != 0));
+        self.allocate_n(device, &mut allocated, chunks.count())?;
+
+        let mut allocated = allocated.into_iter();
+        let mut block_addrs = [HOLE; constants::NODE_DATA_BLOCKS_LEN];
+        for (addr, chunk) in block_addrs.iter_mut().zip(data.chunks(Block::LEN)) {
+            if chunk.iter().any(|byte| *byte != 0) {
+                *addr = allocated.next().unwrap_or(HOLE);
+            }
+        }
+        Ok(Node::new(data.len() as u16, block_addrs))
     }
 
-    /// Attempts to allocate enough blocks to fit `file_size` bytes and returns a [`Node`] instance
-    /// with all the allocated addresses.
-    fn release_node_data<D: BlockDevice>(
+    /// Releases the blocks used by the data of `node`, including those preallocated past
+    /// the end of the file. Holes are left untouched.
+    fn release_node_data<D: BlockDevice + ?Sized>(
         &mut self,
         device: &mut D,
         node: &Node,
     ) -> Result<(), Error> {
         for addr in node.data_addrs() {
-            self.release(device, *addr)?;
+            if *addr != HOLE {
+                self.release(device, *addr)?;
+            }
         }
         Ok(())
     }
@@ -176,7 +228,7 @@ const fn to_addr(bitmap_addr: Addr, allocated_addr: Addr) -> Addr {
 
 #[cfg(test)]
 mod tests {
-    use crate::testutils::MemoryDevice;
+    use crate::testutils::{Fault, FaultyDevice, MemoryDevice, Operations};
 
     use super::*;
 
@@ -261,19 +313,75 @@ mod tests {
     }
 
     #[test]
-    fn allocate_node_data() {
+    fn allocate_n_releases_on_device_error() {
+        let (device, mut sut) = get_sut();
+        let mut device =
+            FaultyDevice::new(device).with_fault(Fault::Sector(1)).failing(Operations::Reads);
+        take_nth_blocks(&mut sut, &mut device, 4094).unwrap();
+
+        // Two blocks are left in the first bitmap, the third requires reading the second.
+        let mut addrs = [0; 3];
+        assert_eq!(Err(Error::Io), sut.allocate_n(&mut device, &mut addrs, 3));
+
+        let mut device = device.into_inner();
+        assert_eq!(Ok(8192 - 4094), sut.count_free_addresses(&mut device));
+    }
+
+    #[test]
+    fn allocate_contiguous() {
         let (mut device, mut sut) = get_sut();
+        assert_eq!(Ok(4), take_nth_blocks(&mut sut, &mut device, 5));
+        assert_eq!(Ok(()), sut.release(&mut device, 2));
+
+        assert_eq!(Ok(5), sut.allocate_contiguous(&mut device, 3, 0));
+        assert_eq!(Ok(100), sut.allocate_contiguous(&mut device, 4, 100));
+        assert_eq!(Ok(4096), sut.allocate_contiguous(&mut device, 2, 4096));
+        assert_eq!(Ok(8), sut.allocate_contiguous(&mut device, 10, 4094));
+        assert_eq!(Ok(8192 - 23), sut.count_free_addresses(&mut device));
+    }
 
-        let node = sut.allocate_node_data(&mut device, 1).unwrap();
-        assert_eq!([0, 0, 0, 0, 0, 0, 0, 0, 0, 0], node.data_addrs());
+    #[test]
+    fn allocate_node_data() {
+        let (mut device, mut sut) = get_sut();
+        assert_eq!(Ok(HOLE), sut.allocate(&mut device));
 
-        let node = sut.allocate_node_data(&mut device, 128).unwrap();
+        let node = sut.allocate_node_data(&mut device, &[1; 1]).unwrap();
         assert_eq!([1, 0, 0, 0, 0, 0, 0, 0, 0, 0], node.data_addrs());
 
-        let node = sut.allocate_node_data(&mut device, 512).unwrap();
+        let node = sut.allocate_node_data(&mut device, &[1; 512]).unwrap();
         assert_eq!([2, 0, 0, 0, 0, 0, 0, 0, 0, 0], node.data_addrs());
 
-        let node = sut.allocate_node_data(&mut device, 1500).unwrap();
+        let node = sut.allocate_node_data(&mut device, &[1; 1500]).unwrap();
         assert_eq!([3, 4, 5, 0, 0, 0, 0, 0, 0, 0], node.data_addrs());
     }
+
+    #[test]
+    fn allocate_node_data_leaves_zero_blocks_as_holes() {
+        let (mut device, mut sut) = get_sut();
+        assert_eq!(Ok(HOLE), sut.allocate(&mut device));
+
+        let mut data = [0; 2000];
+        data[0] = 1;
+        data[1999] = 1;
+        let node = sut.allocate_node_data(&mut device, &data).unwrap();
+        assert_eq!([1, HOLE, HOLE, 2, 0, 0, 0, 0, 0, 0], node.data_addrs());
+        assert_eq!(2000, node.file_len());
+    }
+
+    #[test]
+    fn release_node_data() {
+        let (mut device, mut sut) = get_sut();
+        assert_eq!(Ok(HOLE), sut.allocate(&mut device));
+
+        let first = sut.allocate_node_data(&mut device, &[1; 1]).unwrap();
+        let second = sut.allocate_node_data(&mut device, &[1; 1024]).unwrap();
+        let sparse = sut.allocate_node_data(&mut device, &[0; 1024]).unwrap();
+        assert_eq!(Ok(8188), sut.count_free_addresses(&mut device));
+
+        assert_eq!(Ok(()), sut.release_node_data(&mut device, &second));
+        assert_eq!(Ok(()), sut.release_node_data(&mut device, &sparse));
+        assert_eq!(Ok(8190), sut.count_free_addresses(&mut device));
+        assert_eq!(Ok(2), sut.allocate(&mut device), "block 1 should still be in use");
+        assert_eq!(Ok(()), sut.release_node_data(&mut device, &first));
+    }
 }
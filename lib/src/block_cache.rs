@@ -1,15 +1,53 @@
-use crate::{Addr, BlockDevice, Error, block::Block};
+use crate::{
+    Addr, BlockDevice, Error, FixedLen, block::Block, device_layout::DeviceLayout,
+    directory::TreeNode,
+};
+
+/// Sectors kept by a [`BlockCache`] unless another size is given.
+pub const DEFAULT_SIZE: usize = 8;
+
+/// Most sectors read ahead by a single [`BlockDevice::prefetch`].
+pub const MAX_PREFETCH: usize = 8;
+
+/// Hit, miss and eviction counts of a [`BlockCache`] since it was mounted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Reads served from the cache.
+    pub hits: usize,
+    /// Reads forwarded to the device.
+    pub misses: usize,
+    /// Sectors dropped to make room for another one.
+    pub evictions: usize,
+    /// Sectors read ahead, before being asked for.
+    pub prefetched: usize,
+}
 
 #[derive(Debug)]
 struct CacheEntry {
     sector: Addr,
     block: Block,
+    /// Tick of the last access, the lowest being the least recently used.
+    last_used: u64,
+    protected: bool,
+    /// Read ahead or written, and not read since, its first read not counting as a reuse.
+    prefetched: bool,
 }
 
+/// Segmented LRU cache of `SIZE` sectors in front of a [`BlockDevice`], writing through
+/// to it. A size of 0 disables caching.
+///
+/// Sectors enter a probationary segment, and move to a protected one when read again.
+/// Sectors read ahead with [`BlockDevice::prefetch`] or written join the probationary
+/// segment too, and are only protected when read twice.
+/// Metadata sectors, the bitmaps and the root directory, are protected
+/// right away. Probationary sectors are evicted first, so a large file read, touching
+/// each data sector once, does not flush the sectors every lookup needs.
 #[derive(Debug)]
-pub struct BlockCache<D, const SIZE: usize = 8> {
+pub struct BlockCache<D, const SIZE: usize = DEFAULT_SIZE> {
     delegate: D,
     entries: [Option<CacheEntry>; SIZE],
+    tick: u64,
+    stats: CacheStats,
 }
 
 /// Implements an LRU cache for a [`BlockDevice`] to minimize the number of
@@ -19,9 +57,14 @@ impl<D, const SIZE: usize> BlockCache<D, SIZE>
 where
     D: BlockDevice,
 {
+    /// Entries that may be protected, the least recently used ones going back to
+    /// probation beyond that, so that new sectors still find room.
+    const PROTECTED_LEN: usize = SIZE - SIZE / 4;
+
     /// Takes ownership of a [`BlockDevice`], and returns a new [`BlockCache`].
     pub const fn mount(device: D) -> Self {
-        Self { delegate: device, entries: [const { None }; SIZE] }
+        let stats = CacheStats { hits: 0, misses: 0, evictions: 0, prefetched: 0 };
+        Self { delegate: device, entries: [const { None }; SIZE], tick: 0, stats }
     }
 
     /// Returns ownership of the wrapped device.
@@ -29,47 +72,227 @@ where
         self.delegate
     }
 
-    fn get(&mut self, sector: Addr) -> Option<&mut Block> {
-        if let Some(pos) = self
-            .entries
-            .iter()
-            .position(|option| option.as_ref().is_some_and(|entry| entry.sector == sector))
-        {
-            self.entries.swap(0, pos);
-            return self.entries[0].as_mut().map(|entry| &mut entry.block);
+    pub const fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Whether `sector` holds metadata read by most operations. The bitmaps come before
+    /// the tree, whose first node is the root directory. The meta block before them is
+    /// only read when mounting, protecting it would take room from the path lookups.
+    const fn is_metadata(sector: Addr) -> bool {
+        sector >= DeviceLayout::TREE_BITMAP.begin()
+            && sector < DeviceLayout::TREE.begin() + TreeNode::BLOCKS_LEN as Addr
+    }
+
+    const fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn get(&mut self, sector: Addr) -> Option<&mut CacheEntry> {
+        self.entries.iter_mut().flatten().find(|entry| entry.sector == sector)
+    }
+
+    fn insert(&mut self, sector: Addr, block: Block, prefetched: bool) {
+        if SIZE == 0 {
+            return;
         }
-        None
+        let last_used = self.next_tick();
+        let slot = self.entries.iter().position(Option::is_none).unwrap_or_else(|| {
+            self.stats.evictions += 1;
+            self.victim()
+        });
+        let protected = Self::is_metadata(sector);
+        let entry = CacheEntry { sector, block, last_used, protected, prefetched };
+        self.entries[slot] = Some(entry);
+        self.rebalance();
     }
 
-    const fn insert(&mut self, sector: Addr, block: Block) {
-        self.entries.rotate_right(1);
-        self.entries[0] = Some(CacheEntry { sector, block });
+    /// Returns the slot of the least recently used entry, probationary ones first.
+    fn victim(&self) -> usize {
+        let lru = |protected: bool| {
+            let entries = self.entries.iter().enumerate();
+            let entries = entries.filter_map(|(slot, entry)| Some((slot, entry.as_ref()?)));
+            let entries = entries.filter(|(_, entry)| entry.protected == protected);
+            entries.min_by_key(|(_, entry)| entry.last_used).map(|(slot, _)| slot)
+        };
+        lru(false).or_else(|| lru(true)).unwrap_or(0)
+    }
+
+    /// Moves the least recently used protected entries back to probation, until at most
+    /// [`Self::PROTECTED_LEN`] are left.
+    fn rebalance(&mut self) {
+        let protected = || self.entries.iter().flatten().filter(|entry| entry.protected).count();
+        for _ in Self::PROTECTED_LEN..protected() {
+            let protected = self.entries.iter_mut().flatten().filter(|entry| entry.protected);
+            if let Some(entry) = protected.min_by_key(|entry| entry.last_used) {
+                entry.protected = false;
+            }
+        }
     }
 }
 
 /// Implements the [`BlockDevice`] trait for the [`BlockCache`]. Intercepting
 /// read and write operations to read and populate the cache.
-impl<D> BlockDevice for BlockCache<D>
+impl<D, const SIZE: usize> BlockDevice for BlockCache<D, SIZE>
 where
     D: BlockDevice,
 {
     fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
-        if let Some(block) = self.get(sector) {
-            buf.copy_from_slice(block);
+        let tick = self.next_tick();
+        if let Some(entry) = self.get(sector) {
+            entry.last_used = tick;
+            entry.protected |= !entry.prefetched;
+            entry.prefetched = false;
+            buf.copy_from_slice(&entry.block);
+            self.stats.hits += 1;
+            self.rebalance();
             return Ok(());
         }
 
+        self.stats.misses += 1;
         self.delegate.read(sector, buf)?;
         let block = Block::from_slice(buf);
-        self.insert(sector, block);
+        self.insert(sector, block, false);
+        Ok(())
+    }
+
+    /// Reads the sectors missing from the cache with a single call to the device, up to
+    /// [`MAX_PREFETCH`]. At most half the cache is read ahead, and no more than the
+    /// probationary segment holds, so that prefetched sectors do not evict each other.
+    fn prefetch(&mut self, sector: Addr, count: usize) -> Result<(), Error> {
+        let protected = self.entries.iter().flatten().filter(|entry| entry.protected).count();
+        let count = count.min(SIZE / 2).min(SIZE - protected).min(MAX_PREFETCH);
+        let sectors = sector..sector + count as Addr;
+        let Some(first) = sectors.clone().find(|sector| self.get(*sector).is_none()) else {
+            return Ok(());
+        };
+        let last = sectors.rev().find(|sector| self.get(*sector).is_none()).unwrap_or(first);
+
+        let mut buf = [0; MAX_PREFETCH * Block::LEN];
+        let buf = &mut buf[..(last - first + 1) as usize * Block::LEN];
+        self.delegate.read_sectors(first, buf)?;
+        for (sector, chunk) in (first..).zip(buf.chunks(Block::LEN)) {
+            if self.get(sector).is_none() {
+                self.stats.prefetched += 1;
+                self.insert(sector, Block::from_slice(chunk), true);
+            }
+        }
         Ok(())
     }
 
     fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
         self.delegate.write(sector, buf)?;
-        if let Some(block) = self.get(sector) {
-            block.copy_from_slice(buf);
+        let tick = self.next_tick();
+        if let Some(entry) = self.get(sector) {
+            entry.last_used = tick;
+            entry.block.copy_from_slice(buf);
+        } else {
+            self.insert(sector, Block::from_slice(buf), true);
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::{MemoryDevice, MockDevice};
+    use std::vec::Vec;
+
+    fn read<const SIZE: usize>(cache: &mut BlockCache<MockDevice, SIZE>, sector: Addr) -> Block {
+        let mut block = Block::new();
+        cache.read(sector, &mut block).unwrap();
+        block
+    }
+
+    fn data(n: Addr) -> Addr {
+        DeviceLayout::DATA.nth(n)
+    }
+
+    fn device_reads<const SIZE: usize>(cache: BlockCache<MockDevice, SIZE>) -> Vec<Addr> {
+        cache.unmount().reads.iter().map(|(sector, _)| *sector).collect()
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = BlockCache::<_, 2>::mount(MockDevice::new());
+        for n in [1, 2, 1, 3, 1, 2] {
+            read(&mut cache, data(n));
+        }
+
+        assert_eq!(CacheStats { hits: 2, misses: 4, evictions: 2, prefetched: 0 }, cache.stats());
+        assert_eq!([data(1), data(2), data(3), data(2)][..], device_reads(cache));
+    }
+
+    #[test]
+    fn test_scan_keeps_metadata_and_reused_sectors() {
+        let mut cache = BlockCache::<_, 4>::mount(MockDevice::new());
+        let root = DeviceLayout::TREE.begin();
+        read(&mut cache, root);
+        read(&mut cache, data(0));
+        read(&mut cache, data(0));
+        for n in 1..20 {
+            read(&mut cache, data(n));
+        }
+        read(&mut cache, root);
+        read(&mut cache, data(0));
+
+        assert_eq!(CacheStats { hits: 3, misses: 21, evictions: 17, prefetched: 0 }, cache.stats());
+        assert_eq!(21, device_reads(cache).len(), "root and data(0) should be read once");
+    }
+
+    #[test]
+    fn test_protected_segment_is_bounded() {
+        let mut cache = BlockCache::<_, 4>::mount(MockDevice::new());
+        // Four metadata sectors, the least recently used one going back to probation.
+        for sector in 1..5 {
+            read(&mut cache, sector);
+        }
+        read(&mut cache, data(0));
+        read(&mut cache, 2);
+
+        assert_eq!(CacheStats { hits: 1, misses: 5, evictions: 1, prefetched: 0 }, cache.stats());
+        assert_eq!([1, 2, 3, 4, data(0)][..], device_reads(cache));
+    }
+
+    #[test]
+    fn test_written_sectors_are_cached_on_probation() {
+        let mut cache = BlockCache::<_, 2>::mount(MockDevice::new());
+        cache.write(data(0), &[7; Block::LEN]).unwrap();
+        assert_eq!([7; Block::LEN][..], read(&mut cache, data(0))[..]);
+        // Read once since written, data(0) is still the first to go.
+        read(&mut cache, data(1));
+        read(&mut cache, data(2));
+        read(&mut cache, data(0));
+
+        assert_eq!(CacheStats { hits: 1, misses: 3, evictions: 2, prefetched: 0 }, cache.stats());
+        assert_eq!([data(1), data(2), data(0)][..], device_reads(cache));
+    }
+
+    #[test]
+    fn test_size_zero_reads_through() {
+        let mut cache = BlockCache::<_, 0>::mount(MockDevice::new());
+        cache.write(1, &[7; Block::LEN]).unwrap();
+        read(&mut cache, 1);
+        assert_eq!([7; Block::LEN][..], read(&mut cache, 1)[..]);
+        assert_eq!(CacheStats { hits: 0, misses: 2, evictions: 0, prefetched: 0 }, cache.stats());
+        assert_eq!(2, cache.unmount().reads.len());
+    }
+
+    #[test]
+    fn test_prefetch_reads_missing_sectors_at_once() {
+        let mut cache = BlockCache::<_, 8>::mount(MemoryDevice::fit(data(20)));
+        let mut block = Block::new();
+        cache.read(data(2), &mut block).unwrap();
+        // Capped at half the cache, the cached sector in the middle is read again.
+        cache.prefetch(data(1), 100).unwrap();
+        cache.prefetch(data(1), 4).unwrap();
+        for n in 1..5 {
+            cache.read(data(n), &mut block).unwrap();
+        }
+
+        assert_eq!(CacheStats { hits: 4, misses: 1, evictions: 0, prefetched: 3 }, cache.stats());
+        assert_eq!(2, cache.unmount().reads_count);
+    }
+}
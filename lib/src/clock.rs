@@ -0,0 +1,45 @@
+//! Time source used to stamp files and directories.
+//!
+//! The filesystem does not assume a real-time clock is available. Devices without
+//! one can mount with [`NoClock`], and every timestamp is stored as 0.
+
+/// Seconds since the Unix epoch.
+pub type Timestamp = u32;
+
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Timestamp;
+}
+
+impl<C> Clock for &C
+where
+    C: Clock + ?Sized,
+{
+    fn now(&self) -> Timestamp {
+        (**self).now()
+    }
+}
+
+/// Clock for devices without a real-time clock, always returns 0.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoClock;
+
+impl Clock for NoClock {
+    fn now(&self) -> Timestamp {
+        0
+    }
+}
+
+/// Clock backed by the host system time.
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as u32)
+    }
+}
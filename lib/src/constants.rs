@@ -1,15 +1,38 @@
 /// Block size expected by the filesystem.
 pub const BLOCK_SIZE: usize = 512;
 
+/// Size in bytes of a device holding the whole layout, up to the last data block.
+pub const DEVICE_LEN: usize = crate::device_layout::DeviceLayout::DATA.end() as usize * BLOCK_SIZE;
+
 /// Maximum length of a file name in bytes.
-pub const NAME_LEN: usize = 45;
+pub const NAME_LEN: usize = 255;
+
+/// Length of a file name in bytes that can be stored inline in a directory entry.
+/// Longer names spill over to a dedicated data block.
+pub const INLINE_NAME_LEN: usize = 45;
+
+/// Maximum number of components in a path.
+pub const PATH_MAX_DEPTH: usize = 32;
+
+/// Maximum length of a path in bytes, once a symbolic link has been expanded into it.
+pub const PATH_LEN: usize = 1024;
+
+/// Maximum number of symbolic links followed while resolving a path.
+pub const MAX_SYMLINKS: usize = 8;
+
+/// Bytes of a node reserved for extended attributes, before they spill over to a data block.
+pub const INLINE_XATTR_LEN: usize = 64;
+
+/// Bytes of a node holding the data of small files, which then need no data block.
+/// Files outgrowing it are moved to data blocks.
+pub const INLINE_DATA_LEN: usize = 376;
 
 /// The number of data blocks a single file node can reference.
 /// This limits the maximum file size and is used for serialization, allocation, and layout.
 pub const NODE_DATA_BLOCKS_LEN: usize = 10;
 
 /// Entries that can fit in a directory tree node.
-pub const TREE_NODE_ENTRY_LEN: usize = 30;
+pub const TREE_NODE_ENTRY_LEN: usize = 29;
 
 /// The maximum file size (in bytes) that a single node can represent.
 pub const MAX_FILE_SIZE: usize = NODE_DATA_BLOCKS_LEN * BLOCK_SIZE;
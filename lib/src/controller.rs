@@ -1,92 +1,604 @@
-use core::fmt;
+use core::{fmt, ops::Range};
 
 use crate::{
-    BlockDevice, Error,
+    Addr, BlockDevice, Error, Metadata, TreeNode,
     allocator::{Allocator, DataAllocator},
-    block_cache::BlockCache,
+    block_cache::{self, BlockCache, CacheStats},
+    clock::{Clock, NoClock, Timestamp},
     constants,
     device_layout::DeviceLayout,
-    directory::{self, printer},
+    directory::{self, Caller, printer},
     file::File,
     file_handle::FileHandle,
+    io::Writer,
     meta::Meta,
     node::Node,
-    paths, storage,
+    paths::Path,
+    permissions::{Access, Identity, Permissions},
+    storage,
+    symlink::Symlink,
+    xattr::{self, XattrBlock},
 };
 
+/// Age in seconds past which opening a file updates its access time regardless.
+const ACCESSED_MAX_AGE: Timestamp = 24 * 60 * 60;
+
+/// Filesystem mounted on a device, reading it through a [`BlockCache`] of
+/// `CACHE_SIZE` sectors.
 #[derive(Debug)]
-pub struct Controller<D> {
-    device: BlockCache<D>,
+pub struct Controller<D, C = NoClock, const CACHE_SIZE: usize = { block_cache::DEFAULT_SIZE }> {
+    device: BlockCache<D, CACHE_SIZE>,
     data_allocator: Allocator,
     tree_allocator: Allocator,
+    node_allocator: Allocator,
+    clock: C,
+    /// Caller identity permissions are checked against, or `None` to skip checks.
+    identity: Option<Identity>,
 }
 
 impl<D> Controller<D>
 where
     D: BlockDevice,
 {
-    pub fn mount(mut device: D) -> Result<Self, Error> {
+    /// Mounts the device without a clock, all timestamps are stored as 0.
+    pub fn mount(device: D) -> Result<Self, Error> {
+        Self::mount_with_clock(device, NoClock)
+    }
+
+    pub fn format(device: &mut D) -> Result<(), Error> {
+        storage::store(device, 0, &Meta::new())?;
+        directory::format(device, &mut Allocator::new(DeviceLayout::TREE_BITMAP))?;
+        // Data block 0 is never handed out, its address marks holes in files.
+        Allocator::new(DeviceLayout::DATA_BITMAP).allocate(device)?;
+        Ok(())
+    }
+}
+
+impl<D, C> Controller<D, C>
+where
+    D: BlockDevice,
+    C: Clock,
+{
+    /// Mounts the device, files and directories are stamped with the time provided by `clock`.
+    pub fn mount_with_clock(device: D, clock: C) -> Result<Self, Error> {
+        Self::mount_with_cache(BlockCache::mount(device), clock)
+    }
+}
+
+impl<D, C, const CACHE_SIZE: usize> Controller<D, C, CACHE_SIZE>
+where
+    D: BlockDevice,
+    C: Clock,
+{
+    /// Mounts the device wrapped by `cache`, whose size sets how many sectors are kept
+    /// in memory, 0 disabling caching. Timestamps are provided by `clock`.
+    pub fn mount_with_cache(
+        mut device: BlockCache<D, CACHE_SIZE>,
+        clock: C,
+    ) -> Result<Self, Error> {
         let meta: Meta = storage::load(&mut device, 0)?;
-        let device = BlockCache::mount(device);
         if meta != Meta::new() {
             return Err(Error::UnsupportedDevice);
         }
         let data_allocator = Allocator::new(DeviceLayout::DATA_BITMAP);
         let tree_allocator = Allocator::new(DeviceLayout::TREE_BITMAP);
-        Ok(Self { device, data_allocator, tree_allocator })
+        let node_allocator = Allocator::new(DeviceLayout::NODE_BITMAP);
+        Ok(Self { device, data_allocator, tree_allocator, node_allocator, clock, identity: None })
+    }
+
+    /// Acts on behalf of `identity` from now on: new files and directories are owned by
+    /// it, and permissions are enforced on open, create and delete.
+    ///
+    /// Opening a file requires read access to it, while creating or deleting entries
+    /// requires write and execute access to the directories being modified.
+    #[must_use]
+    pub const fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    fn caller(&self) -> Caller {
+        Caller { now: self.clock.now(), identity: self.identity }
     }
 
     pub fn unmount(self) -> D {
         self.device.unmount()
     }
 
-    pub fn format(device: &mut D) -> Result<(), Error> {
-        storage::store(device, 0, &Meta::new())?;
-        directory::format(device, &mut Allocator::new(DeviceLayout::TREE_BITMAP))?;
-        Ok(())
+    /// Returns the hits, misses and evictions of the block cache since mounting.
+    pub const fn cache_stats(&self) -> CacheStats {
+        self.device.stats()
     }
 
     pub fn create(&mut self, file_path: &str, data: &[u8]) -> Result<(), Error>
     where
         D: BlockDevice,
     {
-        paths::validate(file_path)?;
+        let file_path = Path::parse(file_path)?;
 
         let file_size = data.len();
         if file_size > constants::MAX_FILE_SIZE {
             return Err(Error::FileTooLarge);
         }
 
-        let entry = directory::insert_file(&mut self.device, &mut self.tree_allocator, file_path)?;
+        let caller = self.caller();
+        let node_addr = self.node_allocator.allocate(&mut self.device)?;
+        // Small files are kept in the node, saving a data block and its bitmap update.
+        let node = if file_size <= constants::INLINE_DATA_LEN {
+            Node::new_inline(data)
+        } else {
+            match self.data_allocator.allocate_node_data(&mut self.device, data) {
+                Ok(node) => node,
+                Err(err) => {
+                    self.node_allocator.release(&mut self.device, node_addr)?;
+                    return Err(err);
+                }
+            }
+        }
+        .with_created(caller.now)
+        .with_permissions(Permissions::file(caller.identity));
+        // Store the contents before linking them, a crash in between leaks the node
+        // instead of exposing a file that is not fully written.
+        let entry = storage::store_data(&mut self.device, node.data_addrs(), data)
+            .and_then(|()| storage::store(&mut self.device, node_addr, &node))
+            .and_then(|()| {
+                directory::insert_file(
+                    &mut self.device,
+                    &mut self.tree_allocator,
+                    &mut self.data_allocator,
+                    &file_path,
+                    node_addr,
+                    &caller,
+                )
+            });
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                self.data_allocator.release_node_data(&mut self.device, &node)?;
+                self.node_allocator.release(&mut self.device, node_addr)?;
+                return Err(err);
+            }
+        };
         let file = File::new(*entry.name(), entry.addr());
-        let node = self.data_allocator.allocate_node_data(&mut self.device, file_size)?;
-        storage::store_data(&mut self.device, node.data_addrs(), data)?;
-        storage::store(&mut self.device, file.node_addr(), &node)?;
         storage::store(&mut self.device, file.node_addr(), &file)?;
         Ok(())
     }
 
+    /// Creates the file at `file_path` with the contents of `reader`, streamed a block
+    /// at a time instead of being buffered whole.
+    ///
+    /// The file is created empty then written, and is deleted again if `reader` fails
+    /// or the contents do not fit. The error of the copy is returned even when deleting
+    /// fails as well.
+    ///
+    /// # Errors
+    /// - [`Error::FileTooLarge`] if `reader` yields more than [`constants::MAX_FILE_SIZE`]
+    ///   bytes.
+    /// - [`Error::Io`] if `reader` fails.
+    #[cfg(feature = "std")]
+    pub fn create_from_reader<R>(&mut self, file_path: &str, mut reader: R) -> Result<(), Error>
+    where
+        D: BlockDevice,
+        R: std::io::Read,
+    {
+        self.create(file_path, &[])?;
+        let (node_addr, node) = self.load_file(file_path, Access::Write)?;
+        let mut file = self.handle(node_addr, node);
+        let mut buf = [0; crate::block::Block::LEN];
+        let copied = loop {
+            let len = match reader.read(&mut buf) {
+                Ok(0) => break Ok(()),
+                Ok(len) => len,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => break Err(err.into()),
+            };
+            if let Err(err) = file.write(&buf[..len]) {
+                break Err(err);
+            }
+        };
+        if copied.is_err() {
+            // The copy error explains the failure better, a failed delete only leaks the file.
+            let _ = self.delete(file_path);
+        }
+        copied
+    }
+
+    /// Creates a symbolic link at `link_path` pointing at `target`.
+    ///
+    /// The target does not need to exist, it is only resolved when a path goes
+    /// through the link. Relative targets are resolved from the directory holding
+    /// the link.
+    pub fn symlink(&mut self, target: &str, link_path: &str) -> Result<(), Error> {
+        let link_path = Path::parse(link_path)?;
+        let symlink = Symlink::new(target)?;
+        let caller = self.caller();
+        directory::insert_symlink(
+            &mut self.device,
+            &mut self.tree_allocator,
+            &mut self.data_allocator,
+            &link_path,
+            &symlink,
+            &caller,
+        )?;
+        Ok(())
+    }
+
+    /// Creates an empty directory at `dir_path`, along with any missing parent.
+    ///
+    /// The directory is kept once empty, while missing parents created along with it
+    /// are pruned once empty, like the ones created along with files.
+    pub fn mkdir(&mut self, dir_path: &str) -> Result<(), Error> {
+        let dir_path = Path::parse(dir_path)?;
+        let caller = self.caller();
+        directory::insert_dir(
+            &mut self.device,
+            &mut self.tree_allocator,
+            &mut self.data_allocator,
+            &dir_path,
+            &caller,
+        )?;
+        Ok(())
+    }
+
+    /// Moves the file, directory or symbolic link at `from` to `to`, creating any
+    /// missing parent directory. Existing entries at `to` are not replaced.
+    ///
+    /// Files are linked at `to` before being unlinked from `from`, and symbolic links
+    /// are copied before the original is deleted, so a failure halfway leaves both
+    /// paths. Directories are moved as a whole, removed from `from` before being
+    /// inserted at `to`, so power lost halfway leaks the directory instead of leaving
+    /// it at both paths.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let from_path = Path::parse(from)?;
+        let to_path = Path::parse(to)?;
+        let entry = directory::get_file(&mut self.device, &from_path, self.identity.as_ref())?;
+        if entry.is_file() {
+            self.link_node(entry.addr(), &to_path)?;
+            return self.delete(from);
+        }
+        if entry.is_symlink() {
+            let symlink: Symlink = storage::load(&mut self.device, entry.addr())?;
+            self.symlink(symlink.target(), to)?;
+            return self.delete(from);
+        }
+
+        let caller = self.caller();
+        directory::move_entry(
+            &mut self.device,
+            &mut self.tree_allocator,
+            &mut self.data_allocator,
+            &from_path,
+            &to_path,
+            &caller,
+        )?;
+        directory::prune(
+            &mut self.device,
+            &mut self.tree_allocator,
+            &mut self.data_allocator,
+            0,
+            caller.now,
+        )?;
+        Ok(())
+    }
+
+    /// Reads the target of the symbolic link at `link_path` into `out`, returning its length.
+    pub fn read_link(&mut self, link_path: &str, out: &mut [u8]) -> Result<usize, Error> {
+        let link_path = Path::parse(link_path)?;
+        let symlink = directory::read_link(&mut self.device, &link_path, self.identity.as_ref())?;
+        let target = symlink.target().as_bytes();
+        if out.len() < target.len() {
+            return Err(Error::BufferTooSmall { expected: target.len(), found: out.len() });
+        }
+        out[..target.len()].copy_from_slice(target);
+        Ok(target.len())
+    }
+
+    /// Creates a hard link at `new_path` to the file at `existing_path`, both paths
+    /// then share the same contents and metadata.
+    ///
+    /// Symbolic links are followed, and directories cannot be linked.
+    pub fn link(&mut self, existing_path: &str, new_path: &str) -> Result<(), Error> {
+        let existing_path = Path::parse(existing_path)?;
+        let new_path = Path::parse(new_path)?;
+
+        let entry =
+            directory::follow_file(&mut self.device, &existing_path, self.identity.as_ref())?;
+        if entry.is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        self.link_node(entry.addr(), &new_path)
+    }
+
+    /// Inserts an entry at `new_path` for the node at `node_addr`, counting the link.
+    fn link_node(&mut self, node_addr: Addr, new_path: &Path) -> Result<(), Error> {
+        let caller = self.caller();
+        let mut node: Node = storage::load(&mut self.device, node_addr)?;
+        node.add_link()?;
+        // Store the link count first, a crash in between leaks the node instead of
+        // releasing it while still linked.
+        storage::store(&mut self.device, node_addr, &node)?;
+        if let Err(err) = directory::insert_file(
+            &mut self.device,
+            &mut self.tree_allocator,
+            &mut self.data_allocator,
+            new_path,
+            node_addr,
+            &caller,
+        ) {
+            node.remove_link();
+            storage::store(&mut self.device, node_addr, &node)?;
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Deletes the file at `file_path`. Symbolic links are deleted, not their target.
+    ///
+    /// The file contents are only released once its last hard link is deleted.
     pub fn delete(&mut self, file_path: &str) -> Result<(), Error> {
-        paths::validate(file_path)?;
+        let file_path = Path::parse(file_path)?;
 
-        let entry = directory::get_file(&mut self.device, file_path)?;
-        let node: Node = storage::load(&mut self.device, entry.addr())?;
-        storage::erase::<_, Node>(&mut self.device, entry.addr())?;
-        storage::erase::<_, File>(&mut self.device, entry.addr())?;
-        directory::remove_file(&mut self.device, file_path)?;
-        directory::prune(&mut self.device, &mut self.tree_allocator, 0)?;
+        // Remove the entry first, a crash before the node is updated leaks it instead
+        // of releasing it while still linked.
+        let caller = self.caller();
+        let entry = directory::remove_file(
+            &mut self.device,
+            &mut self.data_allocator,
+            &file_path,
+            &caller,
+        )?;
+        let node = if entry.is_symlink() {
+            None
+        } else {
+            let mut node: Node = storage::load(&mut self.device, entry.addr())?;
+            if node.remove_link() > 0 {
+                storage::store(&mut self.device, entry.addr(), &node)?;
+                None
+            } else {
+                storage::erase::<_, Node>(&mut self.device, entry.addr())?;
+                storage::erase::<_, File>(&mut self.device, entry.addr())?;
+                Some((entry.addr(), node))
+            }
+        };
+        directory::prune(
+            &mut self.device,
+            &mut self.tree_allocator,
+            &mut self.data_allocator,
+            0,
+            caller.now,
+        )?;
 
         // Release data blocks only after metadata is fully erased.
-        self.data_allocator.release_node_data(&mut self.device, &node)?;
+        if let Some((node_addr, node)) = node {
+            self.data_allocator.release_node_data(&mut self.device, &node)?;
+            if let Some(xattr_addr) = node.xattr_addr() {
+                self.data_allocator.release(&mut self.device, xattr_addr)?;
+            }
+            self.node_allocator.release(&mut self.device, node_addr)?;
+        }
         Ok(())
     }
 
+    /// Opens the file at `file_path`, following symbolic links.
+    ///
+    /// Opening requires read access to the file, writing through the handle also
+    /// requires write access.
+    ///
+    /// The access time is only updated when it is not later than the modification
+    /// time, or more than a day old, so opening a file repeatedly does not write its
+    /// node each time.
     pub fn open(&mut self, file_path: &str) -> Result<FileHandle<'_>, Error> {
-        paths::validate(file_path)?;
+        let (node_addr, mut node) = self.load_file(file_path, Access::Read)?;
+        let now = self.clock.now();
+        let stale = node.accessed() <= node.modified()
+            || now.saturating_sub(node.accessed()) >= ACCESSED_MAX_AGE;
+        if stale && node.accessed() != now {
+            node.set_accessed(now);
+            storage::store(&mut self.device, node_addr, &node)?;
+        }
+        Ok(self.handle(node_addr, node))
+    }
+
+    /// Opens the file at `file_path` like [`Self::open`], without updating its access
+    /// time, like `O_NOATIME`. Reading through the handle then leaves the device
+    /// untouched, as when copying files out of an image.
+    pub fn open_noatime(&mut self, file_path: &str) -> Result<FileHandle<'_>, Error> {
+        let (node_addr, node) = self.load_file(file_path, Access::Read)?;
+        Ok(self.handle(node_addr, node))
+    }
+
+    /// Turns `range` of the file at `file_path` into a hole, which reads as zeros.
+    ///
+    /// Data blocks fully covered by `range` are released, the file length is unchanged.
+    pub fn punch_hole(&mut self, file_path: &str, range: Range<usize>) -> Result<(), Error> {
+        let (node_addr, node) = self.load_file(file_path, Access::Write)?;
+        self.handle(node_addr, node).punch_hole(range)
+    }
+
+    /// Sets the extended attribute `key` of the file at `file_path` to `value`.
+    ///
+    /// Small attributes are kept in the file node, the others are moved to a data
+    /// block allocated on demand.
+    pub fn set_xattr(&mut self, file_path: &str, key: &str, value: &[u8]) -> Result<(), Error> {
+        xattr::validate_key(key)?;
+        let (node_addr, mut node) = self.load_file(file_path, Access::Write)?;
+        let mut block = self.load_xattr_block(&node)?;
+
+        node.xattrs_mut().remove(key);
+        let mut block_dirty = block.remove(key);
+        if node.xattrs_mut().insert(key, value).is_err() {
+            block.insert(key, value)?;
+            block_dirty = true;
+        }
+        if block_dirty {
+            self.store_xattr_block(&mut node, &block)?;
+        }
+        storage::store(&mut self.device, node_addr, &node)
+    }
+
+    /// Reads the extended attribute `key` of the file at `file_path` into `out`,
+    /// returning its length.
+    pub fn get_xattr(
+        &mut self,
+        file_path: &str,
+        key: &str,
+        out: &mut [u8],
+    ) -> Result<usize, Error> {
+        let (_, node) = self.load_file(file_path, Access::Read)?;
+        let block = self.load_xattr_block(&node)?;
+        let value =
+            node.xattrs().get(key).or_else(|| block.get(key)).ok_or(Error::XattrNotFound)?;
+        if out.len() < value.len() {
+            return Err(Error::BufferTooSmall { expected: value.len(), found: out.len() });
+        }
+        out[..value.len()].copy_from_slice(value);
+        Ok(value.len())
+    }
+
+    /// Writes the keys of the extended attributes of the file at `file_path` into `out`,
+    /// each one followed by a NUL byte, returning the written length.
+    pub fn list_xattrs(&mut self, file_path: &str, out: &mut [u8]) -> Result<usize, Error> {
+        let (_, node) = self.load_file(file_path, Access::Read)?;
+        let block = self.load_xattr_block(&node)?;
+        let mut writer = Writer::new(out);
+        let mut len = 0;
+        for (key, _) in node.xattrs().iter().chain(block.iter()) {
+            len += writer.write(key)?;
+            len += writer.write(&[0])?;
+        }
+        Ok(len)
+    }
+
+    /// Removes the extended attribute `key` of the file at `file_path`.
+    pub fn remove_xattr(&mut self, file_path: &str, key: &str) -> Result<(), Error> {
+        let (node_addr, mut node) = self.load_file(file_path, Access::Write)?;
+        if !node.xattrs_mut().remove(key) {
+            let mut block = self.load_xattr_block(&node)?;
+            if !block.remove(key) {
+                return Err(Error::XattrNotFound);
+            }
+            self.store_xattr_block(&mut node, &block)?;
+        }
+        storage::store(&mut self.device, node_addr, &node)
+    }
 
-        let entry = directory::get_file(&mut self.device, file_path)?;
+    /// Loads the node of the file at `file_path`, following symbolic links, and checks
+    /// the caller is granted `access` to it.
+    fn load_file(&mut self, file_path: &str, access: Access) -> Result<(Addr, Node), Error> {
+        let file_path = Path::parse(file_path)?;
+
+        let entry = directory::follow_file(&mut self.device, &file_path, self.identity.as_ref())?;
+        if !entry.is_file() {
+            return Err(Error::FileNotFound);
+        }
         let node: Node = storage::load(&mut self.device, entry.addr())?;
-        Ok(FileHandle::new(&mut self.device, node))
+        node.permissions().check(self.identity.as_ref(), &[access])?;
+        Ok((entry.addr(), node))
+    }
+
+    fn handle(&mut self, node_addr: Addr, node: Node) -> FileHandle<'_> {
+        FileHandle::new(
+            &mut self.device,
+            &mut self.data_allocator,
+            &self.clock,
+            self.identity,
+            node_addr,
+            node,
+        )
+    }
+
+    fn load_xattr_block(&mut self, node: &Node) -> Result<XattrBlock, Error> {
+        match node.xattr_addr() {
+            Some(addr) => storage::load(&mut self.device, addr),
+            None => Ok(XattrBlock::new()),
+        }
+    }
+
+    /// Stores `block` for `node`, allocating it when needed, or releasing it once empty.
+    fn store_xattr_block(&mut self, node: &mut Node, block: &XattrBlock) -> Result<(), Error> {
+        match node.xattr_addr() {
+            Some(addr) if block.is_empty() => {
+                self.data_allocator.release(&mut self.device, addr)?;
+                node.set_xattr_addr(None);
+                Ok(())
+            }
+            Some(addr) => storage::store(&mut self.device, addr, block),
+            None if block.is_empty() => Ok(()),
+            None => {
+                let addr = self.data_allocator.allocate(&mut self.device)?;
+                node.set_xattr_addr(Some(addr));
+                storage::store(&mut self.device, addr, block)
+            }
+        }
+    }
+
+    /// Returns the [`Metadata`] of the file or directory at `path`, following symbolic links.
+    pub fn stat(&mut self, path: &str) -> Result<Metadata, Error> {
+        let path = Path::parse(path)?;
+
+        let addr = if path.is_root() {
+            0
+        } else {
+            let entry = directory::follow_file(&mut self.device, &path, self.identity.as_ref())?;
+            if !entry.is_dir() {
+                let node: Node = storage::load(&mut self.device, entry.addr())?;
+                return Ok(Metadata::file(entry.addr(), &node));
+            }
+            entry.addr()
+        };
+        let node: TreeNode = storage::load(&mut self.device, addr)?;
+        Ok(Metadata::dir(addr, &node))
+    }
+
+    /// Changes the mode of the file or directory at `path`, following symbolic links.
+    ///
+    /// Only the owner and the superuser may change the mode.
+    pub fn set_permissions(&mut self, path: &str, mode: u16) -> Result<(), Error> {
+        let identity = self.identity;
+        self.update_permissions(path, |permissions| {
+            if let Some(identity) = identity
+                && !identity.is_root()
+                && identity.uid() != permissions.uid()
+            {
+                return Err(Error::PermissionDenied);
+            }
+            Ok(permissions.with_mode(mode))
+        })
+    }
+
+    /// Changes the owner of the file or directory at `path`, following symbolic links.
+    ///
+    /// Only the superuser may change the owner.
+    pub fn chown(&mut self, path: &str, uid: u32, gid: u32) -> Result<(), Error> {
+        let identity = self.identity;
+        self.update_permissions(path, |permissions| {
+            if identity.is_some_and(|identity| !identity.is_root()) {
+                return Err(Error::PermissionDenied);
+            }
+            Ok(permissions.with_owner(uid, gid))
+        })
+    }
+
+    fn update_permissions<F>(&mut self, path: &str, update: F) -> Result<(), Error>
+    where
+        F: FnOnce(Permissions) -> Result<Permissions, Error>,
+    {
+        let path = Path::parse(path)?;
+
+        let addr = if path.is_root() {
+            0
+        } else {
+            let entry = directory::follow_file(&mut self.device, &path, self.identity.as_ref())?;
+            if !entry.is_dir() {
+                let mut node: Node = storage::load(&mut self.device, entry.addr())?;
+                node.set_permissions(update(*node.permissions())?);
+                return storage::store(&mut self.device, entry.addr(), &node);
+            }
+            entry.addr()
+        };
+        let mut node: TreeNode = storage::load(&mut self.device, addr)?;
+        node.set_permissions(update(*node.permissions())?);
+        storage::store(&mut self.device, addr, &node)
     }
 
     pub fn count_files(&mut self) -> Result<usize, Error> {
@@ -101,18 +613,31 @@ where
         self.data_allocator.count_free_addresses(&mut self.device)
     }
 
+    /// Calls `f` with the path and [`Metadata`] of every entry below the directory at
+    /// `base_path`, parents before their children. Paths are relative to `base_path`,
+    /// and symbolic links are reported without being followed.
+    ///
+    /// Walking stops at the first error returned by `f`.
+    pub fn walk<F>(&mut self, base_path: &str, f: F) -> Result<(), Error>
+    where
+        F: FnMut(&str, &Metadata) -> Result<(), Error>,
+    {
+        let base_path = Path::parse(base_path)?;
+        directory::walk(&mut self.device, &base_path, self.identity.as_ref(), f)
+    }
+
     pub fn print_tree<W>(&mut self, base_path: &str, depth: usize, out: &mut W) -> Result<(), Error>
     where
         W: fmt::Write,
     {
-        paths::validate(base_path)?;
-        printer::print_to(&mut self.device, base_path, depth, out)
+        let base_path = Path::parse(base_path)?;
+        printer::print_to(&mut self.device, &base_path, self.identity.as_ref(), depth, out)
     }
 
     #[cfg(feature = "std")]
     pub fn print_tree_std(&mut self, base_path: &str, depth: usize) -> Result<(), Error> {
-        paths::validate(base_path)?;
-        printer::print(&mut self.device, base_path, depth)
+        let base_path = Path::parse(base_path)?;
+        printer::print(&mut self.device, &base_path, self.identity.as_ref(), depth)
     }
 
     #[cfg(feature = "std")]
@@ -4,6 +4,7 @@ const N_TREE: usize = 100;
 const N_FILE: usize = N_TREE * TreeNode::LEN;
 const N_DATA: usize = constants::NODE_DATA_BLOCKS_LEN * N_FILE;
 const N_FREE: usize = N_DATA / Bitmap::SLOTS;
+const N_NODE_FREE: usize = N_FILE.div_ceil(Bitmap::SLOTS);
 
 #[derive(Debug, Clone, Copy)]
 pub struct DeviceLayout {
@@ -16,7 +17,8 @@ impl DeviceLayout {
     pub const META: Self = Self::new(0, 1);
     pub const TREE_BITMAP: Self = next(Self::META, 1, 1);
     pub const DATA_BITMAP: Self = next(Self::TREE_BITMAP, N_FREE, 1);
-    pub const TREE: Self = next(Self::DATA_BITMAP, N_TREE, TreeNode::BLOCKS_LEN);
+    pub const NODE_BITMAP: Self = next(Self::DATA_BITMAP, N_NODE_FREE, 1);
+    pub const TREE: Self = next(Self::NODE_BITMAP, N_TREE, TreeNode::BLOCKS_LEN);
     pub const FILE: Self = next(Self::TREE, N_FILE, 1);
     pub const NODE: Self = next(Self::FILE, N_FILE, 1);
     pub const DATA: Self = next(Self::NODE, N_DATA, 1);
@@ -29,6 +31,10 @@ impl DeviceLayout {
         self.begin
     }
 
+    pub const fn end(self) -> Addr {
+        self.end
+    }
+
     pub const fn new_with_size(begin: Addr, capacity: Addr, blocks_per_entry: Addr) -> Self {
         debug_assert!(blocks_per_entry > 0, "Entry size must be greater than zero");
 
@@ -88,6 +94,11 @@ pub fn print() {
         DeviceLayout::DATA_BITMAP,
         DeviceLayout::DATA_BITMAP.size_in_bytes()
     );
+    println!(
+        "  NodeBitmap: {:?} ({} bytes)",
+        DeviceLayout::NODE_BITMAP,
+        DeviceLayout::NODE_BITMAP.size_in_bytes()
+    );
     println!("  Tree: {:?} ({} bytes)", DeviceLayout::TREE, DeviceLayout::TREE.size_in_bytes());
     println!("  File: {:?} ({} bytes)", DeviceLayout::FILE, DeviceLayout::FILE.size_in_bytes());
     println!("  Node: {:?} ({} bytes)", DeviceLayout::NODE, DeviceLayout::NODE.size_in_bytes());
@@ -108,7 +119,8 @@ mod tests {
     fn layout_ranges_are_continuous() {
         assert_continuous_layout_range(DeviceLayout::META, DeviceLayout::TREE_BITMAP);
         assert_continuous_layout_range(DeviceLayout::TREE_BITMAP, DeviceLayout::DATA_BITMAP);
-        assert_continuous_layout_range(DeviceLayout::DATA_BITMAP, DeviceLayout::TREE);
+        assert_continuous_layout_range(DeviceLayout::DATA_BITMAP, DeviceLayout::NODE_BITMAP);
+        assert_continuous_layout_range(DeviceLayout::NODE_BITMAP, DeviceLayout::TREE);
         assert_continuous_layout_range(DeviceLayout::TREE, DeviceLayout::FILE);
         assert_continuous_layout_range(DeviceLayout::FILE, DeviceLayout::NODE);
         assert_continuous_layout_range(DeviceLayout::NODE, DeviceLayout::DATA);
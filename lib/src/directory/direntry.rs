@@ -23,6 +23,14 @@ impl DirEntry {
         matches!(self.kind, DirEntryKind::Dir)
     }
 
+    pub const fn is_file(&self) -> bool {
+        matches!(self.kind, DirEntryKind::File)
+    }
+
+    pub const fn is_symlink(&self) -> bool {
+        matches!(self.kind, DirEntryKind::Symlink)
+    }
+
     pub const fn kind(&self) -> DirEntryKind {
         self.kind
     }
@@ -35,8 +43,11 @@ impl DirEntry {
         self.addr
     }
 
+    /// Whether the entry is in use, empty entries have an empty name.
+    ///
+    /// The address cannot tell, as 0 is a valid address for entries pointing at data blocks.
     pub const fn is_set(&self) -> bool {
-        self.addr != 0
+        !self.name.is_empty()
     }
 }
 
@@ -66,6 +77,7 @@ impl Deserializable<Self> for DirEntry {
 pub enum DirEntryKind {
     File,
     Dir,
+    Symlink,
 }
 
 impl FixedLen for DirEntryKind {
@@ -77,6 +89,7 @@ impl Serializable for DirEntryKind {
         let kind_byte = match self {
             Self::File => 0,
             Self::Dir => 1,
+            Self::Symlink => 2,
         };
         writer.write_u8(kind_byte)?;
         Ok(1)
@@ -89,6 +102,7 @@ impl Deserializable<Self> for DirEntryKind {
         match byte {
             0 => Ok(Self::File),
             1 => Ok(Self::Dir),
+            2 => Ok(Self::Symlink),
             _ => Err(Error::UnsupportedDevice),
         }
     }
@@ -102,4 +116,10 @@ mod tests {
     use super::*;
 
     test_serde_symmetry!(DirEntry, DirEntry::new("test_file".into(), 1, DirEntryKind::File));
+
+    #[test]
+    fn test_is_set() {
+        assert!(!DirEntry::empty().is_set());
+        assert!(DirEntry::new("link".into(), 0, DirEntryKind::Symlink).is_set());
+    }
 }
@@ -1,14 +1,20 @@
-pub use direntry::DirEntry;
+pub use direntry::{DirEntry, DirEntryKind};
 pub use tree_node::TreeNode;
 
+use core::iter;
+
 use crate::{
-    Addr, BlockDevice, Error,
+    Addr, BlockDevice, Error, Metadata, Name,
     allocator::Allocator,
-    directory::{
-        direntry::DirEntryKind,
-        visitor::{CounterVisitor, Visitor},
-    },
-    paths, storage,
+    block::Block,
+    clock::Timestamp,
+    constants,
+    device_layout::DeviceLayout,
+    directory::visitor::{CounterVisitor, MetadataVisitor, PathVisitor, Visitor},
+    paths::{self, Path},
+    permissions::{Access, Identity, Permissions},
+    storage,
+    symlink::Symlink,
 };
 
 mod direntry;
@@ -16,6 +22,27 @@ pub mod printer;
 mod tree_node;
 mod visitor;
 
+/// Who is changing the tree and when.
+///
+/// Modified directories are stamped with `now`, and must grant write and execute
+/// access to `identity` when there is one. New directories are owned by `identity`.
+#[derive(Debug, Clone, Copy)]
+pub struct Caller {
+    pub now: Timestamp,
+    pub identity: Option<Identity>,
+}
+
+impl Caller {
+    fn check_write(&self, node: &TreeNode) -> Result<(), Error> {
+        node.permissions().check(self.identity.as_ref(), &[Access::Write, Access::Execute])
+    }
+}
+
+/// Checks `identity` may search the directory `node`, to look an entry up in it.
+fn check_search(node: &TreeNode, identity: Option<&Identity>) -> Result<(), Error> {
+    node.permissions().check(identity, &[Access::Execute])
+}
+
 pub fn format<D>(device: &mut D, allocator: &mut Allocator) -> Result<(), Error>
 where
     D: BlockDevice,
@@ -25,57 +52,299 @@ where
     Ok(())
 }
 
+/// Inserts a file entry at `file_path` pointing at the node at `node_addr`, creating
+/// any missing parent directories.
+///
+/// Directory nodes are taken from `tree_allocator`, while overflow blocks for
+/// long names are taken from `data_allocator`. Every modified directory is
+/// checked and stamped on behalf of `caller`.
 pub fn insert_file<D>(
     device: &mut D,
-    allocator: &mut Allocator,
-    file_path: &str,
+    tree_allocator: &mut Allocator,
+    data_allocator: &mut Allocator,
+    file_path: &Path,
+    node_addr: Addr,
+    caller: &Caller,
 ) -> Result<DirEntry, Error>
 where
     D: BlockDevice,
 {
-    insert_file_at(device, allocator, file_path, 0)
+    let components = file_path.components();
+    let leaf = Leaf::File(node_addr);
+    insert_at(device, tree_allocator, data_allocator, components, &leaf, caller, 0)
 }
 
-pub fn remove_file<D>(device: &mut D, file_path: &str) -> Result<(), Error>
+/// Inserts a symbolic link entry at `link_path`, creating any missing parent directories.
+///
+/// The target is stored in a block taken from `data_allocator`.
+pub fn insert_symlink<D>(
+    device: &mut D,
+    tree_allocator: &mut Allocator,
+    data_allocator: &mut Allocator,
+    link_path: &Path,
+    symlink: &Symlink,
+    caller: &Caller,
+) -> Result<DirEntry, Error>
 where
     D: BlockDevice,
 {
-    find_and_then(device, file_path, 0, |device, addr, parent, pos| {
-        *parent.get_mut(pos) = DirEntry::empty();
+    let components = link_path.components();
+    let leaf = Leaf::Symlink(symlink);
+    insert_at(device, tree_allocator, data_allocator, components, &leaf, caller, 0)
+}
+
+/// Inserts an empty directory at `dir_path`, creating any missing parent directories.
+///
+/// Unlike the directories created along with files, it is kept once empty.
+pub fn insert_dir<D>(
+    device: &mut D,
+    tree_allocator: &mut Allocator,
+    data_allocator: &mut Allocator,
+    dir_path: &Path,
+    caller: &Caller,
+) -> Result<DirEntry, Error>
+where
+    D: BlockDevice,
+{
+    let components = dir_path.components();
+    insert_at(device, tree_allocator, data_allocator, components, &Leaf::Dir, caller, 0)
+}
+
+/// Moves the entry at `from` to `to`, creating any missing parent directories of `to`.
+///
+/// The entry keeps pointing at the same file, directory or link target. It is removed
+/// from `from` before being inserted at `to`, and put back at `from` when that fails,
+/// so that no directory is ever referenced by two entries. Power lost in between
+/// leaves the moved directory unreferenced, leaked rather than shared.
+///
+/// # Errors
+/// - [`Error::FileAlreadyExists`] if there is already an entry at `to`.
+/// - [`Error::InvalidName`] if a directory would be moved inside itself.
+pub fn move_entry<D>(
+    device: &mut D,
+    tree_allocator: &mut Allocator,
+    data_allocator: &mut Allocator,
+    from: &Path,
+    to: &Path,
+    caller: &Caller,
+) -> Result<DirEntry, Error>
+where
+    D: BlockDevice,
+{
+    let identity = caller.identity.as_ref();
+    let entry = get_file(device, from, identity)?;
+    if entry.is_dir() {
+        // A directory cannot be moved inside itself, even through a symbolic link.
+        let [to_parent @ .., _] = to.components() else {
+            return Err(Error::InvalidName);
+        };
+        if to.components().starts_with(from.components())
+            || is_ancestor(device, entry.addr(), to_parent, identity)?
+        {
+            return Err(Error::InvalidName);
+        }
+    }
+    match get_file(device, to, identity) {
+        Ok(_) => return Err(Error::FileAlreadyExists),
+        Err(Error::FileNotFound) => {}
+        Err(err) => return Err(err),
+    }
+    let leaf = Leaf::Moved(entry.kind(), entry.addr());
+    detach(device, data_allocator, from, true, caller)?;
+    match insert_at(device, tree_allocator, data_allocator, to.components(), &leaf, caller, 0) {
+        Ok(moved) => Ok(moved),
+        Err(err) => {
+            insert_at(device, tree_allocator, data_allocator, from.components(), &leaf, caller, 0)?;
+            Err(err)
+        }
+    }
+}
+
+/// Returns whether the directory at `dir_addr` is the directory at `components`, or
+/// one of its parents, once symbolic links are resolved. Missing directories at the
+/// end of `components` are ignored.
+fn is_ancestor<D>(
+    device: &mut D,
+    dir_addr: Addr,
+    components: &[&str],
+    identity: Option<&Identity>,
+) -> Result<bool, Error>
+where
+    D: BlockDevice,
+{
+    for len in 1..=components.len() {
+        let found =
+            find_and_then(device, &components[..len], true, identity, 0, |_, _, parent, pos| {
+                Ok(parent.get(pos).clone())
+            });
+        match found {
+            Ok(entry) if entry.is_dir() && entry.addr() == dir_addr => return Ok(true),
+            Ok(entry) if entry.is_dir() => {}
+            Ok(_) | Err(Error::FileNotFound) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(false)
+}
+
+pub fn remove_file<D>(
+    device: &mut D,
+    data_allocator: &mut Allocator,
+    file_path: &Path,
+    caller: &Caller,
+) -> Result<DirEntry, Error>
+where
+    D: BlockDevice,
+{
+    let entry = detach(device, data_allocator, file_path, false, caller)?;
+    if entry.is_symlink() {
+        data_allocator.release(device, entry.addr())?;
+    }
+    Ok(entry)
+}
+
+/// Removes the entry at `path` from its directory, the entry target is left untouched.
+///
+/// Directories are only removed when `allow_dir` is set, otherwise [`Error::IsADirectory`]
+/// is returned.
+fn detach<D>(
+    device: &mut D,
+    data_allocator: &mut Allocator,
+    path: &Path,
+    allow_dir: bool,
+    caller: &Caller,
+) -> Result<DirEntry, Error>
+where
+    D: BlockDevice,
+{
+    let identity = caller.identity.as_ref();
+    find_and_then(device, path.components(), false, identity, 0, |device, addr, parent, pos| {
+        if !allow_dir && parent.get(pos).is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        caller.check_write(parent)?;
+        release_name(device, data_allocator, parent.get(pos).name())?;
+        let entry = parent.remove(pos);
+        parent.set_mtime(caller.now);
         storage::store(device, addr, parent)?;
-        Ok(())
+        Ok(entry)
     })
 }
 
-pub fn get_file<D>(device: &mut D, file_path: &str) -> Result<DirEntry, Error>
+/// Returns the entry at `file_path`, without following it when it is a symbolic link.
+///
+/// Every directory searched along the way must grant execute access to `identity`
+/// when there is one.
+pub fn get_file<D>(
+    device: &mut D,
+    file_path: &Path,
+    identity: Option<&Identity>,
+) -> Result<DirEntry, Error>
 where
     D: BlockDevice,
 {
-    find_and_then(device, file_path, 0, |_device, _addr, parent, pos| Ok(parent.get(pos).clone()))
+    let components = file_path.components();
+    find_and_then(device, components, false, identity, 0, |_device, _addr, parent, pos| {
+        Ok(parent.get(pos).clone())
+    })
+}
+
+/// Returns the entry at `file_path`, following it when it is a symbolic link.
+///
+/// Every directory searched along the way must grant execute access to `identity`
+/// when there is one.
+pub fn follow_file<D>(
+    device: &mut D,
+    file_path: &Path,
+    identity: Option<&Identity>,
+) -> Result<DirEntry, Error>
+where
+    D: BlockDevice,
+{
+    let components = file_path.components();
+    find_and_then(device, components, true, identity, 0, |_device, _addr, parent, pos| {
+        Ok(parent.get(pos).clone())
+    })
+}
+
+/// Returns the target of the symbolic link at `link_path`.
+pub fn read_link<D>(
+    device: &mut D,
+    link_path: &Path,
+    identity: Option<&Identity>,
+) -> Result<Symlink, Error>
+where
+    D: BlockDevice,
+{
+    let entry = get_file(device, link_path, identity)?;
+    if !entry.is_symlink() {
+        return Err(Error::NotASymlink);
+    }
+    storage::load(device, entry.addr())
+}
+
+/// Returns the address of the [`TreeNode`] for the directory at `dir_path`.
+pub fn find_dir<D>(
+    device: &mut D,
+    dir_path: &Path,
+    identity: Option<&Identity>,
+) -> Result<Addr, Error>
+where
+    D: BlockDevice,
+{
+    if dir_path.is_root() {
+        return Ok(0);
+    }
+    let components = dir_path.components();
+    find_and_then(device, components, true, identity, 0, |_device, _addr, parent, pos| {
+        let entry = parent.get(pos);
+        if !entry.is_dir() {
+            return Err(Error::DirectoryNotFound);
+        }
+        Ok(entry.addr())
+    })
 }
 
-pub fn prune<D>(device: &mut D, allocator: &mut Allocator, addr: Addr) -> Result<bool, Error>
+/// Removes the empty directories below the directory at `addr`, returning whether it
+/// is empty as well. The root directory and pinned directories are never reported as
+/// empty.
+///
+/// Pruned directories are released only once their parent no longer refers to them,
+/// so a failure in between leaks them instead of leaving a dangling entry.
+pub fn prune<D>(
+    device: &mut D,
+    tree_allocator: &mut Allocator,
+    data_allocator: &mut Allocator,
+    addr: Addr,
+    now: Timestamp,
+) -> Result<bool, Error>
 where
     D: BlockDevice,
 {
-    let mut current: TreeNode = storage::load(device, addr)?;
-    let mut dirty = false;
+    let mut current = load_node(device, addr)?;
+    // Directory and name overflow addresses of the pruned entries.
+    let mut pruned = [(0, None); TreeNode::LEN];
+    let mut n = 0;
     for entry in current.iter_entries_mut().filter(|entry| entry.is_dir()) {
-        if let Ok(pruned) = prune(device, allocator, entry.addr())
-            && pruned
-        {
+        if prune(device, tree_allocator, data_allocator, entry.addr(), now)? {
+            let name = entry.name();
+            pruned[n] = (entry.addr(), (!name.is_inline()).then(|| name.overflow_addr()));
+            n += 1;
             *entry = DirEntry::empty();
-            dirty = true;
         }
     }
-    if addr != 0 && current.iter_entries().count() == 0 {
-        allocator.release(device, addr)?;
-        return Ok(true);
-    }
-    if dirty {
+    if n > 0 {
+        current.sort();
+        current.set_mtime(now);
         storage::store(device, addr, &current)?;
+        for (dir_addr, overflow_addr) in &pruned[..n] {
+            tree_allocator.release(device, *dir_addr)?;
+            if let Some(overflow_addr) = overflow_addr {
+                data_allocator.release(device, *overflow_addr)?;
+            }
+        }
     }
-    Ok(false)
+    Ok(addr != 0 && !current.is_pinned() && current.iter_entries().count() == 0)
 }
 
 pub fn count_files<D>(device: &mut D) -> Result<usize, Error>
@@ -96,66 +365,312 @@ where
     Ok(counter.result())
 }
 
-fn insert_file_at<D>(
+/// Calls `f` with the path, relative to `base_path`, and the [`Metadata`] of every
+/// entry below the directory at `base_path`, parents before their children.
+pub fn walk<D, F>(
     device: &mut D,
-    allocator: &mut Allocator,
-    file_path: &str,
-    addr: Addr,
+    base_path: &Path,
+    identity: Option<&Identity>,
+    f: F,
+) -> Result<(), Error>
+where
+    D: BlockDevice,
+    F: FnMut(&str, &Metadata) -> Result<(), Error>,
+{
+    let addr = find_dir(device, base_path, identity)?;
+    MetadataVisitor(f).walk_from(device, addr)
+}
+
+/// Loads the [`TreeNode`] at `addr`. Long names keep only their inline prefix, see
+/// [`read_name`] and [`find_index`].
+fn load_node<D>(device: &mut D, addr: Addr) -> Result<TreeNode, Error>
+where
+    D: BlockDevice,
+{
+    storage::load(device, addr)
+}
+
+/// Returns the position of the entry named `name` in `node`. The overflow block of a
+/// long name is only read when its prefix and length match `name`.
+fn find_index<D>(device: &mut D, node: &TreeNode, name: &str) -> Result<Option<usize>, Error>
+where
+    D: BlockDevice,
+{
+    let mut block = Block::new();
+    node.find_index(name, |entry_name| {
+        device.read(DeviceLayout::DATA.nth(entry_name.overflow_addr()), &mut block)?;
+        Ok(&block[..name.len()] == name.as_bytes())
+    })
+}
+
+/// Copies the full `name` to the start of `buf`, reading its overflow block when it
+/// is not inline.
+fn read_name<D>(device: &mut D, name: &Name, buf: &mut [u8]) -> Result<(), Error>
+where
+    D: BlockDevice,
+{
+    if name.is_inline() {
+        buf[..name.len()].copy_from_slice(name.inline_bytes());
+        return Ok(());
+    }
+    let mut block = Block::new();
+    device.read(DeviceLayout::DATA.nth(name.overflow_addr()), &mut block)?;
+    buf[..name.len()].copy_from_slice(&block[..name.len()]);
+    Ok(())
+}
+
+/// What [`insert_at`] creates at the end of the path.
+enum Leaf<'a> {
+    File(Addr),
+    Symlink(&'a Symlink),
+    /// A new, empty directory.
+    Dir,
+    /// An existing entry moved from elsewhere in the tree.
+    Moved(DirEntryKind, Addr),
+}
+
+fn insert_at<D>(
+    device: &mut D,
+    tree_allocator: &mut Allocator,
+    data_allocator: &mut Allocator,
+    components: &[&str],
+    leaf: &Leaf,
+    caller: &Caller,
+    links: usize,
 ) -> Result<DirEntry, Error>
 where
     D: BlockDevice,
 {
-    let mut current: TreeNode = storage::load(device, addr)?;
-    if paths::dirname(file_path).is_empty() {
-        if current.find(file_path).is_some() {
-            return Err(Error::FileAlreadyExists);
+    let [dirs @ .., last_component] = components else {
+        return Err(Error::InvalidName);
+    };
+
+    let mut addr = 0;
+    for (i, component) in dirs.iter().enumerate() {
+        let mut current = load_node(device, addr)?;
+        check_search(&current, caller.identity.as_ref())?;
+        if let Some(pos) = find_index(device, &current, component)? {
+            let entry = current.get(pos);
+            if entry.is_symlink() {
+                let mut buf = [0u8; constants::PATH_LEN];
+                let path = expand_link(device, entry.addr(), components, i, links, &mut buf)?;
+                let components = path.components();
+                return insert_at(
+                    device,
+                    tree_allocator,
+                    data_allocator,
+                    components,
+                    leaf,
+                    caller,
+                    links + 1,
+                );
+            }
+            if !entry.is_dir() {
+                return Err(Error::NotADirectory);
+            }
+            addr = entry.addr();
+            continue;
         }
 
-        let entry = current.insert(file_path, addr, DirEntryKind::File);
-        storage::store(device, addr, &current)?;
-        return entry;
-    }
+        // If we reach here, it means we need to create a new directory entry for this component.
+        // First check if the current node can fit another child directory.
+        current.find_unset().ok_or(Error::StorageFull)?;
+        caller.check_write(&current)?;
+        let name = store_name(device, data_allocator, component)?;
+        let next_addr = match tree_allocator.allocate(device) {
+            Ok(next_addr) => next_addr,
+            Err(err) => {
+                release_name(device, data_allocator, &name)?;
+                return Err(err);
+            }
+        };
+        current.insert(name, next_addr, DirEntryKind::Dir)?;
+        current.set_mtime(caller.now);
 
-    let next_path = paths::tail(file_path);
-    let first_component = paths::first_component(file_path);
-    if let Some(entry) = current.find(first_component) {
-        return insert_file_at(device, allocator, next_path, entry.addr());
+        let entry = if i == dirs.len() - 1 { TreeNode::new_leaf() } else { TreeNode::new() };
+        let entry =
+            entry.with_mtime(caller.now).with_permissions(Permissions::dir(caller.identity));
+        let stored = storage::store(device, next_addr, &entry)
+            .and_then(|()| storage::store(device, addr, &current));
+        if let Err(err) = stored {
+            tree_allocator.release(device, next_addr)?;
+            release_name(device, data_allocator, &name)?;
+            return Err(err);
+        }
+        addr = next_addr;
     }
 
-    // If we reach here, it means we need to create a new directory entry for the first component.
-    // First check if the current node can fit another child directory.
-    current.find_unset().ok_or(Error::StorageFull)?;
-    let next_addr = allocator.allocate(device)?;
-    current.insert(first_component, next_addr, DirEntryKind::Dir)?;
+    let mut current = load_node(device, addr)?;
+    check_search(&current, caller.identity.as_ref())?;
+    if find_index(device, &current, last_component)?.is_some() {
+        return Err(Error::FileAlreadyExists);
+    }
 
-    let entry = if paths::dirname(paths::tail(file_path)).is_empty() {
-        TreeNode::new_leaf()
-    } else {
-        TreeNode::new()
+    current.find_unset().ok_or(Error::DirectoryFull)?;
+    caller.check_write(&current)?;
+    let name = store_name(device, data_allocator, last_component)?;
+    let (entry_addr, kind) = match leaf {
+        Leaf::File(node_addr) => (*node_addr, DirEntryKind::File),
+        Leaf::Symlink(symlink) => match store_symlink(device, data_allocator, symlink) {
+            Ok(symlink_addr) => (symlink_addr, DirEntryKind::Symlink),
+            Err(err) => {
+                release_name(device, data_allocator, &name)?;
+                return Err(err);
+            }
+        },
+        Leaf::Dir => match store_dir(device, tree_allocator, caller) {
+            Ok(dir_addr) => (dir_addr, DirEntryKind::Dir),
+            Err(err) => {
+                release_name(device, data_allocator, &name)?;
+                return Err(err);
+            }
+        },
+        Leaf::Moved(kind, addr) => (*addr, *kind),
     };
-    storage::store(device, next_addr, &entry)?;
-    storage::store(device, addr, &current)?;
-    insert_file_at(device, allocator, next_path, next_addr)
+    let entry = current.insert(name, entry_addr, kind)?;
+    current.set_mtime(caller.now);
+    if let Err(err) = storage::store(device, addr, &current) {
+        match leaf {
+            Leaf::Symlink(_) => data_allocator.release(device, entry_addr)?,
+            Leaf::Dir => tree_allocator.release(device, entry_addr)?,
+            Leaf::File(_) | Leaf::Moved(..) => {}
+        }
+        release_name(device, data_allocator, &name)?;
+        return Err(err);
+    }
+    Ok(entry)
+}
+
+fn store_symlink<D>(
+    device: &mut D,
+    data_allocator: &mut Allocator,
+    symlink: &Symlink,
+) -> Result<Addr, Error>
+where
+    D: BlockDevice,
+{
+    let addr = data_allocator.allocate(device)?;
+    if let Err(err) = storage::store(device, addr, symlink) {
+        data_allocator.release(device, addr)?;
+        return Err(err);
+    }
+    Ok(addr)
+}
+
+fn store_dir<D>(
+    device: &mut D,
+    tree_allocator: &mut Allocator,
+    caller: &Caller,
+) -> Result<Addr, Error>
+where
+    D: BlockDevice,
+{
+    let addr = tree_allocator.allocate(device)?;
+    let dir = TreeNode::new_leaf()
+        .with_pinned()
+        .with_mtime(caller.now)
+        .with_permissions(Permissions::dir(caller.identity));
+    if let Err(err) = storage::store(device, addr, &dir) {
+        tree_allocator.release(device, addr)?;
+        return Err(err);
+    }
+    Ok(addr)
+}
+
+/// Expands the symbolic link at `link_addr`, found at `components[i]`, into `buf`.
+///
+/// Returns the path to resolve instead of `components`, made of the link target
+/// followed by the components after the link. Relative targets are prefixed with
+/// the components of the directory holding the link.
+fn expand_link<'b, D>(
+    device: &mut D,
+    link_addr: Addr,
+    components: &[&str],
+    i: usize,
+    links: usize,
+    buf: &'b mut [u8],
+) -> Result<Path<'b>, Error>
+where
+    D: BlockDevice,
+{
+    if links >= constants::MAX_SYMLINKS {
+        return Err(Error::TooManyLinks);
+    }
+    let symlink: Symlink = storage::load(device, link_addr)?;
+    let parent = if symlink.is_absolute() { &[][..] } else { &components[..i] };
+    let parts = parent
+        .iter()
+        .copied()
+        .chain(iter::once(symlink.target()))
+        .chain(components[i + 1..].iter().copied());
+    Path::parse(paths::join(buf, parts)?)
+}
+
+/// Creates a [`Name`], storing the full name in an overflow block when it does not fit inline.
+fn store_name<D>(device: &mut D, data_allocator: &mut Allocator, name: &str) -> Result<Name, Error>
+where
+    D: BlockDevice,
+{
+    let stored = Name::new(name)?;
+    if stored.is_inline() {
+        return Ok(stored);
+    }
+    let addr = data_allocator.allocate(device)?;
+    if let Err(err) = storage::store_data(device, &[addr], name.as_bytes()) {
+        data_allocator.release(device, addr)?;
+        return Err(err);
+    }
+    Ok(stored.with_overflow_addr(addr))
+}
+
+fn release_name<D>(device: &mut D, data_allocator: &mut Allocator, name: &Name) -> Result<(), Error>
+where
+    D: BlockDevice,
+{
+    if name.is_inline() {
+        return Ok(());
+    }
+    data_allocator.release(device, name.overflow_addr())
 }
 
+/// Walks `components` from the root directory, and calls `cb` with the node holding
+/// the last component and its position.
+///
+/// Symbolic links in the middle of the path are always followed, while the last
+/// component is only followed when `follow_last` is set. Every directory searched
+/// must grant execute access to `identity` when there is one.
 fn find_and_then<F, R, D>(
     device: &mut D,
-    file_path: &str,
-    addr: Addr,
+    components: &[&str],
+    follow_last: bool,
+    identity: Option<&Identity>,
+    links: usize,
     mut cb: F,
 ) -> Result<R, Error>
 where
     D: BlockDevice,
     F: FnMut(&mut D, Addr, &mut TreeNode, usize) -> Result<R, Error>,
 {
-    let mut node: TreeNode = storage::load(device, addr)?;
-    let first_component = paths::first_component(file_path);
-    if let Some(pos) = node.find_index(first_component) {
-        let next_path = paths::tail(file_path);
-        if next_path == file_path {
+    let mut addr = 0;
+    for (i, component) in components.iter().enumerate() {
+        let mut node = load_node(device, addr)?;
+        check_search(&node, identity)?;
+        let pos = find_index(device, &node, component)?.ok_or(Error::FileNotFound)?;
+        let entry = node.get(pos);
+        let is_last = i == components.len() - 1;
+        if entry.is_symlink() && (follow_last || !is_last) {
+            let mut buf = [0u8; constants::PATH_LEN];
+            let path = expand_link(device, entry.addr(), components, i, links, &mut buf)?;
+            let components = path.components();
+            return find_and_then(device, components, follow_last, identity, links + 1, cb);
+        }
+        if is_last {
             return cb(device, addr, &mut node, pos);
         }
-        return find_and_then(device, next_path, node.get(pos).addr(), cb);
+        if !entry.is_dir() {
+            return Err(Error::NotADirectory);
+        }
+        addr = entry.addr();
     }
     Err(Error::FileNotFound)
 }
@@ -163,80 +678,316 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{device_layout::DeviceLayout, testutils::MemoryDevice};
-    use std::println;
+    use crate::{
+        constants,
+        testutils::{Fault, FaultyDevice, MemoryDevice, Operations, fsck},
+    };
+    use std::{format, println};
+
+    const CALLER: Caller = Caller { now: 0, identity: None };
 
-    const TREE_BITMAP: DeviceLayout = DeviceLayout::new(0, 1);
-    const TREE_LAYOUT: DeviceLayout = DeviceLayout::new_with_size(0, 10, TreeNode::LEN as u32);
+    pub(super) struct Sut {
+        pub device: MemoryDevice,
+        pub tree_allocator: Allocator,
+        pub data_allocator: Allocator,
+        /// Node address given to the next inserted file, nodes are not stored.
+        pub next_node_addr: Addr,
+    }
+
+    impl Sut {
+        pub fn insert_file(&mut self, file_path: &str) -> Result<DirEntry, Error> {
+            let entry = insert_file(
+                &mut self.device,
+                &mut self.tree_allocator,
+                &mut self.data_allocator,
+                &Path::parse(file_path)?,
+                self.next_node_addr,
+                &CALLER,
+            )?;
+            self.next_node_addr += 1;
+            Ok(entry)
+        }
+    }
 
-    pub(super) fn setup_tree() -> (MemoryDevice, Allocator) {
-        let mut device =
-            MemoryDevice::new(512, TREE_LAYOUT.size_in_bytes() + TREE_BITMAP.size_in_bytes());
-        let mut allocator = Allocator::new(TREE_LAYOUT);
-        format(&mut device, &mut allocator).expect("failed to format device");
-        (device, allocator)
+    pub(super) fn setup_tree() -> Sut {
+        // Fit a handful of data blocks, used by long names overflow.
+        let mut device = MemoryDevice::fit(DeviceLayout::DATA.nth(8));
+        let mut tree_allocator = Allocator::new(DeviceLayout::TREE_BITMAP);
+        let mut data_allocator = Allocator::new(DeviceLayout::DATA_BITMAP);
+        format(&mut device, &mut tree_allocator).expect("failed to format device");
+        // Data block 0 marks holes, like the controller does when formatting.
+        data_allocator.allocate(&mut device).expect("failed to reserve data block 0");
+        Sut { device, tree_allocator, data_allocator, next_node_addr: 100 }
     }
 
-    fn find_entry_addr<D: BlockDevice>(
-        device: &mut D,
-        file_path: &str,
-        addr: Addr,
-    ) -> Result<Addr, Error> {
-        find_and_then(device, file_path, addr, |_device, _addr, parent, pos| {
-            Ok(parent.get(pos).addr())
-        })
+    fn find_entry_addr<D: BlockDevice>(device: &mut D, file_path: &str) -> Result<Addr, Error> {
+        let file_path = Path::parse(file_path)?;
+        find_and_then(
+            device,
+            file_path.components(),
+            false,
+            None,
+            0,
+            |_device, _addr, parent, pos| Ok(parent.get(pos).addr()),
+        )
     }
 
     #[test]
     fn test_find_addr_for_path_root() {
-        let (mut device, _) = setup_tree();
-        assert_eq!(Ok(0), find_entry_addr(&mut device, "", 0));
+        let mut sut = setup_tree();
+        assert_eq!(Ok(0), find_dir(&mut sut.device, &Path::parse("").unwrap(), None));
+        assert_eq!(Err(Error::FileNotFound), find_entry_addr(&mut sut.device, ""));
     }
 
     #[test]
     fn test_find_addr_for_path_missing() {
-        let (mut device, _) = setup_tree();
+        let mut sut = setup_tree();
         assert_eq!(
             Err(Error::FileNotFound),
-            find_entry_addr(&mut device, "missing/path/file.txt", 0)
+            find_entry_addr(&mut sut.device, "missing/path/file.txt")
         );
     }
 
     #[test]
     fn test_find_addr_for_path_found() {
-        let (mut device, mut allocator) = setup_tree();
-        insert_file(&mut device, &mut allocator, "some/path/file.txt").expect("cannot insert file");
-        assert_eq!(Ok(0), find_entry_addr(&mut device, "", 0));
-        assert_eq!(Ok(1), find_entry_addr(&mut device, "some", 0));
-        assert_eq!(Ok(2), find_entry_addr(&mut device, "some/path", 0));
-        assert_eq!(Ok(2), find_entry_addr(&mut device, "some/path/file.txt", 0));
+        let mut sut = setup_tree();
+        sut.insert_file("some/path/file.txt").expect("cannot insert file");
+        assert_eq!(Ok(1), find_entry_addr(&mut sut.device, "some"));
+        assert_eq!(Ok(2), find_entry_addr(&mut sut.device, "some/path"));
+        assert_eq!(Ok(100), find_entry_addr(&mut sut.device, "some/path/file.txt"));
+    }
+
+    #[test]
+    fn test_long_names() {
+        let mut sut = setup_tree();
+        let dir = "d".repeat(constants::NAME_LEN);
+        let file = "f".repeat(constants::INLINE_NAME_LEN + 1);
+        let file_path = format!("{dir}/{file}");
+
+        let free_blocks = sut.data_allocator.count_free_addresses(&mut sut.device).unwrap();
+        let entry = sut.insert_file(&file_path).expect("cannot insert file");
+        let mut name = [0u8; constants::NAME_LEN];
+        read_name(&mut sut.device, entry.name(), &mut name).expect("cannot read name");
+        assert_eq!(file.as_bytes(), &name[..entry.name().len()]);
+        assert_eq!(Ok(1), find_entry_addr(&mut sut.device, &dir));
+        assert_eq!(Ok(entry), get_file(&mut sut.device, &Path::parse(&file_path).unwrap(), None));
+        assert_eq!(
+            Ok(free_blocks - 2),
+            sut.data_allocator.count_free_addresses(&mut sut.device),
+            "both names should take an overflow block"
+        );
+
+        remove_file(
+            &mut sut.device,
+            &mut sut.data_allocator,
+            &Path::parse(&file_path).unwrap(),
+            &CALLER,
+        )
+        .unwrap();
+        prune(&mut sut.device, &mut sut.tree_allocator, &mut sut.data_allocator, 0, 0).unwrap();
+        assert_eq!(Err(Error::FileNotFound), find_entry_addr(&mut sut.device, &dir));
+        assert_eq!(Ok(free_blocks), sut.data_allocator.count_free_addresses(&mut sut.device));
+    }
+
+    impl Sut {
+        fn insert_symlink(&mut self, target: &str, link_path: &str) -> Result<DirEntry, Error> {
+            insert_symlink(
+                &mut self.device,
+                &mut self.tree_allocator,
+                &mut self.data_allocator,
+                &Path::parse(link_path)?,
+                &Symlink::new(target)?,
+                &CALLER,
+            )
+        }
+
+        fn follow_file(&mut self, file_path: &str) -> Result<DirEntry, Error> {
+            follow_file(&mut self.device, &Path::parse(file_path)?, None)
+        }
+    }
+
+    #[test]
+    fn test_symlink_intermediate_component() {
+        let mut sut = setup_tree();
+        let file = sut.insert_file("some/path/file.txt").unwrap();
+        sut.insert_symlink("some/path", "link").unwrap();
+        sut.insert_symlink("/some/path", "other/abs").unwrap();
+        sut.insert_symlink("../some/path", "other/rel").unwrap();
+
+        assert_eq!(Ok(&file), sut.follow_file("link/file.txt").as_ref());
+        assert_eq!(Ok(&file), sut.follow_file("other/abs/file.txt").as_ref());
+        assert_eq!(Ok(&file), sut.follow_file("other/rel/file.txt").as_ref());
+        assert_eq!(Ok(2), find_dir(&mut sut.device, &Path::parse("other/rel").unwrap(), None));
+    }
+
+    #[test]
+    fn test_symlink_last_component() {
+        let mut sut = setup_tree();
+        let file = sut.insert_file("some/file.txt").unwrap();
+        let link = sut.insert_symlink("file.txt", "some/link").unwrap();
+
+        assert_eq!(Ok(&file), sut.follow_file("some/link").as_ref());
+        assert_eq!(Ok(link), get_file(&mut sut.device, &Path::parse("some/link").unwrap(), None));
+        assert_eq!(
+            Ok("file.txt"),
+            read_link(&mut sut.device, &Path::parse("some/link").unwrap(), None)
+                .as_ref()
+                .map(Symlink::target)
+        );
+        assert_eq!(
+            Err(Error::NotASymlink),
+            read_link(&mut sut.device, &Path::parse("some/file.txt").unwrap(), None)
+        );
+    }
+
+    #[test]
+    fn test_symlink_dangling() {
+        let mut sut = setup_tree();
+        sut.insert_symlink("missing", "link").unwrap();
+        assert_eq!(Err(Error::FileNotFound), sut.follow_file("link"));
+        assert_eq!(Err(Error::FileNotFound), sut.follow_file("link/file.txt"));
+    }
+
+    #[test]
+    fn test_symlink_loop() {
+        let mut sut = setup_tree();
+        sut.insert_symlink("b", "a").unwrap();
+        sut.insert_symlink("a", "b").unwrap();
+        sut.insert_symlink("self/next", "self").unwrap();
+        assert_eq!(Err(Error::TooManyLinks), sut.follow_file("a"));
+        assert_eq!(Err(Error::TooManyLinks), sut.follow_file("self/file.txt"));
+        assert_eq!(Err(Error::TooManyLinks), sut.insert_file("a/file.txt"));
+    }
+
+    #[test]
+    fn test_insert_through_symlink() {
+        let mut sut = setup_tree();
+        sut.insert_file("some/path/old.txt").unwrap();
+        sut.insert_symlink("some/path", "link").unwrap();
+
+        let file = sut.insert_file("link/new/file.txt").unwrap();
+        assert_eq!(Ok(&file), sut.follow_file("some/path/new/file.txt").as_ref());
+        assert_eq!(Err(Error::FileAlreadyExists), sut.insert_file("link"));
+    }
+
+    #[test]
+    fn test_file_as_parent() {
+        let mut sut = setup_tree();
+        sut.insert_file("some/file.txt").unwrap();
+        assert_eq!(Err(Error::NotADirectory), sut.insert_file("some/file.txt/nested.txt"));
+        assert_eq!(Err(Error::NotADirectory), sut.follow_file("some/file.txt/nested.txt"));
+        assert_eq!(1, count_dirs(&mut sut.device).unwrap());
+    }
+
+    #[test]
+    fn test_remove_symlink() {
+        let mut sut = setup_tree();
+        let free_blocks = sut.data_allocator.count_free_addresses(&mut sut.device).unwrap();
+        let file = sut.insert_file("some/file.txt").unwrap();
+        sut.insert_symlink("some/file.txt", "link").unwrap();
+        assert_eq!(
+            Ok(free_blocks - 1),
+            sut.data_allocator.count_free_addresses(&mut sut.device),
+            "the target should take a data block"
+        );
+
+        remove_file(
+            &mut sut.device,
+            &mut sut.data_allocator,
+            &Path::parse("link").unwrap(),
+            &CALLER,
+        )
+        .unwrap();
+        assert_eq!(Err(Error::FileNotFound), sut.follow_file("link"));
+        assert_eq!(Ok(file), sut.follow_file("some/file.txt"));
+        assert_eq!(Ok(free_blocks), sut.data_allocator.count_free_addresses(&mut sut.device));
+    }
+
+    #[test]
+    fn test_prune_failure_releases_nothing_still_linked() {
+        let mut sut = setup_tree();
+        let file_path = Path::parse("a/b/file.txt").unwrap();
+        sut.insert_file("a/b/file.txt").unwrap();
+        remove_file(&mut sut.device, &mut sut.data_allocator, &file_path, &CALLER).unwrap();
+
+        let mut device = FaultyDevice::new(sut.device.clone())
+            .with_fault(Fault::Sector(DeviceLayout::TREE.nth(0)))
+            .failing(Operations::Writes);
+        assert_eq!(
+            Err(Error::Io),
+            prune(&mut device, &mut sut.tree_allocator, &mut sut.data_allocator, 0, 0)
+        );
+
+        let mut device = device.into_inner();
+        let report = fsck(&mut device).unwrap();
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!(1, count_dirs(&mut device).unwrap(), "a should be left empty in the root");
+    }
+
+    #[test]
+    fn test_long_name_write_failure_releases_overflow_block() {
+        let mut sut = setup_tree();
+        let free_blocks = sut.data_allocator.count_free_addresses(&mut sut.device).unwrap();
+
+        // Data block 1 is the first one allocated, for the overflow of the name.
+        let mut device = FaultyDevice::new(sut.device.clone())
+            .with_fault(Fault::Sector(DeviceLayout::DATA.nth(1)))
+            .failing(Operations::Writes);
+        let name = "f".repeat(constants::NAME_LEN);
+        let file_path = Path::parse(&name).unwrap();
+        assert_eq!(
+            Err(Error::Io),
+            insert_file(
+                &mut device,
+                &mut sut.tree_allocator,
+                &mut sut.data_allocator,
+                &file_path,
+                100,
+                &CALLER,
+            )
+        );
+
+        let mut device = device.into_inner();
+        assert_eq!(Ok(free_blocks), sut.data_allocator.count_free_addresses(&mut device));
+        assert!(fsck(&mut device).unwrap().is_clean());
     }
 
     #[test]
     fn multiple_tree_ops() {
-        let (mut device, mut allocator) = setup_tree();
+        let mut sut = setup_tree();
+        let device = &mut sut.device;
+        let root = Path::root();
+        let file_path = Path::parse("/dir/second/third/file.txt").unwrap();
         println!("tree before insertion:");
-        printer::print(&mut device, "", 0).unwrap();
-        assert_eq!(0, count_dirs(&mut device).unwrap());
+        printer::print(device, &root, None, 0).unwrap();
+        assert_eq!(0, count_dirs(device).unwrap());
 
-        let _ = insert_file(&mut device, &mut allocator, "dir/second/third/file.txt").unwrap();
+        let _ = insert_file(
+            device,
+            &mut sut.tree_allocator,
+            &mut sut.data_allocator,
+            &file_path,
+            100,
+            &CALLER,
+        )
+        .unwrap();
         println!("tree after insertion:");
-        printer::print(&mut device, "", 0).unwrap();
-        assert_eq!(3, count_dirs(&mut device).unwrap());
+        printer::print(device, &root, None, 0).unwrap();
+        assert_eq!(3, count_dirs(device).unwrap());
 
-        let _ = get_file(&mut device, "dir/second/third/file.txt").unwrap();
-        remove_file(&mut device, "/dir/second/third/file.txt").unwrap();
+        let _ = get_file(device, &file_path, None).unwrap();
+        remove_file(device, &mut sut.data_allocator, &file_path, &CALLER).unwrap();
         println!("tree after removal:");
-        printer::print(&mut device, "", 0).unwrap();
+        printer::print(device, &root, None, 0).unwrap();
+
+        assert_eq!(Error::FileNotFound, get_file(device, &file_path, None).unwrap_err());
 
         assert_eq!(
-            Error::FileNotFound,
-            get_file(&mut device, "/dir/second/third/file.txt").unwrap_err()
+            Ok(false),
+            prune(device, &mut sut.tree_allocator, &mut sut.data_allocator, 0, 0)
         );
-
-        assert_eq!(Ok(false), prune(&mut device, &mut allocator, 0));
         println!("tree after prune:");
-        printer::print(&mut device, "", 0).unwrap();
-        assert_eq!(0, count_dirs(&mut device).unwrap());
+        printer::print(device, &root, None, 0).unwrap();
+        assert_eq!(0, count_dirs(device).unwrap());
     }
 }
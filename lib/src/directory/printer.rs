@@ -1,10 +1,18 @@
 use core::fmt;
 
-use crate::{Addr, BlockDevice, Error, TreeNode, directory::find_and_then, storage};
+use crate::{
+    Addr, BlockDevice, Error, constants,
+    directory::{DirEntry, find_dir, load_node, read_name},
+    paths::Path,
+    permissions::Identity,
+    storage,
+    symlink::Symlink,
+};
 
 pub fn print_to<D, W>(
     device: &mut D,
-    base_path: &str,
+    base_path: &Path,
+    identity: Option<&Identity>,
     depth: usize,
     out: &mut W,
 ) -> Result<(), Error>
@@ -12,26 +20,25 @@ where
     D: BlockDevice,
     W: fmt::Write,
 {
-    find_and_then(device, base_path, 0, |device, _addr, node, pos| {
-        let entry = node.get(pos);
-        if !entry.is_dir() {
-            return Err(Error::DirectoryNotFound);
-        }
-        print_in_order(device, entry.addr(), depth, 0, out)?;
-        Ok(())
-    })
+    let addr = find_dir(device, base_path, identity)?;
+    print_in_order(device, addr, depth, 0, out)
 }
 
 #[cfg(feature = "std")]
-pub fn print<D>(device: &mut D, base_path: &str, depth: usize) -> Result<(), Error>
+pub fn print<D>(
+    device: &mut D,
+    base_path: &Path,
+    identity: Option<&Identity>,
+    depth: usize,
+) -> Result<(), Error>
 where
     D: BlockDevice,
 {
-    use std::{println, string::String};
+    use crate::io::StdoutFmtWriter;
+    use fmt::Write as _;
 
-    let mut txt = String::new();
-    print_to(device, base_path, depth, &mut txt)?;
-    println!("{txt}");
+    print_to(device, base_path, identity, depth, &mut StdoutFmtWriter)?;
+    StdoutFmtWriter.write_str("\n")?;
     Ok(())
 }
 
@@ -55,17 +62,35 @@ where
             out.write_str("../\n")?;
         }
     }
-    let node: TreeNode = storage::load(device, addr)?;
+    let node = load_node(device, addr)?;
+    let indent = 2 * (depth + 1);
+    let mut buf = [0u8; constants::NAME_LEN];
     for entry in node.iter_entries().filter(|entry| entry.is_dir()) {
-        out.write_fmt(format_args!("{}{}/\n", "  ".repeat(depth + 1), entry.name().as_str()))?;
+        let name = entry_name(device, entry, &mut buf)?;
+        out.write_fmt(format_args!("{:indent$}{name}/\n", ""))?;
         print_in_order(device, entry.addr(), max_depth, depth + 1, out)?;
     }
     for entry in node.iter_entries().filter(|e| !e.is_dir()) {
-        out.write_fmt(format_args!("{}{}\n", "  ".repeat(depth + 1), entry.name().as_str()))?;
+        let name = entry_name(device, entry, &mut buf)?;
+        out.write_fmt(format_args!("{:indent$}{name}", ""))?;
+        if entry.is_symlink() {
+            let symlink: Symlink = storage::load(device, entry.addr())?;
+            out.write_fmt(format_args!(" -> {}", symlink.target()))?;
+        }
+        out.write_str("\n")?;
     }
     Ok(())
 }
 
+fn entry_name<'b, D>(device: &mut D, entry: &DirEntry, buf: &'b mut [u8]) -> Result<&'b str, Error>
+where
+    D: BlockDevice,
+{
+    let name = entry.name();
+    read_name(device, name, buf)?;
+    str::from_utf8(&buf[..name.len()]).map_err(|_| Error::Unexpected)
+}
+
 #[cfg(test)]
 mod tests {
     use std::string::String;
@@ -76,21 +101,22 @@ mod tests {
 
     fn assert_empty_print<D: BlockDevice>(device: &mut D) {
         let mut out = String::new();
-        assert_eq!(Ok(()), print_to(device, "", 0, &mut out));
+        assert_eq!(Ok(()), print_to(device, &Path::parse("").unwrap(), None, 0, &mut out));
         assert_eq!("$/\n", &out);
     }
 
     #[test]
     fn test_print_tree() {
-        let (mut device, mut allocator) = setup_tree();
-        assert_empty_print(&mut device);
+        let mut sut = setup_tree();
+        assert_empty_print(&mut sut.device);
 
-        directory::insert_file(&mut device, &mut allocator, "dir1/dir2/old.txt")
-            .expect("should insert file");
-        directory::insert_file(&mut device, &mut allocator, "dir1/dir2/dir3/file.txt")
-            .expect("shoud insert file");
+        sut.insert_file("dir1/dir2/old.txt").expect("should insert file");
+        sut.insert_file("dir1/dir2/dir3/file.txt").expect("shoud insert file");
         let mut actual = String::new();
-        assert_eq!(Ok(()), print_to(&mut device, "", 0, &mut actual));
+        assert_eq!(
+            Ok(()),
+            print_to(&mut sut.device, &Path::parse("").unwrap(), None, 0, &mut actual)
+        );
         let expected = "$/
   dir1/
     dir2/
@@ -101,14 +127,48 @@ mod tests {
         assert_eq!(expected, &actual);
     }
 
+    #[test]
+    fn test_print_tree_symlinks() {
+        let mut sut = setup_tree();
+        sut.insert_file("dir1/file.txt").expect("should insert file");
+        sut.insert_file("dir2/file.txt").expect("should insert file");
+        let link_path = Path::parse("dir2/link").unwrap();
+        directory::insert_symlink(
+            &mut sut.device,
+            &mut sut.tree_allocator,
+            &mut sut.data_allocator,
+            &link_path,
+            &Symlink::new("../dir1/file.txt").unwrap(),
+            &directory::Caller { now: 0, identity: None },
+        )
+        .expect("should insert symlink");
+
+        let mut actual = String::new();
+        assert_eq!(
+            Ok(()),
+            print_to(&mut sut.device, &Path::parse("").unwrap(), None, 0, &mut actual)
+        );
+        let expected = "$/
+  dir1/
+    file.txt
+  dir2/
+    file.txt
+    link -> ../dir1/file.txt
+";
+        assert_eq!(expected, &actual);
+    }
+
     #[test]
     fn test_print_tree_relative() {
-        let (mut device, mut allocator) = setup_tree();
-        assert_empty_print(&mut device);
+        let mut sut = setup_tree();
+        assert_empty_print(&mut sut.device);
 
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir2/dir3/file.txt");
+        let _ = sut.insert_file("dir1/dir2/dir3/file.txt");
         let mut actual = String::new();
-        assert_eq!(Ok(()), print_to(&mut device, "dir1/dir2", 0, &mut actual));
+        assert_eq!(
+            Ok(()),
+            print_to(&mut sut.device, &Path::parse("dir1/dir2").unwrap(), None, 0, &mut actual)
+        );
         let expected = "../
   dir3/
     file.txt
@@ -118,15 +178,18 @@ mod tests {
 
     #[test]
     fn test_print_tree_relative_and_max_depth() {
-        let (mut device, mut allocator) = setup_tree();
-        assert_empty_print(&mut device);
+        let mut sut = setup_tree();
+        assert_empty_print(&mut sut.device);
 
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir2/dir3/file.txt");
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir3/file.txt");
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir3/dir4/dir5/file.txt");
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/file.txt");
+        let _ = sut.insert_file("dir1/dir2/dir3/file.txt");
+        let _ = sut.insert_file("dir1/dir3/file.txt");
+        let _ = sut.insert_file("dir1/dir3/dir4/dir5/file.txt");
+        let _ = sut.insert_file("dir1/file.txt");
         let mut actual = String::new();
-        assert_eq!(Ok(()), print_to(&mut device, "dir1", 2, &mut actual));
+        assert_eq!(
+            Ok(()),
+            print_to(&mut sut.device, &Path::parse("dir1").unwrap(), None, 2, &mut actual)
+        );
         let expected = "../
   dir2/
     dir3/
@@ -140,16 +203,17 @@ mod tests {
 
     #[test]
     fn test_print_file_fails() {
-        let (mut device, mut allocator) = setup_tree();
-        assert_empty_print(&mut device);
+        let mut sut = setup_tree();
+        assert_empty_print(&mut sut.device);
 
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir2/dir3/file.txt");
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir3/file.txt");
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/dir3/dir4/dir5/file.txt");
-        let _ = directory::insert_file(&mut device, &mut allocator, "dir1/file.txt");
+        let _ = sut.insert_file("dir1/dir2/dir3/file.txt");
+        let _ = sut.insert_file("dir1/dir3/file.txt");
+        let _ = sut.insert_file("dir1/dir3/dir4/dir5/file.txt");
+        let _ = sut.insert_file("dir1/file.txt");
 
         let mut out = String::new();
-        let result = print_to(&mut device, "dir1/file.txt", 0, &mut out);
+        let result =
+            print_to(&mut sut.device, &Path::parse("dir1/file.txt").unwrap(), None, 0, &mut out);
         assert_eq!(Err(Error::DirectoryNotFound), result);
     }
 }
@@ -1,12 +1,21 @@
 use crate::{
-    Addr, Deserializable, DeviceAddr, DeviceLayout, Error, FixedLen, Name, Serializable, constants,
+    Addr, Deserializable, DeviceAddr, DeviceLayout, Error, FixedLen, Name, Serializable,
+    clock::Timestamp,
+    constants,
     directory::direntry::{DirEntry, DirEntryKind},
     io::{Read, Write},
+    permissions::Permissions,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TreeNode {
     entries: [DirEntry; Self::LEN],
+    /// Last time an entry was added or removed.
+    mtime: Timestamp,
+    permissions: Permissions,
+    /// Whether the directory was made on its own, rather than as the parent of an
+    /// entry, in which case it is kept once empty.
+    pinned: bool,
 }
 
 impl Default for TreeNode {
@@ -21,45 +30,114 @@ impl TreeNode {
     #[must_use]
     pub const fn new() -> Self {
         let entries = [const { DirEntry::empty() }; Self::LEN];
-        Self { entries }
+        Self { entries, mtime: 0, permissions: Permissions::dir(None), pinned: false }
     }
 
     pub(super) const fn new_leaf() -> Self {
         let entries = [const { DirEntry::empty() }; Self::LEN];
-        Self { entries }
+        Self { entries, mtime: 0, permissions: Permissions::dir(None), pinned: false }
+    }
+
+    #[must_use]
+    pub(super) const fn with_pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+
+    /// Whether the directory is kept once empty, instead of being pruned.
+    #[must_use]
+    pub const fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    #[must_use]
+    pub const fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    #[must_use]
+    pub const fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    pub const fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    #[must_use]
+    pub const fn with_mtime(mut self, now: Timestamp) -> Self {
+        self.mtime = now;
+        self
+    }
+
+    #[must_use]
+    pub const fn mtime(&self) -> Timestamp {
+        self.mtime
+    }
+
+    pub const fn set_mtime(&mut self, now: Timestamp) {
+        self.mtime = now;
     }
 
     pub fn insert(
         &mut self,
-        name: &str,
+        name: Name,
         addr: Addr,
         kind: DirEntryKind,
     ) -> Result<DirEntry, Error> {
         let (_, entry) = self.find_unset().ok_or(Error::DirectoryFull)?;
-        let name = Name::new(name)?;
         let value = DirEntry::new(name, addr, kind);
         *entry = value.clone();
-        self.entries.sort_by(|a, b| a.name().as_str().cmp(b.name().as_str()));
+        self.sort();
         Ok(value)
     }
 
-    #[must_use]
-    pub const fn get(&self, pos: usize) -> &DirEntry {
-        &self.entries[pos]
+    /// Removes the entry at `pos`, keeping the remaining entries sorted.
+    pub fn remove(&mut self, pos: usize) -> DirEntry {
+        let entry = core::mem::replace(&mut self.entries[pos], DirEntry::empty());
+        self.sort();
+        entry
     }
 
-    pub const fn get_mut(&mut self, pos: usize) -> &mut DirEntry {
-        &mut self.entries[pos]
+    /// Sorts entries by the inline part of their name, which [`Self::find_index`] relies on.
+    ///
+    /// Must be called after emptying entries in place through [`Self::iter_entries_mut`].
+    pub fn sort(&mut self) {
+        self.entries.sort_unstable_by(|a, b| key(a).cmp(key(b)));
     }
 
     #[must_use]
-    pub fn find_index(&self, name: &str) -> Option<usize> {
-        binary_search_index(&self.entries, name, |entry| entry.name().as_str())
+    pub const fn get(&self, pos: usize) -> &DirEntry {
+        &self.entries[pos]
     }
 
-    #[must_use]
-    pub fn find(&self, name: &str) -> Option<&DirEntry> {
-        self.find_index(name).and_then(|idx| self.entries.get(idx))
+    /// Returns the position of the entry named `name`.
+    ///
+    /// Long names sharing the inline prefix and length of `name` are told apart by
+    /// `matches_overflow`, which compares `name` against their overflow block.
+    pub fn find_index<F>(&self, name: &str, mut matches_overflow: F) -> Result<Option<usize>, Error>
+    where
+        F: FnMut(&Name) -> Result<bool, Error>,
+    {
+        let name_key = Name::key(name.as_bytes());
+        let start = self.entries.partition_point(|entry| key(entry) < name_key);
+        let candidates = self.entries[start..].iter().take_while(|entry| key(entry) == name_key);
+        for (pos, entry) in (start..).zip(candidates) {
+            let entry_name = entry.name();
+            if entry_name.len() != name.len() {
+                continue;
+            }
+            let found = if entry_name.is_inline() {
+                entry_name.inline_bytes() == name.as_bytes()
+            } else {
+                matches_overflow(entry_name)?
+            };
+            if found {
+                return Ok(Some(pos));
+            }
+        }
+        Ok(None)
     }
 
     pub fn find_unset(&mut self) -> Option<(usize, &mut DirEntry)> {
@@ -82,21 +160,9 @@ impl TreeNode {
     }
 }
 
-fn binary_search_index<T, K>(list: &[T], value: &K, get_key: impl Fn(&T) -> &K) -> Option<usize>
-where
-    K: Ord + ?Sized,
-{
-    let mut low = 0;
-    let mut high = list.len();
-    while low < high {
-        let mid = usize::midpoint(low, high);
-        match get_key(&list[mid]).cmp(value) {
-            core::cmp::Ordering::Less => low = mid + 1,
-            core::cmp::Ordering::Equal => return Some(mid),
-            core::cmp::Ordering::Greater => high = mid,
-        }
-    }
-    None
+/// Key entries are sorted and searched by.
+fn key(entry: &DirEntry) -> &[u8] {
+    Name::key(entry.name().inline_bytes())
 }
 
 impl DeviceAddr for TreeNode {
@@ -104,7 +170,8 @@ impl DeviceAddr for TreeNode {
 }
 
 impl FixedLen for TreeNode {
-    const BYTES_LEN: usize = Self::LEN * DirEntry::BYTES_LEN;
+    const BYTES_LEN: usize =
+        Self::LEN * DirEntry::BYTES_LEN + size_of::<Timestamp>() + Permissions::BYTES_LEN + 1;
 }
 
 impl Serializable for TreeNode {
@@ -113,6 +180,9 @@ impl Serializable for TreeNode {
         for entry in &self.entries {
             n += entry.serialize(writer)?;
         }
+        n += writer.write_u32(self.mtime)?;
+        n += self.permissions.serialize(writer)?;
+        n += writer.write_u8(u8::from(self.pinned))?;
         Ok(n)
     }
 }
@@ -123,8 +193,11 @@ impl Deserializable<Self> for TreeNode {
         for entry in &mut entries {
             *entry = DirEntry::deserialize(reader)?;
         }
+        let mtime = reader.read_u32()?;
+        let permissions = Permissions::deserialize(reader)?;
+        let pinned = reader.read_u8()? != 0;
 
-        Ok(Self { entries })
+        Ok(Self { entries, mtime, permissions, pinned })
     }
 }
 #[cfg(test)]
@@ -136,20 +209,65 @@ mod tests {
 
     use super::*;
 
-    test_serde_symmetry!(TreeNode, TreeNode::new());
+    test_serde_symmetry!(
+        TreeNode,
+        TreeNode::new()
+            .with_mtime(1_700_000_000)
+            .with_permissions(Permissions::new(0o700, 1, 2))
+            .with_pinned()
+    );
 
     #[test]
     fn test_insert_full_node() {
         let mut sut = TreeNode::new();
-        for i in 0..=TreeNode::LEN {
+        for i in 0..TreeNode::LEN {
             let addr = Addr::from(i as u32);
             let kind = if i % 2 == 0 { DirEntryKind::File } else { DirEntryKind::Dir };
-            sut.insert(&format!("entry-{i}"), addr, kind).expect("should insert entry");
+            sut.insert(format!("entry-{i}").into(), addr, kind).expect("should insert entry");
         }
 
         assert_eq!(
             Err(Error::DirectoryFull),
-            sut.insert("extra-entry", 100 as Addr, DirEntryKind::File)
+            sut.insert("extra-entry".into(), 100 as Addr, DirEntryKind::File)
         );
     }
+
+    #[test]
+    fn test_remove_keeps_entries_sorted() {
+        let mut sut = TreeNode::new();
+        for name in ["a", "b", "c"] {
+            sut.insert(name.into(), 1, DirEntryKind::File).expect("should insert entry");
+        }
+
+        let pos = sut.find_index("b", |_| Ok(false)).unwrap().unwrap();
+        assert_eq!(b"b", sut.remove(pos).name().inline_bytes());
+        assert_eq!(Ok(None), sut.find_index("b", |_| Ok(false)));
+        assert!(sut.find_index("a", |_| Ok(false)).unwrap().is_some());
+        assert!(sut.find_index("c", |_| Ok(false)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_find_long_names_sharing_prefix() {
+        let prefix = "p".repeat(constants::INLINE_NAME_LEN);
+        let names = [format!("{prefix}a"), format!("{prefix}b"), format!("{prefix}bc")];
+        let mut sut = TreeNode::new();
+        for (i, name) in names.iter().enumerate() {
+            let name = Name::new(name).unwrap().with_overflow_addr(i as Addr);
+            sut.insert(name, i as Addr, DirEntryKind::File).expect("should insert entry");
+        }
+        sut.insert(prefix.as_str().into(), 9, DirEntryKind::File).expect("should insert entry");
+
+        // Overflow blocks are looked up by address, only for candidates of the same length.
+        let mut compared = 0;
+        let pos = sut.find_index(&names[1], |name| {
+            compared += 1;
+            Ok(names[name.overflow_addr() as usize] == names[1])
+        });
+        assert_eq!(1, sut.get(pos.unwrap().unwrap()).addr());
+        assert!(compared <= 2);
+
+        let pos = sut.find_index(&prefix, |_| panic!("inline names need no overflow block"));
+        assert_eq!(9, sut.get(pos.unwrap().unwrap()).addr());
+        assert_eq!(Ok(None), sut.find_index(&format!("{prefix}c"), |_| Ok(false)));
+    }
 }
@@ -1,4 +1,10 @@
-use crate::{Addr, BlockDevice, Error, TreeNode, directory::direntry::DirEntryKind, storage};
+use crate::{
+    Addr, BlockDevice, Error, Metadata, TreeNode, constants,
+    directory::{DirEntry, direntry::DirEntryKind, load_node, read_name},
+    node::Node,
+    storage,
+    symlink::Symlink,
+};
 
 pub trait Visitor<D>
 where
@@ -21,7 +27,7 @@ where
             return Ok(());
         }
 
-        let node: TreeNode = storage::load(device, addr)?;
+        let node = load_node(device, addr)?;
         for entry in node.iter_entries().filter(|entry| entry.is_dir()) {
             self.walk_tree(device, entry.addr(), current_depth + 1, max_depth)?;
         }
@@ -30,6 +36,49 @@ where
     }
 }
 
+/// Visits every entry below a directory, parents before their children, along with
+/// its path relative to that directory.
+pub trait PathVisitor<D>
+where
+    D: BlockDevice,
+{
+    fn visit(&mut self, device: &mut D, path: &str, entry: &DirEntry) -> Result<(), Error>;
+
+    fn walk_from(&mut self, device: &mut D, addr: Addr) -> Result<(), Error> {
+        let mut path = [0u8; constants::PATH_LEN];
+        self.walk_dir(device, addr, &mut path, 0)
+    }
+
+    /// Walks the directory at `addr`, whose path is the first `len` bytes of `path`.
+    fn walk_dir(
+        &mut self,
+        device: &mut D,
+        addr: Addr,
+        path: &mut [u8; constants::PATH_LEN],
+        len: usize,
+    ) -> Result<(), Error> {
+        let node = load_node(device, addr)?;
+        for entry in node.iter_entries() {
+            let start = if len == 0 { 0 } else { len + 1 };
+            let end = start + entry.name().len();
+            if end > path.len() {
+                return Err(Error::NameTooLong);
+            }
+            if len > 0 {
+                path[len] = b'/';
+            }
+            read_name(device, entry.name(), &mut path[start..end])?;
+
+            let entry_path = str::from_utf8(&path[..end]).map_err(|_| Error::Unexpected)?;
+            self.visit(device, entry_path, entry)?;
+            if entry.is_dir() {
+                self.walk_dir(device, entry.addr(), path, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub struct CounterVisitor {
     kind: DirEntryKind,
     count: usize,
@@ -58,3 +107,24 @@ where
         Ok(())
     }
 }
+
+/// Calls a closure with the path and [`Metadata`] of every visited entry.
+pub struct MetadataVisitor<F>(pub F);
+
+impl<D, F> PathVisitor<D> for MetadataVisitor<F>
+where
+    D: BlockDevice,
+    F: FnMut(&str, &Metadata) -> Result<(), Error>,
+{
+    fn visit(&mut self, device: &mut D, path: &str, entry: &DirEntry) -> Result<(), Error> {
+        let addr = entry.addr();
+        let metadata = match entry.kind() {
+            DirEntryKind::File => Metadata::file(addr, &storage::load::<_, Node>(device, addr)?),
+            DirEntryKind::Dir => Metadata::dir(addr, &storage::load(device, addr)?),
+            DirEntryKind::Symlink => {
+                Metadata::symlink(addr, &storage::load::<_, Symlink>(device, addr)?)
+            }
+        };
+        (self.0)(path, &metadata)
+    }
+}
@@ -0,0 +1,78 @@
+//! Implementations of the [`embedded_io`] traits, so that open files plug into
+//! firmware libraries such as HTTP servers and parsers.
+
+use embedded_io::{ErrorKind, ErrorType, Read, Seek, SeekFrom, Write};
+
+use crate::{
+    Error,
+    file_handle::{FileHandle, SeekOrigin},
+};
+
+impl embedded_io::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::FileNotFound | Self::DirectoryNotFound | Self::XattrNotFound => {
+                ErrorKind::NotFound
+            }
+            Self::FileAlreadyExists => ErrorKind::AlreadyExists,
+            Self::PermissionDenied => ErrorKind::PermissionDenied,
+            Self::BufferTooSmall { .. }
+            | Self::NameTooLong
+            | Self::PathTooDeep
+            | Self::InvalidName
+            | Self::FileTooLarge
+            | Self::IsADirectory
+            | Self::NotADirectory
+            | Self::NotASymlink
+            | Self::TooManyLinks
+            | Self::XattrTooLarge
+            | Self::InvalidSeek => ErrorKind::InvalidInput,
+            Self::UnsupportedDevice => ErrorKind::InvalidData,
+            Self::StorageFull | Self::DirectoryFull | Self::Io | Self::Unexpected => {
+                ErrorKind::Other
+            }
+        }
+    }
+}
+
+impl ErrorType for FileHandle<'_> {
+    type Error = Error;
+}
+
+impl Read for FileHandle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        Self::read(self, buf)
+    }
+}
+
+impl Write for FileHandle<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        Self::write(self, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl Seek for FileHandle<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        match pos {
+            SeekFrom::Start(pos) => self.seek_from(SeekOrigin::Start, pos),
+            SeekFrom::End(offset) => self.seek_from(SeekOrigin::End, offset),
+            SeekFrom::Current(offset) => self.seek_from(SeekOrigin::Current, offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_kind() {
+        assert_eq!(ErrorKind::NotFound, embedded_io::Error::kind(&Error::FileNotFound));
+        assert_eq!(ErrorKind::InvalidInput, embedded_io::Error::kind(&Error::FileTooLarge));
+        assert_eq!(ErrorKind::Other, embedded_io::Error::kind(&Error::StorageFull));
+    }
+}
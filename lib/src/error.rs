@@ -10,29 +10,85 @@ pub enum Error {
     FileAlreadyExists,
     /// The name exceeds the maximum allowed length.
     NameTooLong,
+    /// The path has more components than allowed.
+    PathTooDeep,
+    /// The name is empty, reserved, or contains forbidden characters.
+    InvalidName,
     /// The file does not exist.
     FileNotFound,
     /// The file is too large to be stored.
     FileTooLarge,
+    /// The entry is a directory, where a file was expected.
+    IsADirectory,
+    /// A component of the path is a file, where a directory was expected.
+    NotADirectory,
     /// The directory is not found.
     DirectoryNotFound,
     /// The directory is full and cannot accommodate more entries.
     DirectoryFull,
     /// The file system is full and cannot accommodate more files.
     StorageFull,
+    /// The entry is not a symbolic link.
+    NotASymlink,
+    /// Too many symbolic links were followed while resolving a path, or a node has
+    /// too many hard links.
+    TooManyLinks,
+    /// The caller identity is not allowed to perform the operation.
+    PermissionDenied,
+    /// The extended attribute does not exist.
+    XattrNotFound,
+    /// The extended attribute does not fit in the space left for attributes.
+    XattrTooLarge,
+    /// The position sought is before the start of the file, or out of range.
+    InvalidSeek,
     /// The device is not formatted correctly.
     UnsupportedDevice,
+    /// The device failed to read or write a sector.
+    Io,
     /// Unexpected
     Unexpected,
 }
 
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferTooSmall { expected, found } => {
+                write!(f, "buffer too small, expected {expected} bytes but found {found}")
+            }
+            Self::FileAlreadyExists => f.write_str("file already exists"),
+            Self::NameTooLong => f.write_str("name too long"),
+            Self::PathTooDeep => f.write_str("path too deep"),
+            Self::InvalidName => f.write_str("invalid name"),
+            Self::FileNotFound => f.write_str("no such file"),
+            Self::FileTooLarge => f.write_str("file too large"),
+            Self::IsADirectory => f.write_str("is a directory"),
+            Self::NotADirectory => f.write_str("not a directory"),
+            Self::DirectoryNotFound => f.write_str("no such directory"),
+            Self::DirectoryFull => f.write_str("directory full"),
+            Self::StorageFull => f.write_str("no space left on device"),
+            Self::NotASymlink => f.write_str("not a symbolic link"),
+            Self::TooManyLinks => f.write_str("too many links"),
+            Self::PermissionDenied => f.write_str("permission denied"),
+            Self::XattrNotFound => f.write_str("no such attribute"),
+            Self::XattrTooLarge => f.write_str("attribute too large"),
+            Self::InvalidSeek => f.write_str("invalid seek"),
+            Self::UnsupportedDevice => f.write_str("unsupported device"),
+            Self::Io => f.write_str("input/output error"),
+            Self::Unexpected => f.write_str("unexpected error"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
 impl From<io::Error> for Error {
     fn from(value: io::Error) -> Self {
         match value {
             io::Error::BufferTooSmall { expected, found } => {
                 Self::BufferTooSmall { expected, found }
             }
-            io::Error::IO { io: _ } => Self::UnsupportedDevice,
+            #[cfg(feature = "std")]
+            io::Error::IO { io: _ } => Self::Io,
         }
     }
 }
@@ -1,15 +1,75 @@
+use core::ops::Range;
+
 use crate::{
-    BlockDevice, Error, block::Block, device_layout::DeviceLayout, io::Writer, node::Node,
+    Addr, BlockDevice, Error,
+    allocator::Allocator,
+    block::Block,
+    clock::Clock,
+    constants,
+    device_layout::DeviceLayout,
+    node::{HOLE, Node},
+    permissions::{Access, Identity},
+    storage,
 };
 
+/// Position a seek is relative to.
+#[cfg(any(feature = "std", feature = "embedded-io"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SeekOrigin {
+    Start,
+    End,
+    Current,
+}
+
+/// Data blocks read ahead when a file is read sequentially.
+const READ_AHEAD_BLOCKS: usize = 4;
+
+/// An open file, reading and writing at a position moved forward by each call.
+///
+/// Small files keep their data inline in their node, and are moved to data blocks
+/// once a write makes them larger than [`constants::INLINE_DATA_LEN`]. Ranges that
+/// were never written are holes, which read as zeros and use no data block.
+///
+/// Reads continuing where the previous one stopped prefetch the next data blocks, so
+/// a cache in front of the device fetches contiguous blocks with a single read.
+///
+/// With the `std` or `embedded-io` feature, it implements the `Read`, `Write` and
+/// `Seek` traits of that crate. Writes are stored before returning, so flushing does
+/// nothing.
 pub struct FileHandle<'dev> {
     device: &'dev mut dyn BlockDevice,
+    data_allocator: &'dev mut Allocator,
+    clock: &'dev dyn Clock,
+    identity: Option<Identity>,
+    node_addr: Addr,
     node: Node,
+    pos: usize,
+    /// Where the last read stopped, the next read being sequential if it starts there.
+    read_end: usize,
+    /// Index of the first data block not read ahead yet.
+    read_ahead: usize,
 }
 
 impl<'dev> FileHandle<'dev> {
-    pub const fn new(device: &'dev mut dyn BlockDevice, node: Node) -> Self {
-        Self { device, node }
+    pub const fn new(
+        device: &'dev mut dyn BlockDevice,
+        data_allocator: &'dev mut Allocator,
+        clock: &'dev dyn Clock,
+        identity: Option<Identity>,
+        node_addr: Addr,
+        node: Node,
+    ) -> Self {
+        Self {
+            device,
+            data_allocator,
+            clock,
+            identity,
+            node_addr,
+            node,
+            pos: 0,
+            read_end: 0,
+            read_ahead: 0,
+        }
     }
 
     #[must_use]
@@ -17,29 +77,287 @@ impl<'dev> FileHandle<'dev> {
         self.node.file_len()
     }
 
+    /// Current position, where the next read or write starts.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Moves the position to `pos`, which may be past the end of the file.
+    pub const fn set_position(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// Moves the position `offset` bytes away from `origin`, returning the new position.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidSeek`] if the position would be negative, or overflow.
+    #[cfg(any(feature = "std", feature = "embedded-io"))]
+    pub(crate) fn seek_from<O>(&mut self, origin: SeekOrigin, offset: O) -> Result<u64, Error>
+    where
+        O: TryInto<isize>,
+    {
+        let base = match origin {
+            SeekOrigin::Start => 0,
+            SeekOrigin::End => self.file_len().into(),
+            SeekOrigin::Current => self.pos,
+        };
+        let offset = offset.try_into().map_err(|_| Error::InvalidSeek)?;
+        self.pos = base.checked_add_signed(offset).ok_or(Error::InvalidSeek)?;
+        Ok(self.pos as u64)
+    }
+
+    /// Reads the whole file into `out`, regardless of the current position.
     pub fn readall(&mut self, out: &mut [u8]) -> Result<usize, Error> {
-        if out.len() < self.node.file_len() as usize {
-            return Err(Error::BufferTooSmall {
-                expected: self.node.file_len() as usize,
-                found: out.len(),
-            });
+        let file_len = self.node.file_len() as usize;
+        if out.len() < file_len {
+            return Err(Error::BufferTooSmall { expected: file_len, found: out.len() });
+        }
+        self.read_ahead = 0;
+        self.read_at(0, &mut out[..file_len])
+    }
+
+    /// Reads from the current position into `out`, returning the number of bytes read,
+    /// which is 0 once the end of the file is reached.
+    pub fn read(&mut self, out: &mut [u8]) -> Result<usize, Error> {
+        let remaining = (self.node.file_len() as usize).saturating_sub(self.pos);
+        let len = out.len().min(remaining);
+        if self.pos != self.read_end {
+            // Only the blocks past the first one are read ahead after a seek.
+            self.read_ahead = self.pos / Block::LEN + 1;
+        }
+        let n = self.read_at(self.pos, &mut out[..len])?;
+        self.pos += n;
+        self.read_end = self.pos;
+        Ok(n)
+    }
+
+    /// Writes all of `data` at the current position, growing the file as needed.
+    ///
+    /// Writing past the end of the file leaves a hole in between.
+    ///
+    /// # Errors
+    /// - [`Error::PermissionDenied`] if the caller may not write to the file.
+    /// - [`Error::FileTooLarge`] if the file would exceed [`constants::MAX_FILE_SIZE`].
+    /// - [`Error::StorageFull`] if no data block is left, in which case part of `data`
+    ///   may have been written.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        self.node.permissions().check(self.identity.as_ref(), &[Access::Write])?;
+        let end = self.pos.checked_add(data.len()).ok_or(Error::FileTooLarge)?;
+        if end > constants::MAX_FILE_SIZE {
+            return Err(Error::FileTooLarge);
+        }
+
+        // The node is stored even on failure, so it keeps track of allocated blocks.
+        let result = self.write_at(self.pos, data);
+        self.node.set_modified(self.clock.now());
+        storage::store(self.device, self.node_addr, &self.node)?;
+        result?;
+        self.pos = end;
+        Ok(data.len())
+    }
+
+    /// Reserves data blocks so the file can grow up to `len` bytes without allocating,
+    /// which keeps later writes from failing with [`Error::StorageFull`].
+    ///
+    /// The blocks past the end of the file are reserved as a single contiguous run,
+    /// following the last block of the file when possible. The file length is left
+    /// unchanged, and so are the holes within the file.
+    ///
+    /// # Errors
+    /// - [`Error::PermissionDenied`] if the caller may not write to the file.
+    /// - [`Error::FileTooLarge`] if `len` exceeds [`constants::MAX_FILE_SIZE`].
+    /// - [`Error::StorageFull`] if no contiguous run of free blocks is long enough.
+    pub fn preallocate(&mut self, len: usize) -> Result<(), Error> {
+        self.node.permissions().check(self.identity.as_ref(), &[Access::Write])?;
+        if len > constants::MAX_FILE_SIZE {
+            return Err(Error::FileTooLarge);
+        }
+        if self.node.is_inline() && len <= constants::INLINE_DATA_LEN {
+            return Ok(());
+        }
+
+        let addrs = self.node.data_addrs();
+        let first = addrs.iter().rposition(|addr| *addr != HOLE).map_or(0, |index| index + 1);
+        let first = first.max(self.node.blocks_needed());
+        let last = len.div_ceil(Block::LEN);
+        if first < last {
+            let near =
+                addrs[..first].iter().rfind(|addr| **addr != HOLE).map_or(HOLE, |addr| addr + 1);
+            let start = self.data_allocator.allocate_contiguous(self.device, last - first, near)?;
+            for (index, addr) in (first..last).zip(start..) {
+                self.node.set_data_addr(index, addr);
+            }
+        }
+
+        // The node is stored even on failure, so it keeps track of allocated blocks.
+        let result = if self.node.is_inline() { self.promote() } else { Ok(()) };
+        storage::store(self.device, self.node_addr, &self.node)?;
+        result
+    }
+
+    /// Turns `range` into a hole, releasing the data blocks it fully covers and zeroing
+    /// the others. The file length is unchanged, the part of `range` past the end of
+    /// the file is ignored.
+    ///
+    /// # Errors
+    /// Returns [`Error::PermissionDenied`] if the caller may not write to the file.
+    pub fn punch_hole(&mut self, range: Range<usize>) -> Result<(), Error> {
+        self.node.permissions().check(self.identity.as_ref(), &[Access::Write])?;
+        let file_len = self.node.file_len() as usize;
+        let (start, end) = (range.start.min(file_len), range.end.min(file_len));
+        if start >= end {
+            return Ok(());
+        }
+
+        let zeros = [0; Block::LEN];
+        let mut released = [HOLE; constants::NODE_DATA_BLOCKS_LEN];
+        if self.node.is_inline() {
+            self.node.write_inline(start, &zeros[..end - start]);
+        } else {
+            let mut pos = start;
+            while pos < end {
+                let index = pos / Block::LEN;
+                let block_start = index * Block::LEN;
+                let chunk_end = end.min(block_start + Block::LEN);
+                let addr = self.node.data_addrs()[index];
+                let covered =
+                    pos == block_start && chunk_end >= file_len.min(block_start + Block::LEN);
+                if addr != HOLE && covered {
+                    self.node.set_data_addr(index, HOLE);
+                    released[index] = addr;
+                } else if addr != HOLE {
+                    self.write_blocks(pos, &zeros[..chunk_end - pos])?;
+                }
+                pos = chunk_end;
+            }
+        }
+
+        // Blocks are only released once the node no longer references them.
+        self.node.set_modified(self.clock.now());
+        storage::store(self.device, self.node_addr, &self.node)?;
+        for addr in released.into_iter().filter(|addr| *addr != HOLE) {
+            self.data_allocator.release(self.device, addr)?;
+        }
+        Ok(())
+    }
+
+    fn read_at(&mut self, offset: usize, out: &mut [u8]) -> Result<usize, Error> {
+        if out.is_empty() || offset >= self.node.file_len() as usize {
+            return Ok(0);
+        }
+        if self.node.is_inline() {
+            out.copy_from_slice(&self.node.inline_data()[offset..offset + out.len()]);
+            return Ok(out.len());
         }
 
         let mut block = Block::new();
-        let mut writer = Writer::new(out);
-        let blocks_needed = self.node.blocks_needed();
-        for (i, data_addr) in
-            self.node.data_addrs().iter().take(self.node.blocks_needed()).enumerate()
-        {
-            let sector = DeviceLayout::DATA.nth(*data_addr);
-            self.device.read(sector, &mut block)?;
-            if i == blocks_needed - 1 {
-                let remaining_bytes = self.node.file_len() as usize % Block::LEN;
-                writer.write(&block[..remaining_bytes])?;
+        let mut done = 0;
+        while done < out.len() {
+            let pos = offset + done;
+            let start = pos % Block::LEN;
+            let len = (Block::LEN - start).min(out.len() - done);
+            let index = pos / Block::LEN;
+            if index >= self.read_ahead {
+                self.prefetch(index)?;
+            }
+            let addr = self.node.data_addrs()[index];
+            if addr == HOLE {
+                out[done..done + len].fill(0);
             } else {
-                writer.write(&block)?;
+                self.device.read(DeviceLayout::DATA.nth(addr), &mut block)?;
+                out[done..done + len].copy_from_slice(&block[start..start + len]);
             }
+            done += len;
+        }
+        Ok(done)
+    }
+
+    /// Prefetches the data blocks from `index` on, up to [`READ_AHEAD_BLOCKS`], one run
+    /// of contiguous blocks at a time.
+    fn prefetch(&mut self, index: usize) -> Result<(), Error> {
+        let end = (index + READ_AHEAD_BLOCKS).min(self.node.blocks_needed());
+        let addrs = &self.node.data_addrs()[index..end];
+        let runs = addrs.chunk_by(|a, b| *a != HOLE && *b == a + 1);
+        for run in runs.filter(|run| run[0] != HOLE) {
+            self.device.prefetch(DeviceLayout::DATA.nth(run[0]), run.len())?;
+        }
+        self.read_ahead = end;
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        let end = offset + data.len();
+        if self.node.is_inline() {
+            if end <= constants::INLINE_DATA_LEN {
+                self.node.write_inline(offset, data);
+                return Ok(());
+            }
+            self.promote()?;
+        }
+
+        // Blocks in the gap past the end of the file are cleared, holes are left as they are.
+        let zeros = [0; Block::LEN];
+        let mut pos = self.node.file_len() as usize;
+        while pos < offset {
+            let index = pos / Block::LEN;
+            let chunk_end = offset.min((index + 1) * Block::LEN);
+            if self.node.data_addrs()[index] != HOLE {
+                self.write_blocks(pos, &zeros[..chunk_end - pos])?;
+            }
+            pos = chunk_end;
+        }
+        self.write_blocks(offset, data)
+    }
+
+    /// Moves the data of an inline file to data blocks, using the ones already
+    /// preallocated if any.
+    fn promote(&mut self) -> Result<(), Error> {
+        let mut data = [0; constants::INLINE_DATA_LEN];
+        let len = self.node.file_len() as usize;
+        data[..len].copy_from_slice(self.node.inline_data());
+        self.node.clear_inline();
+        self.write_blocks(0, &data[..len])
+    }
+
+    /// Writes `data` at `offset` to data blocks, allocating holes along the way.
+    ///
+    /// Bytes of the written blocks past the end of the file are cleared, so the blocks
+    /// skipped between the end of the file and `offset` must be holes.
+    fn write_blocks(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        let mut block = Block::new();
+        let mut done = 0;
+        while done < data.len() {
+            let pos = offset + done;
+            let index = pos / Block::LEN;
+            let start = pos % Block::LEN;
+            let len = (Block::LEN - start).min(data.len() - done);
+            let file_len = self.node.file_len() as usize;
+
+            let addr = self.node.data_addrs()[index];
+            let addr = if addr != HOLE {
+                // Bytes past the end of the file, or of preallocated blocks, are not
+                // guaranteed to be zero.
+                let valid = file_len.saturating_sub(index * Block::LEN);
+                if valid > 0 {
+                    self.device.read(DeviceLayout::DATA.nth(addr), &mut block)?;
+                }
+                if valid < Block::LEN {
+                    block[valid..].fill(0);
+                }
+                addr
+            } else {
+                let addr = self.data_allocator.allocate(self.device)?;
+                self.node.set_data_addr(index, addr);
+                block = Block::new();
+                addr
+            };
+
+            block[start..start + len].copy_from_slice(&data[done..done + len]);
+            self.device.write(DeviceLayout::DATA.nth(addr), &block)?;
+            self.node.set_file_len(file_len.max(pos + len) as u16);
+            done += len;
         }
-        Ok(self.node.file_len() as usize)
+        Ok(())
     }
 }
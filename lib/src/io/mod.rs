@@ -1,5 +1,3 @@
-use core::fmt;
-
 pub use reader::Reader;
 pub use writer::Writer;
 
@@ -11,10 +9,11 @@ mod writer;
 #[cfg(feature = "std")]
 pub struct StdoutFmtWriter;
 
-impl fmt::Write for StdoutFmtWriter {
-    fn write_str(&mut self, s: &str) -> fmt::Result {
+#[cfg(feature = "std")]
+impl core::fmt::Write for StdoutFmtWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
         use std::io::{self, Write};
-        io::stdout().write_all(s.as_bytes()).map_err(|_| fmt::Error)
+        io::stdout().write_all(s.as_bytes()).map_err(|_| core::fmt::Error)
     }
 }
 
@@ -23,6 +22,7 @@ pub enum Error {
     BufferTooSmall { expected: usize, found: usize },
 
     /// An underlying I/O error occurred.
+    #[cfg(feature = "std")]
     IO { io: std::io::Error },
 }
 
@@ -38,6 +38,10 @@ pub trait Write {
         self.write(&value.to_le_bytes())
     }
 
+    fn write_u32(&mut self, value: u32) -> Result<usize, Error> {
+        self.write(&value.to_le_bytes())
+    }
+
     fn write_addr(&mut self, addr: Addr) -> Result<usize, Error> {
         self.write(&addr.to_le_bytes())
     }
@@ -59,6 +63,12 @@ pub trait Read {
         Ok(u16::from_le_bytes(buf))
     }
 
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let mut buf = [0u8; 4];
+        self.read(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
     fn read_addr(&mut self) -> Result<Addr, Error> {
         let mut buf = [0u8; size_of::<Addr>()];
         self.read(&mut buf)?;
@@ -8,8 +8,16 @@ extern crate std;
 #[cfg(any(test, feature = "test-support"))]
 pub mod testutils;
 
+pub use block_cache::{BlockCache, CacheStats};
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+pub use clock::{Clock, NoClock, Timestamp};
 pub use controller::Controller;
 pub use error::Error;
+pub use metadata::Metadata;
+pub use permissions::{Access, Identity, Permissions};
+#[cfg(feature = "sd-card")]
+pub use sd_card::{SdCard, SdError};
 
 use crate::{
     block::Block,
@@ -22,19 +30,30 @@ use crate::{
 mod allocator;
 mod block;
 mod block_cache;
+mod clock;
 pub mod constants;
 mod controller;
 mod device_layout;
 mod directory;
+#[cfg(feature = "embedded-io")]
+mod embedded;
 mod error;
 mod file;
 mod file_handle;
 mod io;
 mod meta;
+mod metadata;
 mod name;
 mod node;
 mod paths;
+mod permissions;
+#[cfg(feature = "sd-card")]
+mod sd_card;
+#[cfg(feature = "std")]
+mod std_io;
 mod storage;
+mod symlink;
+mod xattr;
 
 // Logical address type for sectors/blocks. Change here to update everywhere.
 pub type Addr = u32;
@@ -65,6 +84,38 @@ pub trait BlockDevice {
 
     /// Writes a block of data to the specified sector.
     fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error>;
+
+    /// Reads consecutive sectors starting at `sector` into `buf`, whose length is a
+    /// multiple of the block size.
+    ///
+    /// Devices able to transfer several sectors with a single command should override
+    /// it, the default reads them one at a time.
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        for (sector, chunk) in (sector..).zip(buf.chunks_mut(Block::LEN)) {
+            self.read(sector, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `buf`, whose length is a multiple of the block size, to consecutive
+    /// sectors starting at `sector`.
+    ///
+    /// Devices able to transfer several sectors with a single command should override
+    /// it, the default writes them one at a time.
+    fn write_sectors(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        for (sector, chunk) in (sector..).zip(buf.chunks(Block::LEN)) {
+            self.write(sector, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Hints that the `count` sectors starting at `sector` are about to be read.
+    ///
+    /// Caches use it to read them ahead, devices ignore it by default.
+    fn prefetch(&mut self, sector: Addr, count: usize) -> Result<(), Error> {
+        let _ = (sector, count);
+        Ok(())
+    }
 }
 
 pub trait DeviceAddr {
@@ -12,6 +12,7 @@ pub struct Meta {
     node_sector: Addr,
     data_bitmap: Addr,
     data_sector: Addr,
+    node_bitmap: Addr,
     block_size: u16,
     signature: [u8; 2],
 }
@@ -33,6 +34,7 @@ impl Meta {
             node_sector: DeviceLayout::NODE.begin(),
             data_bitmap: DeviceLayout::DATA_BITMAP.begin(),
             data_sector: DeviceLayout::DATA.begin(),
+            node_bitmap: DeviceLayout::NODE_BITMAP.begin(),
             block_size: Block::LEN as u16,
             signature: Self::SIGNATURE,
         }
@@ -55,8 +57,9 @@ impl Serializable for Meta {
         n += writer.write_addr(self.node_sector)?;
         n += writer.write_addr(self.data_bitmap)?;
         n += writer.write_addr(self.data_sector)?;
+        n += writer.write_addr(self.node_bitmap)?;
         n += writer.write_u16(self.block_size)?;
-        n += writer.write(&[0; 484])?;
+        n += writer.write(&[0; 480])?;
         n += writer.write(&Self::SIGNATURE)?;
         Ok(n)
     }
@@ -70,8 +73,9 @@ impl Deserializable<Self> for Meta {
         let node_sector = reader.read_addr()?;
         let data_bitmap = reader.read_addr()?;
         let data_sector = reader.read_addr()?;
+        let node_bitmap = reader.read_addr()?;
         let block_size = reader.read_u16()?;
-        reader.read(&mut [0; 484])?;
+        reader.read(&mut [0; 480])?;
         let mut signature = [0u8; 2];
         reader.read(&mut signature)?;
 
@@ -82,6 +86,7 @@ impl Deserializable<Self> for Meta {
             node_sector,
             data_bitmap,
             data_sector,
+            node_bitmap,
             block_size,
             signature,
         })
@@ -0,0 +1,126 @@
+use crate::{
+    Addr, clock::Timestamp, directory::DirEntryKind, directory::TreeNode, node::Node,
+    permissions::Permissions, symlink::Symlink,
+};
+
+/// Describes a file or directory, as returned by [`crate::Controller::stat`], or a
+/// symbolic link, as found by [`crate::Controller::walk`].
+///
+/// Directories only keep track of their modification time, their creation and
+/// access times are always 0. Symbolic links have no metadata of their own, only
+/// the length of their target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    kind: DirEntryKind,
+    addr: Addr,
+    len: u16,
+    links: u16,
+    created: Timestamp,
+    modified: Timestamp,
+    accessed: Timestamp,
+    permissions: Permissions,
+}
+
+impl Metadata {
+    /// Mode reported for symbolic links, which grant every access like on POSIX systems.
+    const SYMLINK_MODE: u16 = 0o777;
+
+    pub(crate) const fn file(addr: Addr, node: &Node) -> Self {
+        Self {
+            kind: DirEntryKind::File,
+            addr,
+            len: node.file_len(),
+            links: node.links(),
+            created: node.created(),
+            modified: node.modified(),
+            accessed: node.accessed(),
+            permissions: *node.permissions(),
+        }
+    }
+
+    pub(crate) const fn dir(addr: Addr, node: &TreeNode) -> Self {
+        Self {
+            kind: DirEntryKind::Dir,
+            addr,
+            len: 0,
+            links: 1,
+            created: 0,
+            modified: node.mtime(),
+            accessed: 0,
+            permissions: *node.permissions(),
+        }
+    }
+
+    pub(crate) fn symlink(addr: Addr, symlink: &Symlink) -> Self {
+        Self {
+            kind: DirEntryKind::Symlink,
+            addr,
+            len: symlink.target().len() as u16,
+            links: 1,
+            created: 0,
+            modified: 0,
+            accessed: 0,
+            permissions: Permissions::new(Self::SYMLINK_MODE, 0, 0),
+        }
+    }
+
+    #[must_use]
+    pub const fn is_file(&self) -> bool {
+        matches!(self.kind, DirEntryKind::File)
+    }
+
+    #[must_use]
+    pub const fn is_dir(&self) -> bool {
+        matches!(self.kind, DirEntryKind::Dir)
+    }
+
+    #[must_use]
+    pub const fn is_symlink(&self) -> bool {
+        matches!(self.kind, DirEntryKind::Symlink)
+    }
+
+    /// Address of the file node, directory or symbolic link, within its own area. Files
+    /// at the same address are hard links to each other.
+    #[must_use]
+    pub const fn addr(&self) -> Addr {
+        self.addr
+    }
+
+    /// Length of the file in bytes, of the target for symbolic links, 0 for directories.
+    #[must_use]
+    pub const fn len(&self) -> u16 {
+        self.len
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of directory entries pointing at the file, always 1 for directories.
+    #[must_use]
+    pub const fn links(&self) -> u16 {
+        self.links
+    }
+
+    /// Mode and ownership of the file or directory.
+    #[must_use]
+    pub const fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    #[must_use]
+    pub const fn created(&self) -> Timestamp {
+        self.created
+    }
+
+    #[must_use]
+    pub const fn modified(&self) -> Timestamp {
+        self.modified
+    }
+
+    #[must_use]
+    pub const fn accessed(&self) -> Timestamp {
+        self.accessed
+    }
+}
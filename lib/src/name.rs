@@ -4,62 +4,115 @@
 //! To facilitate type safety, incoming `&str` file names are converted to
 //! [`Name`] to ensure that file names are always valid and conform to the
 //! maximum length constraint.
+//!
+//! Only the first [`Name::INLINE_LEN`] bytes are serialized with the name. Longer
+//! names keep a prefix inline, followed by the address of an overflow data block
+//! that holds the full name. Only the prefix is kept in memory, reading the
+//! overflow block is up to the caller when the prefix is not enough.
 
 use crate::{
-    Deserializable, Error, FixedLen, Serializable, constants,
+    Addr, Deserializable, Error, FixedLen, Serializable, constants,
     io::{Read, Write},
     paths,
 };
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Name {
-    buf: [u8; Self::LEN],
+    /// The name when it is inline, otherwise its prefix.
+    buf: [u8; Self::INLINE_LEN],
     len: usize,
+    overflow_addr: Addr,
 }
 
 impl Name {
     const LEN: usize = constants::NAME_LEN;
+    const INLINE_LEN: usize = constants::INLINE_NAME_LEN;
+
+    /// Length of the prefix kept inline when the name spills over, the remaining
+    /// inline bytes store the overflow block address.
+    const PREFIX_LEN: usize = Self::INLINE_LEN - size_of::<Addr>();
 
     #[must_use]
     pub const fn empty() -> Self {
-        Self { buf: [0u8; Self::LEN], len: 0 }
+        Self { buf: [0u8; Self::INLINE_LEN], len: 0, overflow_addr: 0 }
     }
 
     /// Creates a new [`FileName`] from a string slice.
     ///
     /// # Errors
     /// Returns an error if the provided name exceeds the maximum length of
-    /// [`Self::MAX_LEN`].
+    /// [`Self::MAX_LEN`], or is not valid as per [`paths::validate_name`].
     pub fn new(name: &str) -> Result<Self, Error> {
-        debug_assert!(
-            !name.contains(paths::SEPARATOR),
-            "File names should never contain a separator character"
-        );
+        paths::validate_name(name)?;
+        let bytes = name.as_bytes();
 
-        if name.len() > Self::LEN {
-            return Err(Error::NameTooLong);
-        }
+        let mut name = Self { buf: [0u8; Self::INLINE_LEN], len: name.len(), overflow_addr: 0 };
+        let kept = &bytes[..bytes.len().min(name.inline_len())];
+        name.buf[..kept.len()].copy_from_slice(kept);
+        Ok(name)
+    }
+
+    /// Length of the full name in bytes.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The name when it is inline, otherwise the prefix kept inline.
+    #[must_use]
+    pub fn inline_bytes(&self) -> &[u8] {
+        &self.buf[..self.len.min(self.inline_len())]
+    }
+
+    /// Bytes names are ordered by within a directory, the part of `name` that is
+    /// always kept inline.
+    #[must_use]
+    pub fn key(name: &[u8]) -> &[u8] {
+        &name[..name.len().min(Self::PREFIX_LEN)]
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the name fits in the inline encoding, without an overflow block.
+    #[must_use]
+    pub const fn is_inline(&self) -> bool {
+        self.len <= Self::INLINE_LEN
+    }
 
-        let mut buf = [0u8; Self::LEN];
-        buf[..name.len()].copy_from_slice(name.as_bytes());
-        Ok(Self { buf, len: name.len() })
+    /// Address of the data block holding the full name, only meaningful when the
+    /// name is not inline.
+    #[must_use]
+    pub const fn overflow_addr(&self) -> Addr {
+        self.overflow_addr
     }
 
-    /// Returns the file name as a string slice.
     #[must_use]
-    pub fn as_str(&self) -> &str {
-        str::from_utf8(&self.buf[..self.len]).unwrap_or("<invalid utf8>")
+    pub const fn with_overflow_addr(mut self, addr: Addr) -> Self {
+        self.overflow_addr = addr;
+        self
+    }
+
+    const fn inline_len(&self) -> usize {
+        if self.is_inline() { Self::INLINE_LEN } else { Self::PREFIX_LEN }
     }
 }
 
 impl FixedLen for Name {
-    const BYTES_LEN: usize = Self::LEN + 1;
+    const BYTES_LEN: usize = Self::INLINE_LEN + 1;
 }
 
 impl Serializable for Name {
     fn serialize<W: Write>(&self, writer: &mut W) -> Result<usize, Error> {
         let mut n = writer.write_u8(self.len as u8)?;
-        n += writer.write(&self.buf)?;
+        if self.is_inline() {
+            n += writer.write(&self.buf[..Self::INLINE_LEN])?;
+        } else {
+            n += writer.write(&self.buf[..Self::PREFIX_LEN])?;
+            n += writer.write_addr(self.overflow_addr)?;
+        }
         Ok(n)
     }
 }
@@ -71,33 +124,14 @@ impl Deserializable<Self> for Name {
             return Err(Error::NameTooLong);
         }
 
-        let mut buf = [0u8; Self::LEN];
-        reader.read(&mut buf)?;
-        Ok(Self { buf, len })
-    }
-}
-
-impl PartialEq<str> for Name {
-    fn eq(&self, other: &str) -> bool {
-        self.as_str() == other
-    }
-}
-
-impl PartialEq<&str> for Name {
-    fn eq(&self, other: &&str) -> bool {
-        self.as_str() == *other
-    }
-}
-
-impl PartialEq<Name> for &Name {
-    fn eq(&self, other: &Name) -> bool {
-        self.as_str() == other.as_str()
-    }
-}
-
-impl PartialEq<Name> for &str {
-    fn eq(&self, other: &Name) -> bool {
-        other.as_str() == *self
+        let mut name = Self { buf: [0u8; Self::INLINE_LEN], len, overflow_addr: 0 };
+        if name.is_inline() {
+            reader.read(&mut name.buf[..Self::INLINE_LEN])?;
+        } else {
+            reader.read(&mut name.buf[..Self::PREFIX_LEN])?;
+            name.overflow_addr = reader.read_addr()?;
+        }
+        Ok(name)
     }
 }
 
@@ -118,7 +152,10 @@ impl From<std::string::String> for Name {
 #[cfg(test)]
 mod tests {
 
-    use crate::test_serde_symmetry;
+    use crate::{
+        io::{Reader, Writer},
+        test_serde_symmetry,
+    };
 
     use super::*;
 
@@ -128,16 +165,48 @@ mod tests {
     fn test_empty() {
         let sut = Name::empty();
         assert_eq!(sut.len, 0);
-        assert_eq!(sut.buf, [0u8; Name::LEN]);
+        assert_eq!(sut.buf, [0u8; Name::INLINE_LEN]);
         assert_eq!(Name::empty(), Name::empty());
     }
 
     #[test]
-    fn test_as_str() {
+    fn test_inline_bytes() {
         let name = "valid_name";
         let sut = Name::new(name).unwrap();
-        assert_eq!(name, sut.as_str());
+        assert_eq!(name.as_bytes(), sut.inline_bytes());
         assert_eq!(Name::new(name), Name::new(name));
+
+        let long = "a".repeat(Name::INLINE_LEN + 1);
+        let sut = Name::new(&long).unwrap();
+        assert_eq!(long.len(), sut.len());
+        assert_eq!(&long.as_bytes()[..Name::PREFIX_LEN], sut.inline_bytes());
+    }
+
+    #[test]
+    fn test_is_inline() {
+        assert!(Name::new(&"a".repeat(Name::INLINE_LEN)).unwrap().is_inline());
+        assert!(!Name::new(&"a".repeat(Name::INLINE_LEN + 1)).unwrap().is_inline());
+    }
+
+    #[test]
+    fn test_long_name_serde() {
+        let input = "c".repeat(Name::LEN);
+        let expected = Name::new(&input).unwrap().with_overflow_addr(42);
+
+        let mut buf = [0u8; Name::BYTES_LEN];
+        assert_eq!(Ok(Name::BYTES_LEN), expected.serialize(&mut Writer::new(&mut buf)));
+
+        let actual = Name::deserialize(&mut Reader::new(&buf)).unwrap();
+        assert_eq!(42, actual.overflow_addr());
+        assert_eq!(Name::LEN, actual.len());
+        assert_eq!(&input.as_bytes()[..Name::PREFIX_LEN], actual.inline_bytes());
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        assert_eq!(Err(Error::InvalidName), Name::new("a/b"));
+        assert_eq!(Err(Error::InvalidName), Name::new("a\0b"));
     }
 
     #[test]
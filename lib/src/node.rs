@@ -1,21 +1,199 @@
 use crate::{
-    Addr, Block, Deserializable, DeviceAddr, Error, FixedLen, Serializable, constants,
+    Addr, Block, Deserializable, DeviceAddr, Error, FixedLen, Serializable,
+    clock::Timestamp,
+    constants,
     device_layout::DeviceLayout,
     io::{Read, Write},
+    permissions::Permissions,
+    xattr::Xattrs,
 };
 
+/// Data address marking a hole, a range of the file that reads as zeros and has no
+/// data block. Data block 0 is reserved when formatting so it is never handed out.
+pub const HOLE: Addr = 0;
+
+/// Marks the absence of an extended attributes block.
+const NO_XATTR_BLOCK: Addr = Addr::MAX;
+
 const N: usize = constants::NODE_DATA_BLOCKS_LEN;
 
+/// Flag set when the file data is kept in the node instead of data blocks.
+const FLAG_INLINE: u8 = 1;
+
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Node {
     file_len: u16,
+    /// Number of directory entries pointing at this node.
+    links: u16,
+    /// Whether the data lives in `inline_data` rather than in `data_addrs`.
+    inline: bool,
     data_addrs: [Addr; N],
+    created: Timestamp,
+    modified: Timestamp,
+    accessed: Timestamp,
+    permissions: Permissions,
+    /// Extended attributes kept in the node itself.
+    xattrs: Xattrs<{ constants::INLINE_XATTR_LEN }>,
+    /// Data block holding the extended attributes that did not fit inline.
+    xattr_addr: Option<Addr>,
+    /// Data of small files, only meaningful when `inline` is set.
+    inline_data: [u8; constants::INLINE_DATA_LEN],
 }
 
 impl Node {
     #[must_use]
     pub const fn new(file_size: u16, data_addrs: [Addr; N]) -> Self {
-        Self { file_len: file_size, data_addrs }
+        Self {
+            file_len: file_size,
+            links: 1,
+            inline: false,
+            data_addrs,
+            created: 0,
+            modified: 0,
+            accessed: 0,
+            permissions: Permissions::file(None),
+            xattrs: Xattrs::new(),
+            xattr_addr: None,
+            inline_data: [0; constants::INLINE_DATA_LEN],
+        }
+    }
+
+    /// Creates a node keeping `data` inline, without any data block.
+    ///
+    /// # Panics
+    /// If `data` is longer than [`constants::INLINE_DATA_LEN`].
+    #[must_use]
+    pub fn new_inline(data: &[u8]) -> Self {
+        assert!(data.len() <= constants::INLINE_DATA_LEN, "data does not fit inline");
+        let mut node = Self::new(data.len() as u16, [0; N]);
+        node.inline = true;
+        node.inline_data[..data.len()].copy_from_slice(data);
+        node
+    }
+
+    #[must_use]
+    pub const fn is_inline(&self) -> bool {
+        self.inline
+    }
+
+    /// Data of an inline file, empty when the data lives in data blocks.
+    #[must_use]
+    pub fn inline_data(&self) -> &[u8] {
+        if self.inline { &self.inline_data[..self.file_len as usize] } else { &[] }
+    }
+
+    /// Writes `data` at `offset` of an inline file, zero filling any gap past the end.
+    ///
+    /// # Panics
+    /// If the node is not inline or the data does not fit inline.
+    pub fn write_inline(&mut self, offset: usize, data: &[u8]) {
+        assert!(self.inline, "node data is not inline");
+        let end = offset + data.len();
+        assert!(end <= constants::INLINE_DATA_LEN, "data does not fit inline");
+        let file_len = self.file_len as usize;
+        if offset > file_len {
+            self.inline_data[file_len..offset].fill(0);
+        }
+        self.inline_data[offset..end].copy_from_slice(data);
+        self.file_len = self.file_len.max(end as u16);
+    }
+
+    /// Turns an inline file into an empty one using data blocks, the inline data is
+    /// discarded and must have been saved by the caller.
+    pub const fn clear_inline(&mut self) {
+        self.inline = false;
+        self.file_len = 0;
+        self.inline_data = [0; constants::INLINE_DATA_LEN];
+    }
+
+    #[must_use]
+    pub const fn xattrs(&self) -> &Xattrs<{ constants::INLINE_XATTR_LEN }> {
+        &self.xattrs
+    }
+
+    pub const fn xattrs_mut(&mut self) -> &mut Xattrs<{ constants::INLINE_XATTR_LEN }> {
+        &mut self.xattrs
+    }
+
+    #[must_use]
+    pub const fn xattr_addr(&self) -> Option<Addr> {
+        self.xattr_addr
+    }
+
+    pub const fn set_xattr_addr(&mut self, addr: Option<Addr>) {
+        self.xattr_addr = addr;
+    }
+
+    #[must_use]
+    pub const fn with_permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    #[must_use]
+    pub const fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
+
+    pub const fn set_permissions(&mut self, permissions: Permissions) {
+        self.permissions = permissions;
+    }
+
+    #[must_use]
+    pub const fn links(&self) -> u16 {
+        self.links
+    }
+
+    /// Records a new directory entry pointing at this node.
+    ///
+    /// # Errors
+    /// Returns [`Error::TooManyLinks`] if the link count would overflow.
+    pub const fn add_link(&mut self) -> Result<(), Error> {
+        match self.links.checked_add(1) {
+            Some(links) => {
+                self.links = links;
+                Ok(())
+            }
+            None => Err(Error::TooManyLinks),
+        }
+    }
+
+    /// Forgets a directory entry pointing at this node, returning the remaining link count.
+    pub const fn remove_link(&mut self) -> u16 {
+        self.links = self.links.saturating_sub(1);
+        self.links
+    }
+
+    /// Stamps a newly created node, setting all of its timestamps to `now`.
+    #[must_use]
+    pub const fn with_created(mut self, now: Timestamp) -> Self {
+        self.created = now;
+        self.modified = now;
+        self.accessed = now;
+        self
+    }
+
+    #[must_use]
+    pub const fn created(&self) -> Timestamp {
+        self.created
+    }
+
+    #[must_use]
+    pub const fn modified(&self) -> Timestamp {
+        self.modified
+    }
+
+    #[must_use]
+    pub const fn accessed(&self) -> Timestamp {
+        self.accessed
+    }
+
+    pub const fn set_accessed(&mut self, now: Timestamp) {
+        self.accessed = now;
+    }
+
+    pub const fn set_modified(&mut self, now: Timestamp) {
+        self.modified = now;
     }
 
     #[must_use]
@@ -23,14 +201,24 @@ impl Node {
         &self.data_addrs
     }
 
+    /// Sets the address of the `index`-th data block, or [`HOLE`] to mark it as a hole.
+    pub const fn set_data_addr(&mut self, index: usize, addr: Addr) {
+        self.data_addrs[index] = addr;
+    }
+
     #[must_use]
     pub const fn file_len(&self) -> u16 {
         self.file_len
     }
 
+    pub const fn set_file_len(&mut self, file_len: u16) {
+        self.file_len = file_len;
+    }
+
+    /// Number of data blocks used by the file, always 0 for inline files.
     #[must_use]
     pub const fn blocks_needed(&self) -> usize {
-        (self.file_len as usize).div_ceil(Block::LEN)
+        if self.inline { 0 } else { (self.file_len as usize).div_ceil(Block::LEN) }
     }
 }
 
@@ -39,15 +227,32 @@ impl DeviceAddr for Node {
 }
 
 impl FixedLen for Node {
-    const BYTES_LEN: usize = 2 + (size_of::<Addr>() * N);
+    const BYTES_LEN: usize = 2
+        + 2
+        + 1
+        + (size_of::<Addr>() * N)
+        + (3 * size_of::<Timestamp>())
+        + Permissions::BYTES_LEN
+        + constants::INLINE_XATTR_LEN
+        + size_of::<Addr>()
+        + constants::INLINE_DATA_LEN;
 }
 
 impl Serializable for Node {
     fn serialize<W: Write>(&self, writer: &mut W) -> Result<usize, Error> {
         let mut n = writer.write_u16(self.file_len)?;
+        n += writer.write_u16(self.links)?;
+        n += writer.write_u8(if self.inline { FLAG_INLINE } else { 0 })?;
         for addr in self.data_addrs() {
             n += writer.write_addr(*addr)?;
         }
+        n += writer.write_u32(self.created)?;
+        n += writer.write_u32(self.modified)?;
+        n += writer.write_u32(self.accessed)?;
+        n += self.permissions.serialize(writer)?;
+        n += self.xattrs.serialize(writer)?;
+        n += writer.write_addr(self.xattr_addr.unwrap_or(NO_XATTR_BLOCK))?;
+        n += writer.write(&self.inline_data)?;
         Ok(n)
     }
 }
@@ -55,11 +260,33 @@ impl Serializable for Node {
 impl Deserializable<Self> for Node {
     fn deserialize<R: Read>(reader: &mut R) -> Result<Self, Error> {
         let file_len = reader.read_u16()?;
+        let links = reader.read_u16()?;
+        let flags = reader.read_u8()?;
         let mut block_addrs = [0 as Addr; constants::NODE_DATA_BLOCKS_LEN];
         for addr in &mut block_addrs {
             *addr = reader.read_addr()?;
         }
-        Ok(Self { file_len, data_addrs: block_addrs })
+        let created = reader.read_u32()?;
+        let modified = reader.read_u32()?;
+        let accessed = reader.read_u32()?;
+        let permissions = Permissions::deserialize(reader)?;
+        let xattrs = Xattrs::deserialize(reader)?;
+        let xattr_addr = Some(reader.read_addr()?).filter(|addr| *addr != NO_XATTR_BLOCK);
+        let mut inline_data = [0; constants::INLINE_DATA_LEN];
+        reader.read(&mut inline_data)?;
+        Ok(Self {
+            file_len,
+            links,
+            inline: flags & FLAG_INLINE != 0,
+            data_addrs: block_addrs,
+            created,
+            modified,
+            accessed,
+            permissions,
+            xattrs,
+            xattr_addr,
+            inline_data,
+        })
     }
 }
 
@@ -70,7 +297,51 @@ mod tests {
 
     use super::*;
 
-    test_serde_symmetry!(Node, Node::new(5120, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10]));
+    test_serde_symmetry!(Node, {
+        let mut node = Node::new(5120, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10])
+            .with_created(1_700_000_000)
+            .with_permissions(Permissions::new(0o755, 1000, 100));
+        node.xattrs_mut().insert("user.type", b"text/plain").unwrap();
+        node.set_xattr_addr(Some(42));
+        node
+    });
+
+    mod inline {
+        use super::*;
+
+        test_serde_symmetry!(Node, Node::new_inline(b"key = value"));
+    }
+
+    #[test]
+    fn test_fits_in_a_block() {
+        assert_eq!(1, Node::BLOCKS_LEN);
+    }
+
+    #[test]
+    fn test_write_inline() {
+        let mut node = Node::new_inline(b"hello");
+        node.write_inline(3, b"p!");
+        assert_eq!(b"help!", node.inline_data());
+        node.write_inline(7, b"x");
+        assert_eq!(b"help!\0\0x", node.inline_data());
+        assert_eq!(0, node.blocks_needed());
+
+        node.clear_inline();
+        assert!(!node.is_inline());
+        assert_eq!(0, node.file_len());
+        assert_eq!(b"", node.inline_data());
+    }
+
+    #[test]
+    fn test_links() {
+        let mut node = Node::new(1, [0; N]);
+        assert_eq!(1, node.links());
+        assert_eq!(Ok(()), node.add_link());
+        assert_eq!(2, node.links());
+        assert_eq!(1, node.remove_link());
+        assert_eq!(0, node.remove_link());
+        assert_eq!(0, node.remove_link());
+    }
 
     #[test]
     fn test_node_blocks_needed() {
@@ -1,77 +1,192 @@
-use crate::{Error, constants};
+//! Paths are parsed once into a [`Path`], a list of validated components.
+//!
+//! Leading and trailing separators are ignored, `.` components are skipped and
+//! `..` components are resolved against the previous component. A path never
+//! escapes the root directory.
+
+use crate::{Error, constants, io::Writer};
 
 pub const SEPARATOR: char = '/';
 
-pub fn validate(path: &str) -> Result<(), Error> {
-    let first_component = first_component(path);
-    if first_component == path && path.len() < constants::NAME_LEN {
-        return Ok(());
+const CURRENT: &str = ".";
+const PARENT: &str = "..";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Path<'a> {
+    components: [&'a str; constants::PATH_MAX_DEPTH],
+    len: usize,
+}
+
+impl<'a> Path<'a> {
+    /// Maximum number of components a path can have once resolved.
+    pub const MAX_DEPTH: usize = constants::PATH_MAX_DEPTH;
+
+    /// Returns the root path, without components.
+    #[must_use]
+    pub const fn root() -> Self {
+        Self { components: [""; Self::MAX_DEPTH], len: 0 }
     }
-    if first_component.len() >= constants::NAME_LEN {
-        return Err(Error::NameTooLong);
+
+    /// Parses and validates `path`.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`] if a component is empty (`a//b`), contains forbidden
+    ///   characters, or `..` would escape the root directory.
+    /// - [`Error::NameTooLong`] if a component exceeds [`constants::NAME_LEN`].
+    /// - [`Error::PathTooDeep`] if the path exceeds [`Self::MAX_DEPTH`] components.
+    pub fn parse(path: &'a str) -> Result<Self, Error> {
+        let mut result = Self::root();
+        let path = path.trim_start_matches(SEPARATOR).trim_end_matches(SEPARATOR);
+        if path.is_empty() {
+            return Ok(result);
+        }
+
+        for component in path.split(SEPARATOR) {
+            match component {
+                CURRENT => {}
+                PARENT => {
+                    result.len = result.len.checked_sub(1).ok_or(Error::InvalidName)?;
+                }
+                name => {
+                    validate_name(name)?;
+                    if result.len == Self::MAX_DEPTH {
+                        return Err(Error::PathTooDeep);
+                    }
+                    result.components[result.len] = name;
+                    result.len += 1;
+                }
+            }
+        }
+        Ok(result)
     }
-    validate(tail(path))
-}
 
-pub fn dirname(path: &str) -> &str {
-    norm(path).rsplit_once(SEPARATOR).map(|(dirname, _)| dirname).unwrap_or_default()
-}
+    #[must_use]
+    pub fn components(&self) -> &[&'a str] {
+        &self.components[..self.len]
+    }
 
-pub fn tail(path: &str) -> &str {
-    let path = norm(path);
-    if dirname(path).is_empty() {
-        return path;
+    #[must_use]
+    pub const fn is_root(&self) -> bool {
+        self.len == 0
     }
-    let first = first_component(path);
-    norm(path.strip_prefix(first).unwrap())
 }
 
-pub fn first_component(path: &str) -> &str {
-    norm(path).split(SEPARATOR).next().unwrap_or("")
+/// Joins `parts` into `buf` using [`SEPARATOR`], skipping empty parts.
+///
+/// # Errors
+/// Returns [`Error::NameTooLong`] if the joined path does not fit in `buf`.
+pub fn join<'b, 'p>(
+    buf: &'b mut [u8],
+    parts: impl IntoIterator<Item = &'p str>,
+) -> Result<&'b str, Error> {
+    let mut writer = Writer::new(buf);
+    let mut len = 0;
+    for part in parts.into_iter().map(|part| part.trim_matches(SEPARATOR)) {
+        if part.is_empty() {
+            continue;
+        }
+        if len > 0 {
+            len += writer.write(&[SEPARATOR as u8]).map_err(|_| Error::NameTooLong)?;
+        }
+        len += writer.write(part.as_bytes()).map_err(|_| Error::NameTooLong)?;
+    }
+    str::from_utf8(&buf[..len]).map_err(|_| Error::InvalidName)
 }
 
-fn norm(path: &str) -> &str {
-    path.trim_start_matches(SEPARATOR).trim_end_matches(SEPARATOR)
+/// Validates a single path component, as stored in a directory entry.
+///
+/// # Errors
+/// - [`Error::InvalidName`] if the name is empty, reserved (`.` or `..`), or contains
+///   a separator, NUL or any other control character.
+/// - [`Error::NameTooLong`] if the name exceeds [`constants::NAME_LEN`] bytes.
+pub fn validate_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name == CURRENT || name == PARENT {
+        return Err(Error::InvalidName);
+    }
+    if name.len() > constants::NAME_LEN {
+        return Err(Error::NameTooLong);
+    }
+    if name.chars().any(|c| c == SEPARATOR || c.is_control()) {
+        return Err(Error::InvalidName);
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::string::String;
+
     use super::*;
 
     #[test]
-    fn test_validate() {
-        let mut input = "a".repeat(constants::NAME_LEN - 1);
-        assert!(validate(&input).is_ok());
+    fn test_parse() {
+        assert_eq!(Ok(&[][..]), Path::parse("").as_ref().map(Path::components));
+        assert_eq!(Ok(&[][..]), Path::parse("/").as_ref().map(Path::components));
+        assert_eq!(Ok(&["a"][..]), Path::parse("a").as_ref().map(Path::components));
+        assert_eq!(
+            Ok(&["path", "to", "file.txt"][..]),
+            Path::parse("/path/to/file.txt/").as_ref().map(Path::components)
+        );
+    }
+
+    #[test]
+    fn test_parse_name_length() {
+        let mut input = "a".repeat(constants::NAME_LEN);
+        assert!(Path::parse(&input).is_ok());
 
         input += "/a/b/c/d/";
-        assert!(validate(&input).is_ok());
+        assert!(Path::parse(&input).is_ok());
 
-        input += "a".repeat(constants::NAME_LEN).as_str();
-        assert_eq!(Error::NameTooLong, validate(&input).unwrap_err());
+        input += "a".repeat(constants::NAME_LEN + 1).as_str();
+        assert_eq!(Err(Error::NameTooLong), Path::parse(&input));
     }
 
     #[test]
-    fn test_dirname() {
-        assert_eq!("", dirname(""));
+    fn test_parse_max_depth() {
+        let input = "a/".repeat(Path::MAX_DEPTH);
+        assert_eq!(Ok(Path::MAX_DEPTH), Path::parse(&input).map(|path| path.components().len()));
+
+        let input = input + "a";
+        assert_eq!(Err(Error::PathTooDeep), Path::parse(&input));
+    }
 
-        assert_eq!("", dirname("/"));
+    #[test]
+    fn test_parse_resolves_dots() {
+        assert_eq!(Ok(&["a", "c"][..]), Path::parse("./a/./b/../c").as_ref().map(Path::components));
+        assert_eq!(Ok(&[][..]), Path::parse("a/..").as_ref().map(Path::components));
+        assert_eq!(Err(Error::InvalidName), Path::parse(".."));
+        assert_eq!(Err(Error::InvalidName), Path::parse("a/../../b"));
+    }
 
-        let input = "/path/to/file.txt";
-        assert_eq!("path/to", dirname(input));
+    #[test]
+    fn test_parse_rejects_empty_components() {
+        assert_eq!(Err(Error::InvalidName), Path::parse("a//b"));
+    }
 
-        let input = "/path/to/file.txt/";
-        assert_eq!("path/to", dirname(input));
+    #[test]
+    fn test_parse_rejects_forbidden_characters() {
+        assert_eq!(Err(Error::InvalidName), Path::parse("a/b\0c"));
+        assert_eq!(Err(Error::InvalidName), Path::parse("a/b\nc"));
+        assert_eq!(Err(Error::InvalidName), Path::parse("a/\u{7f}"));
+    }
 
-        let input = "file.txt";
-        assert_eq!("", dirname(input));
+    #[test]
+    fn test_join() {
+        let mut buf = [0u8; 16];
+        assert_eq!(Ok("a/b/c"), join(&mut buf, ["a", "/b/", "", "c"]));
+        assert_eq!(Ok(""), join(&mut buf, []));
+        assert_eq!(Err(Error::NameTooLong), join(&mut buf, ["abcdefgh", "abcdefgh"]));
     }
 
     #[test]
-    fn test_tail() {
-        let input = "foo/bar/baz";
-        assert_eq!("bar/baz", tail(input));
-        assert_eq!("baz", tail(tail(input)));
-        assert_eq!("baz", tail(tail(tail(input))));
+    fn test_validate_name() {
+        assert_eq!(Ok(()), validate_name("file.txt"));
+        assert_eq!(Ok(()), validate_name(&String::from("ñ").repeat(constants::NAME_LEN / 2)));
+        assert_eq!(Err(Error::InvalidName), validate_name(""));
+        assert_eq!(Err(Error::InvalidName), validate_name("."));
+        assert_eq!(Err(Error::InvalidName), validate_name(".."));
+        assert_eq!(Err(Error::InvalidName), validate_name("a/b"));
+        assert_eq!(Err(Error::NameTooLong), validate_name(&"a".repeat(constants::NAME_LEN + 1)));
     }
 }
@@ -0,0 +1,223 @@
+//! POSIX-style permission bits and ownership, kept for both files and directories.
+//!
+//! Permissions are only enforced when the controller is given an [`Identity`],
+//! the superuser (uid 0) is always granted access.
+
+use crate::{
+    Deserializable, Error, FixedLen, Serializable,
+    io::{Read, Write},
+};
+
+/// User and group performing the operations on a mounted device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    uid: u32,
+    gid: u32,
+}
+
+impl Identity {
+    /// The superuser, which is granted every access.
+    pub const ROOT: Self = Self::new(0, 0);
+
+    #[must_use]
+    pub const fn new(uid: u32, gid: u32) -> Self {
+        Self { uid, gid }
+    }
+
+    #[must_use]
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    #[must_use]
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    #[must_use]
+    pub const fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+impl Access {
+    const fn bits(self) -> u16 {
+        match self {
+            Self::Read => 0o4,
+            Self::Write => 0o2,
+            Self::Execute => 0o1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    mode: u16,
+    uid: u32,
+    gid: u32,
+}
+
+impl Permissions {
+    /// Mask of the bits kept in a mode: setuid, setgid, sticky and the `rwx` triplets.
+    pub const MODE_MASK: u16 = 0o7777;
+    /// Mode given to newly created files.
+    pub const FILE_MODE: u16 = 0o644;
+    /// Mode given to newly created directories.
+    pub const DIR_MODE: u16 = 0o755;
+
+    /// Creates new [`Permissions`], bits outside of [`Self::MODE_MASK`] are dropped.
+    #[must_use]
+    pub const fn new(mode: u16, uid: u32, gid: u32) -> Self {
+        Self { mode: mode & Self::MODE_MASK, uid, gid }
+    }
+
+    /// Permissions of a new file owned by `owner`, or by the superuser when there is none.
+    #[must_use]
+    pub const fn file(owner: Option<Identity>) -> Self {
+        let owner = match owner {
+            Some(owner) => owner,
+            None => Identity::ROOT,
+        };
+        Self::new(Self::FILE_MODE, owner.uid, owner.gid)
+    }
+
+    /// Permissions of a new directory owned by `owner`, or by the superuser when there is none.
+    #[must_use]
+    pub const fn dir(owner: Option<Identity>) -> Self {
+        let owner = match owner {
+            Some(owner) => owner,
+            None => Identity::ROOT,
+        };
+        Self::new(Self::DIR_MODE, owner.uid, owner.gid)
+    }
+
+    #[must_use]
+    pub const fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    #[must_use]
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    #[must_use]
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    #[must_use]
+    pub const fn with_mode(mut self, mode: u16) -> Self {
+        self.mode = mode & Self::MODE_MASK;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = uid;
+        self.gid = gid;
+        self
+    }
+
+    /// Whether `identity` is granted `access`, using the owner, group or other bits.
+    #[must_use]
+    pub const fn allows(&self, identity: &Identity, access: Access) -> bool {
+        if identity.is_root() {
+            return true;
+        }
+        let shift = if identity.uid == self.uid {
+            6
+        } else if identity.gid == self.gid {
+            3
+        } else {
+            0
+        };
+        (self.mode >> shift) & access.bits() != 0
+    }
+
+    /// Checks every access in `accesses` for `identity`, or grants them all when there is none.
+    ///
+    /// # Errors
+    /// Returns [`Error::PermissionDenied`] if any access is not granted.
+    pub fn check(&self, identity: Option<&Identity>, accesses: &[Access]) -> Result<(), Error> {
+        let Some(identity) = identity else {
+            return Ok(());
+        };
+        if accesses.iter().all(|access| self.allows(identity, *access)) {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied)
+        }
+    }
+}
+
+impl FixedLen for Permissions {
+    const BYTES_LEN: usize = size_of::<u16>() + 2 * size_of::<u32>();
+}
+
+impl Serializable for Permissions {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<usize, Error> {
+        let mut n = writer.write_u16(self.mode)?;
+        n += writer.write_u32(self.uid)?;
+        n += writer.write_u32(self.gid)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable<Self> for Permissions {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mode = reader.read_u16()?;
+        let uid = reader.read_u32()?;
+        let gid = reader.read_u32()?;
+        Ok(Self::new(mode, uid, gid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_serde_symmetry;
+
+    use super::*;
+
+    test_serde_symmetry!(Permissions, Permissions::new(0o4755, 1000, 100));
+
+    #[test]
+    fn test_allows() {
+        let sut = Permissions::new(0o640, 1000, 100);
+        let owner = Identity::new(1000, 100);
+        let group = Identity::new(1001, 100);
+        let other = Identity::new(1002, 200);
+
+        assert!(sut.allows(&owner, Access::Read));
+        assert!(sut.allows(&owner, Access::Write));
+        assert!(!sut.allows(&owner, Access::Execute));
+        assert!(sut.allows(&group, Access::Read));
+        assert!(!sut.allows(&group, Access::Write));
+        assert!(!sut.allows(&other, Access::Read));
+        assert!(sut.allows(&Identity::ROOT, Access::Execute));
+    }
+
+    #[test]
+    fn test_check() {
+        let sut = Permissions::new(0o500, 1000, 100);
+        let owner = Identity::new(1000, 100);
+        assert_eq!(Ok(()), sut.check(None, &[Access::Write]));
+        assert_eq!(Ok(()), sut.check(Some(&owner), &[Access::Read, Access::Execute]));
+        assert_eq!(
+            Err(Error::PermissionDenied),
+            sut.check(Some(&owner), &[Access::Write, Access::Execute])
+        );
+    }
+
+    #[test]
+    fn test_new_masks_mode() {
+        assert_eq!(0o755, Permissions::new(0o170_755, 0, 0).mode());
+    }
+}
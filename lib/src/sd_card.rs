@@ -0,0 +1,513 @@
+//! SD card driver over SPI, built on the [`embedded_hal`] traits.
+//!
+//! Commands and responses follow the SPI mode of the SD physical layer specification.
+//! Both standard capacity cards, addressed in bytes, and high capacity ones, addressed
+//! in blocks, are supported. CRCs are sent with every command but data CRCs are not
+//! checked, as cards leave them off in SPI mode.
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiBus};
+
+use crate::{Addr, BlockDevice, Error, block::Block};
+
+/// Resets the card into SPI mode.
+const CMD0: u8 = 0;
+/// Checks the card supports the supply voltage, which only version 2 cards answer.
+const CMD8: u8 = 8;
+/// Stops a multiple block read.
+const CMD12: u8 = 12;
+/// Sets the block length of standard capacity cards.
+const CMD16: u8 = 16;
+const CMD17: u8 = 17;
+const CMD18: u8 = 18;
+const CMD24: u8 = 24;
+const CMD25: u8 = 25;
+/// Announces that the next command is an application command.
+const CMD55: u8 = 55;
+/// Reads the operation conditions register, telling whether the card is high capacity.
+const CMD58: u8 = 58;
+/// Starts the initialization of the card.
+const ACMD41: u8 = 41;
+
+/// R1 flag set while the card is initializing.
+const R1_IDLE: u8 = 0x01;
+/// R1 flag set when the card does not know the command.
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+/// Voltage range and check pattern sent with [`CMD8`], and echoed back by the card.
+const IF_COND: u32 = 0x1AA;
+/// Host capacity support flag of [`ACMD41`], and card capacity status flag of the OCR.
+const HIGH_CAPACITY: u32 = 1 << 30;
+
+const TOKEN_START_BLOCK: u8 = 0xFE;
+const TOKEN_START_MULTIPLE_WRITE: u8 = 0xFC;
+const TOKEN_STOP_TRANSMISSION: u8 = 0xFD;
+/// Data response of a block the card accepted.
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Bytes polled for a response to a command before giving up.
+const RESPONSE_POLLS: usize = 10;
+/// Attempts of [`CMD0`] and [`ACMD41`], a millisecond apart.
+const INIT_ATTEMPTS: u32 = 1000;
+/// Polls for a data token or the end of a write, 10 µs apart.
+const BUSY_POLLS: u32 = 50_000;
+
+/// Error of an [`SdCard`], `E` being the error of its SPI bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdError<E> {
+    /// The SPI bus failed.
+    Spi(E),
+    /// The chip select pin failed.
+    ChipSelect,
+    /// The card did not answer, or stayed busy, for too long.
+    Timeout,
+    /// The card answered a command with the R1 error flags `r1`.
+    Command { cmd: u8, r1: u8 },
+    /// The card failed to read a block, with the data error token `token`.
+    Read { token: u8 },
+    /// The card rejected a block written, with the data response `response`.
+    Write { response: u8 },
+    /// The card does not support the supply voltage, or is not an SD card.
+    Unsupported,
+}
+
+/// SD card connected to an SPI bus, which only it uses, and a chip select pin.
+///
+/// The bus is driven directly rather than through an `SpiDevice`, as the card must
+/// stay selected while it is polled for responses. Initialization must run with a
+/// clock of 100 to 400 kHz, the bus may be sped up once [`SdCard::new`] returns.
+///
+/// As a [`BlockDevice`], failures are reported as [`Error::Io`], the cause being kept
+/// in [`SdCard::last_error`].
+pub struct SdCard<B, CS, D>
+where
+    B: SpiBus,
+{
+    bus: B,
+    cs: CS,
+    delay: D,
+    /// Whether the card is addressed in blocks rather than bytes.
+    high_capacity: bool,
+    last_error: Option<SdError<B::Error>>,
+}
+
+impl<B, CS, D> SdCard<B, CS, D>
+where
+    B: SpiBus,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    /// Initializes the card, switching it to SPI mode.
+    ///
+    /// # Errors
+    /// - [`SdError::Timeout`] if no card answers, or it does not finish initializing.
+    /// - [`SdError::Unsupported`] if the card is not a usable SD card.
+    pub fn new(bus: B, cs: CS, delay: D) -> Result<Self, SdError<B::Error>> {
+        let mut card = Self { bus, cs, delay, high_capacity: false, last_error: None };
+        card.init()?;
+        Ok(card)
+    }
+
+    /// Returns the bus, the chip select pin and the delay back.
+    pub fn release(self) -> (B, CS, D) {
+        (self.bus, self.cs, self.delay)
+    }
+
+    /// Whether the card is high or extended capacity (SDHC or SDXC).
+    #[must_use]
+    pub const fn is_high_capacity(&self) -> bool {
+        self.high_capacity
+    }
+
+    /// Returns the cause of the last operation that failed with [`Error::Io`].
+    #[must_use]
+    pub const fn last_error(&self) -> Option<&SdError<B::Error>> {
+        self.last_error.as_ref()
+    }
+
+    fn init(&mut self) -> Result<(), SdError<B::Error>> {
+        // At least 74 clock cycles with the card deselected enter native mode.
+        self.cs.set_high().map_err(|_| SdError::ChipSelect)?;
+        self.bus.write(&[0xFF; 10]).map_err(SdError::Spi)?;
+        self.selected(|card| {
+            card.retry(|card| Ok(card.command(CMD0, 0)? == R1_IDLE))?;
+
+            let r1 = card.command(CMD8, IF_COND)?;
+            let version_2 = r1 & R1_ILLEGAL_COMMAND == 0;
+            if version_2 {
+                expect(CMD8, r1, R1_IDLE)?;
+                let mut r7 = [0xFF; 4];
+                card.bus.transfer_in_place(&mut r7).map_err(SdError::Spi)?;
+                if u32::from_be_bytes(r7) & 0xFFF != IF_COND {
+                    return Err(SdError::Unsupported);
+                }
+            }
+
+            let arg = if version_2 { HIGH_CAPACITY } else { 0 };
+            card.retry(|card| {
+                let r1 = card.app_command(ACMD41, arg)?;
+                if r1 & !R1_IDLE != 0 {
+                    return Err(SdError::Command { cmd: ACMD41, r1 });
+                }
+                Ok(r1 == 0)
+            })?;
+
+            if version_2 {
+                let r1 = card.command(CMD58, 0)?;
+                expect(CMD58, r1, 0)?;
+                let mut ocr = [0xFF; 4];
+                card.bus.transfer_in_place(&mut ocr).map_err(SdError::Spi)?;
+                card.high_capacity = u32::from_be_bytes(ocr) & HIGH_CAPACITY != 0;
+            }
+            if !card.high_capacity {
+                let r1 = card.command(CMD16, Block::LEN as u32)?;
+                expect(CMD16, r1, 0)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads the block at `sector` into `buf`.
+    fn read_block(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), SdError<B::Error>> {
+        self.selected(|card| {
+            let r1 = card.command(CMD17, card.address(sector))?;
+            expect(CMD17, r1, 0)?;
+            card.receive(buf)
+        })
+    }
+
+    /// Reads consecutive blocks from `sector` into `buf`, with a single command.
+    fn read_blocks(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), SdError<B::Error>> {
+        self.selected(|card| {
+            let r1 = card.command(CMD18, card.address(sector))?;
+            expect(CMD18, r1, 0)?;
+            let received = buf.chunks_mut(Block::LEN).try_for_each(|chunk| card.receive(chunk));
+            // The transmission is stopped even when a block failed.
+            let r1 = card.command(CMD12, 0)?;
+            received?;
+            expect(CMD12, r1, 0)?;
+            card.wait_ready()
+        })
+    }
+
+    /// Writes `buf` to the block at `sector`.
+    fn write_block(&mut self, sector: Addr, buf: &[u8]) -> Result<(), SdError<B::Error>> {
+        self.selected(|card| {
+            let r1 = card.command(CMD24, card.address(sector))?;
+            expect(CMD24, r1, 0)?;
+            card.send(TOKEN_START_BLOCK, buf)
+        })
+    }
+
+    /// Writes `buf` to consecutive blocks from `sector`, with a single command.
+    fn write_blocks(&mut self, sector: Addr, buf: &[u8]) -> Result<(), SdError<B::Error>> {
+        self.selected(|card| {
+            let r1 = card.command(CMD25, card.address(sector))?;
+            expect(CMD25, r1, 0)?;
+            let sent = buf
+                .chunks(Block::LEN)
+                .try_for_each(|chunk| card.send(TOKEN_START_MULTIPLE_WRITE, chunk));
+            card.bus.write(&[TOKEN_STOP_TRANSMISSION, 0xFF]).map_err(SdError::Spi)?;
+            card.wait_ready()?;
+            sent
+        })
+    }
+
+    /// Selects the card for the duration of `f`, deselecting it even when `f` fails.
+    fn selected<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, SdError<B::Error>>,
+    ) -> Result<T, SdError<B::Error>> {
+        self.cs.set_low().map_err(|_| SdError::ChipSelect)?;
+        let result = f(self);
+        self.cs.set_high().map_err(|_| SdError::ChipSelect)?;
+        // The card releases its data line on the next clock after being deselected.
+        self.bus.write(&[0xFF]).map_err(SdError::Spi)?;
+        result
+    }
+
+    /// Runs `attempt` until it returns `true`, waiting a millisecond in between.
+    fn retry(
+        &mut self,
+        mut attempt: impl FnMut(&mut Self) -> Result<bool, SdError<B::Error>>,
+    ) -> Result<(), SdError<B::Error>> {
+        for _ in 0..INIT_ATTEMPTS {
+            if attempt(self)? {
+                return Ok(());
+            }
+            self.delay.delay_ms(1);
+        }
+        Err(SdError::Timeout)
+    }
+
+    /// Sends the command `cmd`, and returns its R1 response.
+    fn command(&mut self, cmd: u8, arg: u32) -> Result<u8, SdError<B::Error>> {
+        if cmd != CMD0 && cmd != CMD12 {
+            self.wait_ready()?;
+        }
+        let [a, b, c, d] = arg.to_be_bytes();
+        let mut frame = [0x40 | cmd, a, b, c, d, 0];
+        frame[5] = (crc7(&frame[..5]) << 1) | 1;
+        self.bus.write(&frame).map_err(SdError::Spi)?;
+        if cmd == CMD12 {
+            // The byte following the stop command is meaningless.
+            self.read_byte()?;
+        }
+
+        for _ in 0..RESPONSE_POLLS {
+            let r1 = self.read_byte()?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(SdError::Timeout)
+    }
+
+    fn app_command(&mut self, cmd: u8, arg: u32) -> Result<u8, SdError<B::Error>> {
+        let r1 = self.command(CMD55, 0)?;
+        if r1 & !R1_IDLE != 0 {
+            return Err(SdError::Command { cmd: CMD55, r1 });
+        }
+        self.command(cmd, arg)
+    }
+
+    /// Address of `sector` in the unit of the card.
+    const fn address(&self, sector: Addr) -> u32 {
+        if self.high_capacity { sector } else { sector * Block::LEN as u32 }
+    }
+
+    /// Receives a data block into `buf`, once the card sends its start token.
+    fn receive(&mut self, buf: &mut [u8]) -> Result<(), SdError<B::Error>> {
+        let token = self.poll(|byte| byte != 0xFF)?;
+        if token != TOKEN_START_BLOCK {
+            return Err(SdError::Read { token });
+        }
+        buf.fill(0xFF);
+        self.bus.transfer_in_place(buf).map_err(SdError::Spi)?;
+        let mut crc = [0xFF; 2];
+        self.bus.transfer_in_place(&mut crc).map_err(SdError::Spi)
+    }
+
+    /// Sends `buf` as a data block starting with `token`, and waits until it is written.
+    fn send(&mut self, token: u8, buf: &[u8]) -> Result<(), SdError<B::Error>> {
+        self.bus.write(&[0xFF, token]).map_err(SdError::Spi)?;
+        self.bus.write(buf).map_err(SdError::Spi)?;
+        self.bus.write(&[0xFF, 0xFF]).map_err(SdError::Spi)?;
+        let response = self.read_byte()? & 0x1F;
+        if response != DATA_ACCEPTED {
+            return Err(SdError::Write { response });
+        }
+        self.wait_ready()
+    }
+
+    /// Waits until the card no longer holds its data line low to signal it is busy.
+    fn wait_ready(&mut self) -> Result<(), SdError<B::Error>> {
+        self.poll(|byte| byte == 0xFF).map(|_| ())
+    }
+
+    /// Reads bytes until one matches `done`, and returns it.
+    fn poll(&mut self, done: impl Fn(u8) -> bool) -> Result<u8, SdError<B::Error>> {
+        for _ in 0..BUSY_POLLS {
+            let byte = self.read_byte()?;
+            if done(byte) {
+                return Ok(byte);
+            }
+            self.delay.delay_us(10);
+        }
+        Err(SdError::Timeout)
+    }
+
+    fn read_byte(&mut self) -> Result<u8, SdError<B::Error>> {
+        let mut byte = [0xFF];
+        self.bus.transfer_in_place(&mut byte).map_err(SdError::Spi)?;
+        Ok(byte[0])
+    }
+
+    /// Keeps the cause of a failure, and reports it as [`Error::Io`].
+    fn io<T>(&mut self, result: Result<T, SdError<B::Error>>) -> Result<T, Error> {
+        result.map_err(|err| {
+            self.last_error = Some(err);
+            Error::Io
+        })
+    }
+}
+
+impl<B, CS, D> BlockDevice for SdCard<B, CS, D>
+where
+    B: SpiBus,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    /// Reads the whole block, only copying the start of it to a shorter `buf`.
+    fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        let result = if buf.len() == Block::LEN {
+            self.read_block(sector, buf)
+        } else {
+            let mut block = Block::new();
+            let result = self.read_block(sector, &mut block);
+            result.map(|()| buf.copy_from_slice(&block[..buf.len()]))
+        };
+        self.io(result)
+    }
+
+    /// Writes a shorter `buf` over the start of the block, keeping the rest of it.
+    fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        let result = if buf.len() == Block::LEN {
+            self.write_block(sector, buf)
+        } else {
+            let mut block = Block::new();
+            self.read_block(sector, &mut block).and_then(|()| {
+                block[..buf.len()].copy_from_slice(buf);
+                self.write_block(sector, &block)
+            })
+        };
+        self.io(result)
+    }
+
+    /// Reads the sectors with a multiple block read.
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        if buf.len() <= Block::LEN {
+            return self.read(sector, buf);
+        }
+        let result = self.read_blocks(sector, buf);
+        self.io(result)
+    }
+
+    /// Writes the sectors with a multiple block write.
+    fn write_sectors(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        if buf.len() <= Block::LEN {
+            return self.write(sector, buf);
+        }
+        let result = self.write_blocks(sector, buf);
+        self.io(result)
+    }
+}
+
+/// Checks the R1 response `r1` of the command `cmd` is `expected`.
+const fn expect<E>(cmd: u8, r1: u8, expected: u8) -> Result<(), SdError<E>> {
+    if r1 == expected { Ok(()) } else { Err(SdError::Command { cmd, r1 }) }
+}
+
+/// CRC7 of a command frame, with the polynomial x^7 + x^3 + 1.
+pub fn crc7(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in bytes {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            crc <<= 1;
+            if (byte ^ crc) & 0x80 != 0 {
+                crc ^= 0x09;
+            }
+            byte <<= 1;
+        }
+    }
+    crc & 0x7F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::{MockCs, MockSdCard, MockSpi, NoDelay};
+    use std::vec::Vec;
+
+    type Card = SdCard<MockSpi, MockCs, NoDelay>;
+
+    fn mount(mock: &MockSdCard) -> Card {
+        let card = SdCard::new(mock.spi(), mock.cs(), NoDelay).expect("should initialize card");
+        mock.clear_commands();
+        card
+    }
+
+    fn blocks(count: u8) -> Vec<u8> {
+        (0..count).flat_map(|n| [n + 1; Block::LEN]).collect()
+    }
+
+    #[test]
+    fn test_crc7() {
+        assert_eq!(0x95, (crc7(&[0x40, 0, 0, 0, 0]) << 1) | 1);
+        assert_eq!(0x87, (crc7(&[0x48, 0, 0, 0x01, 0xAA]) << 1) | 1);
+    }
+
+    #[test]
+    fn test_init() {
+        let mock = MockSdCard::new(16).with_init_polls(3);
+        let card = SdCard::new(mock.spi(), mock.cs(), NoDelay).unwrap();
+        assert!(card.is_high_capacity());
+        let acmd41 = [CMD55, ACMD41];
+        let expected: Vec<u8> =
+            [CMD0, CMD8].into_iter().chain(acmd41.repeat(4)).chain([CMD58]).collect();
+        assert_eq!(expected, mock.commands());
+
+        let mock = MockSdCard::new(16).standard_capacity();
+        let card = SdCard::new(mock.spi(), mock.cs(), NoDelay).unwrap();
+        assert!(!card.is_high_capacity());
+        assert_eq!(Some(&CMD16), mock.commands().last());
+    }
+
+    #[test]
+    fn test_init_timeout() {
+        let mock = MockSdCard::new(16).with_init_polls(usize::MAX);
+        let card = SdCard::new(mock.spi(), mock.cs(), NoDelay);
+        assert_eq!(Some(SdError::Timeout), card.err());
+    }
+
+    #[test]
+    fn test_single_block() {
+        for mock in [MockSdCard::new(16), MockSdCard::new(16).standard_capacity()] {
+            let mut card = mount(&mock);
+            let block = blocks(1);
+            card.write(3, &block).unwrap();
+            let mut buf = [0; Block::LEN];
+            card.read(3, &mut buf).unwrap();
+
+            assert_eq!(block, buf);
+            assert_eq!(block, mock.sector(3));
+            assert_eq!([CMD24, CMD17][..], mock.commands());
+        }
+    }
+
+    #[test]
+    fn test_partial_block() {
+        let mock = MockSdCard::new(16);
+        let mut card = mount(&mock);
+        card.write(3, &blocks(1)).unwrap();
+        card.write(3, &[9; 100]).unwrap();
+        let mut buf = [0; 200];
+        card.read(3, &mut buf).unwrap();
+
+        assert_eq!([[9; 100], [1; 100]].concat(), buf);
+        assert_eq!([&[9; 100][..], &[1; Block::LEN - 100]].concat(), mock.sector(3));
+    }
+
+    #[test]
+    fn test_multiple_blocks() {
+        let mock = MockSdCard::new(16);
+        let mut card = mount(&mock);
+        let data = blocks(4);
+        card.write_sectors(5, &data).unwrap();
+        let mut buf = [0; 4 * Block::LEN];
+        card.read_sectors(5, &mut buf).unwrap();
+
+        assert_eq!(data, buf);
+        assert_eq!(data[Block::LEN..2 * Block::LEN], mock.sector(6));
+        assert_eq!([CMD25, CMD18, CMD12][..], mock.commands());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mock = MockSdCard::new(16).with_unreadable(2).with_unwritable(9);
+        let mut card = mount(&mock);
+        let mut buf = [0; 2 * Block::LEN];
+
+        assert_eq!(Err(Error::Io), card.read_sectors(1, &mut buf));
+        assert_eq!(Some(&SdError::Read { token: 0x04 }), card.last_error());
+        assert_eq!(Err(Error::Io), card.write(9, &buf[..Block::LEN]));
+        assert_eq!(Some(&SdError::Write { response: 0x0D }), card.last_error());
+        assert_eq!(Err(Error::Io), card.read(16, &mut buf[..Block::LEN]));
+        assert_eq!(Some(&SdError::Command { cmd: CMD17, r1: 0x40 }), card.last_error());
+
+        // The card keeps working after errors.
+        card.write_sectors(8, &blocks(2)).unwrap_err();
+        card.read_sectors(3, &mut buf).unwrap();
+        assert_eq!(blocks(1), mock.sector(8));
+    }
+}
@@ -0,0 +1,91 @@
+//! Adapters between the filesystem and [`std::io`], so that files can be passed to any
+//! std consumer.
+
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use crate::{
+    Error,
+    file_handle::{FileHandle, SeekOrigin},
+};
+
+impl Error {
+    /// Returns the [`ErrorKind`] closest to the error.
+    #[must_use]
+    pub const fn kind(self) -> ErrorKind {
+        match self {
+            Self::FileNotFound | Self::DirectoryNotFound | Self::XattrNotFound => {
+                ErrorKind::NotFound
+            }
+            Self::FileAlreadyExists => ErrorKind::AlreadyExists,
+            Self::PermissionDenied => ErrorKind::PermissionDenied,
+            Self::IsADirectory => ErrorKind::IsADirectory,
+            Self::NotADirectory => ErrorKind::NotADirectory,
+            Self::StorageFull | Self::DirectoryFull => ErrorKind::StorageFull,
+            Self::FileTooLarge => ErrorKind::FileTooLarge,
+            Self::TooManyLinks => ErrorKind::TooManyLinks,
+            Self::NameTooLong | Self::PathTooDeep | Self::InvalidName => ErrorKind::InvalidFilename,
+            Self::BufferTooSmall { .. }
+            | Self::NotASymlink
+            | Self::XattrTooLarge
+            | Self::InvalidSeek => ErrorKind::InvalidInput,
+            Self::UnsupportedDevice => ErrorKind::InvalidData,
+            Self::Io | Self::Unexpected => ErrorKind::Other,
+        }
+    }
+}
+
+/// Wraps the error, so that converting back to an [`Error`] gives it again.
+impl From<Error> for io::Error {
+    fn from(value: Error) -> Self {
+        Self::new(value.kind(), value)
+    }
+}
+
+/// Unwraps errors of the filesystem, any other error being [`Error::Io`].
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        value.get_ref().and_then(|err| err.downcast_ref::<Self>()).copied().unwrap_or(Self::Io)
+    }
+}
+
+impl Read for FileHandle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(Self::read(self, buf)?)
+    }
+}
+
+impl Write for FileHandle<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(Self::write(self, buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FileHandle<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        Ok(match pos {
+            SeekFrom::Start(pos) => self.seek_from(SeekOrigin::Start, pos),
+            SeekFrom::End(offset) => self.seek_from(SeekOrigin::End, offset),
+            SeekFrom::Current(offset) => self.seek_from(SeekOrigin::Current, offset),
+        }?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn test_error_round_trip() {
+        let err = io::Error::from(Error::StorageFull);
+        assert_eq!(ErrorKind::StorageFull, err.kind());
+        assert_eq!("no space left on device", err.to_string());
+        assert_eq!(Error::StorageFull, Error::from(err));
+
+        assert_eq!(Error::Io, Error::from(io::Error::from(ErrorKind::UnexpectedEof)));
+    }
+}
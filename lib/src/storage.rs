@@ -3,6 +3,7 @@ use crate::{
     block::Block,
     device_layout::DeviceLayout,
     io::{Reader, Writer},
+    node::HOLE,
 };
 
 /// Length of the buffer used to store/load data from the block device.
@@ -16,7 +17,7 @@ const BUFFER_LEN: usize = Block::LEN * 3;
 
 pub fn store<D, T>(device: &mut D, logical: Addr, object: &T) -> Result<(), Error>
 where
-    D: BlockDevice,
+    D: BlockDevice + ?Sized,
     T: DeviceAddr + Serializable,
 {
     assert!(T::BLOCKS_LEN <= 3, "nothing should serialize to more than 3 blocks");
@@ -30,9 +31,10 @@ where
     Ok(())
 }
 
+/// Stores `data` in the blocks at `block_addrs`, chunks whose address is a [`HOLE`] are skipped.
 pub fn store_data<D>(device: &mut D, block_addrs: &[Addr], data: &[u8]) -> Result<(), Error>
 where
-    D: BlockDevice,
+    D: BlockDevice + ?Sized,
 {
     assert!(
         block_addrs.len() >= data.len().div_ceil(Block::LEN),
@@ -43,6 +45,9 @@ where
 
     for (i, chunk) in data.chunks(Block::LEN).enumerate() {
         let addr = block_addrs[i];
+        if addr == HOLE {
+            continue;
+        }
         device.write(DeviceLayout::DATA.nth(addr), chunk)?;
     }
     Ok(())
@@ -50,7 +55,7 @@ where
 
 pub fn load<D, T>(device: &mut D, logical: Addr) -> Result<T, Error>
 where
-    D: BlockDevice,
+    D: BlockDevice + ?Sized,
     T: DeviceAddr + Deserializable<T>,
 {
     assert!(T::BLOCKS_LEN <= 3, "nothing should serialize to more than 3 blocks");
@@ -64,7 +69,7 @@ where
 
 pub fn erase<D, T>(device: &mut D, logical: Addr) -> Result<(), Error>
 where
-    D: BlockDevice,
+    D: BlockDevice + ?Sized,
     T: DeviceAddr + FixedLen,
 {
     let empty_block = Block::new();
@@ -91,20 +96,29 @@ mod tests {
     #[test]
     fn test_store_data_single_chunk() {
         let mut device = MockDevice::new();
-        assert_eq!(Ok(()), store_data(&mut device, &[0], b"hello world"));
+        assert_eq!(Ok(()), store_data(&mut device, &[1], b"hello world"));
         assert_eq!(1, device.writes.len());
-        device.assert_write(0, DeviceLayout::DATA.nth(0), b"hello world");
+        device.assert_write(0, DeviceLayout::DATA.nth(1), b"hello world");
     }
 
     #[test]
     fn test_store_data_multiple_chunks() {
         let mut device = MockDevice::new();
-        assert_eq!(Ok(()), store_data(&mut device, &[0, 1, 2, 3, 4], &[13u8; 2500]));
+        assert_eq!(Ok(()), store_data(&mut device, &[1, 2, 3, 4, 5], &[13u8; 2500]));
         assert_eq!(5, device.writes.len());
-        device.assert_write(0, DeviceLayout::DATA.nth(0), &[13u8; Block::LEN]);
-        device.assert_write(1, DeviceLayout::DATA.nth(1), &[13u8; Block::LEN]);
-        device.assert_write(2, DeviceLayout::DATA.nth(2), &[13u8; Block::LEN]);
-        device.assert_write(3, DeviceLayout::DATA.nth(3), &[13u8; Block::LEN]);
-        device.assert_write(4, DeviceLayout::DATA.nth(4), &[13u8; 452]);
+        device.assert_write(0, DeviceLayout::DATA.nth(1), &[13u8; Block::LEN]);
+        device.assert_write(1, DeviceLayout::DATA.nth(2), &[13u8; Block::LEN]);
+        device.assert_write(2, DeviceLayout::DATA.nth(3), &[13u8; Block::LEN]);
+        device.assert_write(3, DeviceLayout::DATA.nth(4), &[13u8; Block::LEN]);
+        device.assert_write(4, DeviceLayout::DATA.nth(5), &[13u8; 452]);
+    }
+
+    #[test]
+    fn test_store_data_skips_holes() {
+        let mut device = MockDevice::new();
+        assert_eq!(Ok(()), store_data(&mut device, &[1, HOLE, 3], &[0u8; 1500]));
+        assert_eq!(2, device.writes.len());
+        device.assert_write(0, DeviceLayout::DATA.nth(1), &[0u8; Block::LEN]);
+        device.assert_write(1, DeviceLayout::DATA.nth(3), &[0u8; 476]);
     }
 }
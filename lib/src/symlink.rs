@@ -0,0 +1,104 @@
+//! Symbolic links store their target path in a single data block, referenced
+//! directly by the directory entry of the link.
+//!
+//! Targets are kept as written, and only resolved when a path goes through the
+//! link. Relative targets are resolved against the directory holding the link.
+
+use crate::{
+    Block, Deserializable, DeviceAddr, Error, FixedLen, Serializable,
+    device_layout::DeviceLayout,
+    io::{Read, Write},
+    paths,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symlink {
+    target: [u8; Self::LEN],
+    len: u16,
+}
+
+impl Symlink {
+    /// Maximum length of a target path, the remaining bytes of the block store its length.
+    pub const LEN: usize = Block::LEN - size_of::<u16>();
+
+    /// Creates a new [`Symlink`] pointing at `target`.
+    ///
+    /// # Errors
+    /// - [`Error::InvalidName`] if the target is empty or contains control characters.
+    /// - [`Error::NameTooLong`] if the target exceeds [`Self::LEN`] bytes.
+    pub fn new(target: &str) -> Result<Self, Error> {
+        if target.is_empty() || target.chars().any(char::is_control) {
+            return Err(Error::InvalidName);
+        }
+        if target.len() > Self::LEN {
+            return Err(Error::NameTooLong);
+        }
+
+        let mut buf = [0u8; Self::LEN];
+        buf[..target.len()].copy_from_slice(target.as_bytes());
+        Ok(Self { target: buf, len: target.len() as u16 })
+    }
+
+    #[must_use]
+    pub fn target(&self) -> &str {
+        str::from_utf8(&self.target[..self.len as usize]).unwrap_or("<invalid utf8>")
+    }
+
+    /// Whether the target is resolved from the root directory, rather than from the
+    /// directory holding the link.
+    #[must_use]
+    pub fn is_absolute(&self) -> bool {
+        self.target().starts_with(paths::SEPARATOR)
+    }
+}
+
+impl DeviceAddr for Symlink {
+    const LAYOUT: DeviceLayout = DeviceLayout::DATA;
+}
+
+impl FixedLen for Symlink {
+    const BYTES_LEN: usize = Block::LEN;
+}
+
+impl Serializable for Symlink {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<usize, Error> {
+        let mut n = writer.write_u16(self.len)?;
+        n += writer.write(&self.target)?;
+        Ok(n)
+    }
+}
+
+impl Deserializable<Self> for Symlink {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let len = reader.read_u16()?;
+        if len as usize > Self::LEN {
+            return Err(Error::NameTooLong);
+        }
+        let mut target = [0u8; Self::LEN];
+        reader.read(&mut target)?;
+        Ok(Self { target, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_serde_symmetry;
+
+    use super::*;
+
+    test_serde_symmetry!(Symlink, Symlink::new("../some/target.txt").unwrap());
+
+    #[test]
+    fn test_new() {
+        assert_eq!(Ok("a/b"), Symlink::new("a/b").as_ref().map(Symlink::target));
+        assert_eq!(Err(Error::InvalidName), Symlink::new(""));
+        assert_eq!(Err(Error::InvalidName), Symlink::new("a\0b"));
+        assert_eq!(Err(Error::NameTooLong), Symlink::new(&"a".repeat(Symlink::LEN + 1)));
+    }
+
+    #[test]
+    fn test_is_absolute() {
+        assert!(Symlink::new("/a/b").unwrap().is_absolute());
+        assert!(!Symlink::new("a/b").unwrap().is_absolute());
+    }
+}
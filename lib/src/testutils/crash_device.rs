@@ -0,0 +1,142 @@
+use std::vec::Vec;
+
+use crate::{
+    Addr, BlockDevice, Controller, Error,
+    testutils::{MemoryDevice, fsck},
+};
+
+/// Wraps a [`MemoryDevice`] and records every write, so a test can rebuild the device
+/// as it was when power was lost right after any of them.
+///
+/// Writes reach the wrapped device immediately, the device starts from a copy of the
+/// contents it was created with.
+#[derive(Debug)]
+pub struct CrashDevice {
+    initial: MemoryDevice,
+    device: MemoryDevice,
+    pub writes: Vec<(Addr, Vec<u8>)>,
+}
+
+impl CrashDevice {
+    #[must_use]
+    pub fn new(device: MemoryDevice) -> Self {
+        Self { initial: device.clone(), device, writes: Vec::new() }
+    }
+
+    /// Returns the wrapped device, holding every recorded write.
+    #[must_use]
+    pub fn into_inner(self) -> MemoryDevice {
+        self.device
+    }
+
+    /// Returns a copy of the initial device with the first `writes` recorded writes
+    /// applied, as if power was lost right after them.
+    ///
+    /// With `torn_len`, only the first `torn_len` bytes of the last applied write reach
+    /// the device, the rest of its sectors keep their previous contents.
+    ///
+    /// # Panics
+    /// If more writes are requested than were recorded.
+    #[must_use]
+    pub fn replay(&self, writes: usize, torn_len: Option<usize>) -> MemoryDevice {
+        let mut device = self.initial.clone();
+        for (n, (sector, data)) in self.writes[..writes].iter().enumerate() {
+            let len = match torn_len {
+                Some(torn_len) if n + 1 == writes => torn_len.min(data.len()),
+                _ => data.len(),
+            };
+            let mut buf = data.clone();
+            let mut previous = std::vec![0; data.len()];
+            device.read(*sector, &mut previous).expect("memory device should read");
+            buf[len..].copy_from_slice(&previous[len..]);
+            device.write(*sector, &buf).expect("memory device should write");
+        }
+        device
+    }
+
+    /// Mounts the device as it was after every crash point, from before the first
+    /// recorded write to after the last one, and runs `check` on it along with the
+    /// number of applied writes. The device is then checked with [`fsck`].
+    ///
+    /// With `torn_len`, the last write of every crash point is torn, see [`Self::replay`].
+    ///
+    /// # Panics
+    /// If the device cannot be mounted at some crash point, when `check` panics, or
+    /// when the device is left inconsistent.
+    pub fn check_crash_points<F>(&self, torn_len: Option<usize>, mut check: F)
+    where
+        F: FnMut(usize, &mut Controller<MemoryDevice>),
+    {
+        for writes in 0..=self.writes.len() {
+            let device = self.replay(writes, torn_len);
+            let mut ctrl = Controller::mount(device)
+                .unwrap_or_else(|err| panic!("should mount after {writes} writes: {err}"));
+            check(writes, &mut ctrl);
+
+            let mut device = ctrl.unmount();
+            let report = fsck(&mut device)
+                .unwrap_or_else(|err| panic!("should check after {writes} writes: {err}"));
+            assert!(report.is_consistent(), "after {writes} writes: {report:?}");
+        }
+    }
+}
+
+impl BlockDevice for CrashDevice {
+    fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.device.read(sector, buf)
+    }
+
+    fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        self.writes.push((sector, buf.into()));
+        self.device.write(sector, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> CrashDevice {
+        let mut device = MemoryDevice::new(512, 4 * 512);
+        BlockDevice::write(&mut device, 1, &[1; 512]).unwrap();
+        let mut sut = CrashDevice::new(device);
+        sut.write(1, &[2; 512]).unwrap();
+        sut.write(2, &[3; 512]).unwrap();
+        sut
+    }
+
+    fn sector(device: &mut MemoryDevice, sector: Addr) -> Vec<u8> {
+        let mut buf = std::vec![0; 512];
+        BlockDevice::read(device, sector, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_replay() {
+        let sut = setup();
+        assert_eq!(2, sut.writes.len());
+
+        let mut device = sut.replay(0, None);
+        assert_eq!([1; 512], sector(&mut device, 1)[..]);
+        let mut device = sut.replay(1, None);
+        assert_eq!([2; 512], sector(&mut device, 1)[..]);
+        assert_eq!([0; 512], sector(&mut device, 2)[..]);
+        let mut device = sut.replay(2, None);
+        assert_eq!([3; 512], sector(&mut device, 2)[..]);
+        assert_eq!([3; 512], sector(&mut sut.into_inner(), 2)[..]);
+    }
+
+    #[test]
+    fn test_replay_torn() {
+        let sut = setup();
+
+        let mut device = sut.replay(1, Some(100));
+        let torn = sector(&mut device, 1);
+        assert_eq!([2; 100], torn[..100]);
+        assert!(torn[100..].iter().all(|byte| *byte == 1), "should keep previous contents");
+
+        let mut device = sut.replay(2, Some(600));
+        assert_eq!([2; 512], sector(&mut device, 1)[..], "only the last write is torn");
+        assert_eq!([3; 512], sector(&mut device, 2)[..]);
+    }
+}
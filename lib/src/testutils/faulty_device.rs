@@ -0,0 +1,168 @@
+use std::vec::Vec;
+
+use crate::{Addr, BlockDevice, Error};
+
+/// When a [`FaultyDevice`] fails an operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Operations on the sector fail.
+    Sector(Addr),
+    /// Operations fail once `n` operations succeeded.
+    After(usize),
+    /// Operations fail with a `percent` chance, drawn from a generator seeded with
+    /// `seed` so that failures are reproducible.
+    Random { percent: u8, seed: u64 },
+}
+
+/// Operations a [`FaultyDevice`] may fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operations {
+    Reads,
+    Writes,
+    All,
+}
+
+/// Wraps a [`BlockDevice`] and fails some of its operations with [`Error::Io`],
+/// as configured by its [`Fault`]s.
+///
+/// Failed operations never reach the wrapped device.
+#[derive(Debug)]
+pub struct FaultyDevice<D> {
+    device: D,
+    faults: Vec<Fault>,
+    operations: Operations,
+    /// State of the xorshift generator behind [`Fault::Random`].
+    state: u64,
+    /// Number of operations that reached the wrapped device.
+    pub succeeded: usize,
+    /// Number of failed operations.
+    pub failed: usize,
+}
+
+impl<D> FaultyDevice<D>
+where
+    D: BlockDevice,
+{
+    /// Wraps `device` without any fault, operations only fail once faults are added.
+    pub const fn new(device: D) -> Self {
+        Self {
+            device,
+            faults: Vec::new(),
+            operations: Operations::All,
+            state: 0,
+            succeeded: 0,
+            failed: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_fault(mut self, fault: Fault) -> Self {
+        if let Fault::Random { seed, .. } = fault {
+            // Xorshift gets stuck on 0, any other seed works.
+            self.state = seed.max(1);
+        }
+        self.faults.push(fault);
+        self
+    }
+
+    /// Only fails the given `operations`, the others always succeed.
+    #[must_use]
+    pub const fn failing(mut self, operations: Operations) -> Self {
+        self.operations = operations;
+        self
+    }
+
+    /// Removes every fault, so the device can be inspected after a failure.
+    pub fn heal(&mut self) {
+        self.faults.clear();
+    }
+
+    /// Returns the wrapped device.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    fn check(&mut self, sector: Addr, operation: Operations) -> Result<(), Error> {
+        let fails = (self.operations == Operations::All || self.operations == operation)
+            && self.faults.clone().into_iter().any(|fault| match fault {
+                Fault::Sector(faulty) => faulty == sector,
+                Fault::After(n) => self.succeeded >= n,
+                Fault::Random { percent, .. } => self.next_random() % 100 < u64::from(percent),
+            });
+        if fails {
+            self.failed += 1;
+            return Err(Error::Io);
+        }
+        self.succeeded += 1;
+        Ok(())
+    }
+}
+
+impl<D> BlockDevice for FaultyDevice<D>
+where
+    D: BlockDevice,
+{
+    fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.check(sector, Operations::Reads)?;
+        self.device.read(sector, buf)
+    }
+
+    fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        self.check(sector, Operations::Writes)?;
+        self.device.write(sector, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testutils::MemoryDevice;
+
+    use super::*;
+
+    fn setup() -> FaultyDevice<MemoryDevice> {
+        FaultyDevice::new(MemoryDevice::new(512, 16 * 512))
+    }
+
+    #[test]
+    fn test_sector() {
+        let mut sut = setup().with_fault(Fault::Sector(3)).failing(Operations::Writes);
+        let mut buf = [0; 512];
+        assert_eq!(Ok(()), sut.write(2, &[1; 512]));
+        assert_eq!(Err(Error::Io), sut.write(3, &[1; 512]));
+        assert_eq!(Ok(()), sut.read(3, &mut buf));
+        assert_eq!([0; 512], buf, "failed writes should not reach the device");
+        assert_eq!((2, 1), (sut.succeeded, sut.failed));
+
+        sut.heal();
+        assert_eq!(Ok(()), sut.write(3, &[1; 512]));
+    }
+
+    #[test]
+    fn test_after() {
+        let mut sut = setup().with_fault(Fault::After(2));
+        let mut buf = [0; 512];
+        assert_eq!(Ok(()), sut.write(0, &buf));
+        assert_eq!(Ok(()), sut.read(0, &mut buf));
+        assert_eq!(Err(Error::Io), sut.read(0, &mut buf));
+        assert_eq!(Err(Error::Io), sut.write(1, &buf));
+    }
+
+    #[test]
+    fn test_random_is_reproducible() {
+        let outcomes = |seed| {
+            let mut sut = setup().with_fault(Fault::Random { percent: 30, seed });
+            (0..100).map(|sector| sut.write(sector % 16, &[0; 512]).is_ok()).collect::<Vec<_>>()
+        };
+        let failures = outcomes(42).iter().filter(|ok| !**ok).count();
+        assert!((15..45).contains(&failures), "{failures} failures");
+        assert_eq!(outcomes(42), outcomes(42));
+        assert_ne!(outcomes(42), outcomes(7));
+    }
+}
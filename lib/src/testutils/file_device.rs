@@ -30,6 +30,10 @@ impl BlockDevice for FileDevice {
             .map_err(|e| io::Error::IO { io: e })?)
     }
 
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.read(sector, buf)
+    }
+
     fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
         Ok(self
             .file
@@ -0,0 +1,280 @@
+//! Consistency checker, cross-checking the directory tree against the allocation
+//! bitmaps and the link counts of the file nodes.
+
+use std::{vec, vec::Vec};
+
+use crate::{
+    Addr, Block, BlockDevice, Deserializable, Error,
+    allocator::Bitmap,
+    device_layout::DeviceLayout,
+    directory::{DirEntryKind, TreeNode},
+    node::{HOLE, Node},
+    storage,
+};
+
+/// Address space an address belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Area {
+    Tree,
+    Node,
+    Data,
+}
+
+impl Area {
+    const fn layout(self) -> DeviceLayout {
+        match self {
+            Self::Tree => DeviceLayout::TREE,
+            Self::Node => DeviceLayout::NODE,
+            Self::Data => DeviceLayout::DATA,
+        }
+    }
+
+    const fn bitmap(self) -> DeviceLayout {
+        match self {
+            Self::Tree => DeviceLayout::TREE_BITMAP,
+            Self::Node => DeviceLayout::NODE_BITMAP,
+            Self::Data => DeviceLayout::DATA_BITMAP,
+        }
+    }
+}
+
+/// Inconsistency that can corrupt files, unlike a leak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    /// The address is referenced, but free in its bitmap.
+    Unallocated(Area, Addr),
+    /// The address is past the end of its area.
+    OutOfRange(Area, Addr),
+    /// The address is referenced more than once, only nodes may be shared by hard links.
+    Shared(Area, Addr),
+    /// The node has fewer links than entries pointing at it, it would be released
+    /// while still in use.
+    LinkCount { node: Addr, links: u16, entries: u16 },
+}
+
+/// Outcome of [`fsck`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+    /// Addresses taken in their bitmap but never referenced, left behind when an
+    /// operation is interrupted. They waste space without corrupting anything.
+    ///
+    /// Nodes with more links than entries are reported as well, as they are never
+    /// released.
+    pub leaks: Vec<(Area, Addr)>,
+}
+
+impl Report {
+    /// Whether there is neither a problem nor a leak.
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty() && self.leaks.is_empty()
+    }
+
+    /// Whether there is no problem, leaks aside.
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Number of references to every address of an area.
+struct References {
+    area: Area,
+    counts: Vec<u16>,
+}
+
+impl References {
+    fn new(area: Area) -> Self {
+        Self { area, counts: vec![0; area.layout().entries_count() as usize] }
+    }
+
+    /// Counts a reference to `addr`, returning whether it is the first one.
+    fn add(&mut self, addr: Addr, problems: &mut Vec<Problem>) -> bool {
+        let Some(count) = self.counts.get_mut(addr as usize) else {
+            problems.push(Problem::OutOfRange(self.area, addr));
+            return false;
+        };
+        *count += 1;
+        if *count == 2 && self.area != Area::Node {
+            problems.push(Problem::Shared(self.area, addr));
+        }
+        *count == 1
+    }
+
+    /// Compares the references with the bitmap of the area.
+    fn check_bitmap<D>(&self, device: &mut D, report: &mut Report) -> Result<(), Error>
+    where
+        D: BlockDevice,
+    {
+        let mut block = Block::new();
+        for (i, sector) in self.area.bitmap().iter_sectors().enumerate() {
+            device.read(sector, &mut block)?;
+            let bitmap = Bitmap::deserialize(&mut block.reader())?;
+            for offset in 0..Bitmap::SLOTS {
+                let addr = i * Bitmap::SLOTS + offset;
+                let taken = bitmap.is_taken(offset as Addr);
+                match self.counts.get(addr) {
+                    Some(0) if taken => report.leaks.push((self.area, addr as Addr)),
+                    Some(count) if *count > 0 && !taken => {
+                        report.problems.push(Problem::Unallocated(self.area, addr as Addr));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks the consistency of a formatted device, only device errors are returned
+/// as [`Err`].
+///
+/// Every directory, file node and data block reachable from the root directory must
+/// be allocated and referenced once, except for nodes, referenced once per hard link.
+/// Allocated addresses that cannot be reached are reported as leaks.
+pub fn fsck<D>(device: &mut D) -> Result<Report, Error>
+where
+    D: BlockDevice,
+{
+    let mut report = Report::default();
+    let mut tree = References::new(Area::Tree);
+    let mut nodes = References::new(Area::Node);
+    let mut data = References::new(Area::Data);
+    // Data block 0 is reserved at format time, its address marks holes.
+    data.add(HOLE, &mut report.problems);
+
+    tree.add(0, &mut report.problems);
+    check_dir(device, 0, &mut tree, &mut nodes, &mut data, &mut report.problems)?;
+
+    for (addr, entries) in nodes.counts.iter().enumerate().filter(|(_, count)| **count > 0) {
+        let node: Node = storage::load(device, addr as Addr)?;
+        if node.links() < *entries {
+            let (node, links, entries) = (addr as Addr, node.links(), *entries);
+            report.problems.push(Problem::LinkCount { node, links, entries });
+        } else if node.links() > *entries {
+            report.leaks.push((Area::Node, addr as Addr));
+        }
+        let blocks = node.data_addrs().iter().filter(|addr| **addr != HOLE);
+        for block in blocks.chain(node.xattr_addr().as_ref()) {
+            data.add(*block, &mut report.problems);
+        }
+    }
+
+    for references in [&tree, &nodes, &data] {
+        references.check_bitmap(device, &mut report)?;
+    }
+    Ok(report)
+}
+
+fn check_dir<D>(
+    device: &mut D,
+    addr: Addr,
+    tree: &mut References,
+    nodes: &mut References,
+    data: &mut References,
+    problems: &mut Vec<Problem>,
+) -> Result<(), Error>
+where
+    D: BlockDevice,
+{
+    let dir: TreeNode = storage::load(device, addr)?;
+    for entry in dir.iter_entries() {
+        if !entry.name().is_inline() {
+            data.add(entry.name().overflow_addr(), problems);
+        }
+        match entry.kind() {
+            // Directories referenced twice are only walked once, so cycles terminate.
+            DirEntryKind::Dir => {
+                if tree.add(entry.addr(), problems) {
+                    check_dir(device, entry.addr(), tree, nodes, data, problems)?;
+                }
+            }
+            DirEntryKind::File => {
+                nodes.add(entry.addr(), problems);
+            }
+            DirEntryKind::Symlink => {
+                data.add(entry.addr(), problems);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Controller, allocator::Allocator, testutils::MemoryDevice};
+
+    use super::*;
+
+    fn setup() -> MemoryDevice {
+        let mut device = MemoryDevice::new(512, 8 * 1024 * 1024);
+        Controller::format(&mut device).unwrap();
+        let mut ctrl = Controller::mount(device).unwrap();
+        ctrl.create("dir/file.txt", &[1; 2000]).unwrap();
+        ctrl.create(&"long".repeat(20), b"name overflows").unwrap();
+        ctrl.link("dir/file.txt", "link.txt").unwrap();
+        ctrl.symlink("dir/file.txt", "symlink").unwrap();
+        ctrl.set_xattr("link.txt", "user.big", &[2; 100]).unwrap();
+        ctrl.unmount()
+    }
+
+    #[test]
+    fn test_clean() {
+        let mut device = setup();
+        let report = fsck(&mut device).unwrap();
+        assert!(report.is_clean(), "{report:?}");
+    }
+
+    #[test]
+    fn test_leak() {
+        let mut device = setup();
+        let addr = Allocator::new(DeviceLayout::DATA_BITMAP).allocate(&mut device).unwrap();
+
+        let report = fsck(&mut device).unwrap();
+        assert!(report.is_consistent(), "{report:?}");
+        assert_eq!(vec![(Area::Data, addr)], report.leaks);
+    }
+
+    #[test]
+    fn test_unallocated() {
+        let mut device = setup();
+        let node: Node = storage::load(&mut device, 0).unwrap();
+        let block = node.data_addrs()[1];
+        Allocator::new(DeviceLayout::DATA_BITMAP).release(&mut device, block).unwrap();
+
+        let report = fsck(&mut device).unwrap();
+        assert_eq!(vec![Problem::Unallocated(Area::Data, block)], report.problems);
+    }
+
+    #[test]
+    fn test_link_count() {
+        let mut device = setup();
+        let mut node: Node = storage::load(&mut device, 0).unwrap();
+        node.remove_link();
+        storage::store(&mut device, 0, &node).unwrap();
+
+        let report = fsck(&mut device).unwrap();
+        assert_eq!(vec![Problem::LinkCount { node: 0, links: 1, entries: 2 }], report.problems);
+
+        node.add_link().unwrap();
+        node.add_link().unwrap();
+        storage::store(&mut device, 0, &node).unwrap();
+        let report = fsck(&mut device).unwrap();
+        assert!(report.is_consistent(), "{report:?}");
+        assert_eq!(vec![(Area::Node, 0)], report.leaks);
+    }
+
+    #[test]
+    fn test_shared() {
+        let mut device = setup();
+        let mut node: Node = storage::load(&mut device, 0).unwrap();
+        let block = node.data_addrs()[0];
+        node.set_data_addr(1, block);
+        storage::store(&mut device, 0, &node).unwrap();
+
+        let report = fsck(&mut device).unwrap();
+        assert_eq!(Some(&Problem::Shared(Area::Data, block)), report.problems.first());
+        assert_eq!(1, report.leaks.len(), "the replaced block is leaked");
+    }
+}
@@ -12,8 +12,7 @@ use crate::Error;
 /// Simulates an actual volume, but in memory.
 ///
 /// This is useful for testing purposes, where we want to avoid writing to the actual disk.
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MemoryDevice {
     block_size: usize,
     data: Box<[u8]>,
@@ -98,6 +97,11 @@ impl BlockDevice for MemoryDevice {
         self.write(buf);
         Ok(())
     }
+
+    /// Reads all the sectors at once, counting a single read.
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        BlockDevice::read(self, sector, buf)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,29 @@
+use core::cell::Cell;
+
+use crate::{Clock, Timestamp};
+
+/// Clock that returns whatever time the test sets.
+///
+/// Mount with a reference to the clock, so the test can move time forward
+/// while the controller is mounted.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: Cell<Timestamp>,
+}
+
+impl MockClock {
+    #[must_use]
+    pub const fn new(now: Timestamp) -> Self {
+        Self { now: Cell::new(now) }
+    }
+
+    pub fn set(&self, now: Timestamp) {
+        self.now.set(now);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Timestamp {
+        self.now.get()
+    }
+}
@@ -0,0 +1,379 @@
+use core::{cell::RefCell, convert::Infallible};
+use std::{collections::VecDeque, rc::Rc, vec, vec::Vec};
+
+use embedded_hal::{delay::DelayNs, digital, spi};
+
+use crate::{Addr, block::Block, sd_card::crc7};
+
+const R1_READY: u8 = 0x00;
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+const R1_CRC_ERROR: u8 = 0x08;
+const R1_ADDRESS_ERROR: u8 = 0x20;
+const R1_PARAMETER_ERROR: u8 = 0x40;
+
+/// Data error token of a block the card failed to read.
+const TOKEN_ECC_FAILED: u8 = 0x04;
+/// Data error token of a multiple block read going past the end of the card.
+const TOKEN_OUT_OF_RANGE: u8 = 0x08;
+const DATA_ACCEPTED: u8 = 0xE5;
+const DATA_WRITE_ERROR: u8 = 0xED;
+
+/// What the card does with the bytes it receives.
+#[derive(Debug)]
+enum State {
+    Command,
+    /// Sending consecutive blocks, the next one being `sector`.
+    Reading {
+        sector: Addr,
+    },
+    /// Receiving one block, or consecutive ones if `multiple`, into `sector`.
+    Writing {
+        sector: Addr,
+        multiple: bool,
+        data: Option<Vec<u8>>,
+    },
+}
+
+#[derive(Debug)]
+struct Card {
+    data: Vec<u8>,
+    high_capacity: bool,
+    selected: bool,
+    state: State,
+    frame: Vec<u8>,
+    out: VecDeque<u8>,
+    app_command: bool,
+    /// Initialization polls still answered as idle, or `None` once the card is ready.
+    idle_polls: Option<usize>,
+    unreadable: Vec<Addr>,
+    unwritable: Vec<Addr>,
+    commands: Vec<u8>,
+}
+
+impl Card {
+    /// Clocks one byte in each direction, `mosi` being sent by the host.
+    fn exchange(&mut self, mosi: u8) -> u8 {
+        if !self.selected {
+            return 0xFF;
+        }
+        if self.out.is_empty()
+            && let State::Reading { sector } = self.state
+        {
+            self.send_block(sector);
+            self.state = State::Reading { sector: sector + 1 };
+        }
+        let miso = self.out.pop_front().unwrap_or(0xFF);
+
+        match &mut self.state {
+            State::Command | State::Reading { .. } => {
+                if !self.frame.is_empty() || mosi & 0xC0 == 0x40 {
+                    self.frame.push(mosi);
+                }
+                if self.frame.len() == 6 {
+                    let frame = core::mem::take(&mut self.frame);
+                    self.command(&frame);
+                }
+            }
+            State::Writing { multiple, data: data @ None, .. } => match mosi {
+                0xFE | 0xFC => *data = Some(Vec::with_capacity(Block::LEN + 2)),
+                0xFD if *multiple => {
+                    // Busy for a couple of bytes after the stuff byte.
+                    self.out.extend([0xFF, 0x00, 0x00]);
+                    self.state = State::Command;
+                }
+                _ => {}
+            },
+            State::Writing { sector, multiple, data: Some(data) } => {
+                data.push(mosi);
+                // The block is followed by its CRC.
+                if data.len() == Block::LEN + 2 {
+                    let (sector, multiple) = (*sector, *multiple);
+                    let block = core::mem::take(data);
+                    self.receive_block(sector, &block[..Block::LEN]);
+                    self.state = if multiple {
+                        State::Writing { sector: sector + 1, multiple, data: None }
+                    } else {
+                        State::Command
+                    };
+                }
+            }
+        }
+        miso
+    }
+
+    fn command(&mut self, frame: &[u8]) {
+        let cmd = frame[0] & 0x3F;
+        let arg = u32::from_be_bytes([frame[1], frame[2], frame[3], frame[4]]);
+        let app_command = core::mem::take(&mut self.app_command);
+        self.commands.push(cmd);
+
+        let idle = if self.idle_polls.is_some() { R1_IDLE } else { R1_READY };
+        if frame[5] != (crc7(&frame[..5]) << 1) | 1 {
+            self.respond(idle | R1_CRC_ERROR);
+            return;
+        }
+        match (cmd, app_command) {
+            (0, _) => {
+                self.idle_polls.get_or_insert(0);
+                self.state = State::Command;
+                self.respond(R1_IDLE);
+            }
+            (8, _) => {
+                self.respond(idle);
+                self.out.extend((arg & 0xFFF).to_be_bytes());
+            }
+            (12, _) => {
+                // The stuff byte, then the response and a busy byte.
+                self.out.clear();
+                self.state = State::Command;
+                self.out.extend([0xFF, R1_READY, 0x00]);
+            }
+            (55, _) => {
+                self.app_command = true;
+                self.respond(idle);
+            }
+            (41, true) => {
+                self.idle_polls = self.idle_polls.and_then(|polls| polls.checked_sub(1));
+                self.respond(if self.idle_polls.is_some() { R1_IDLE } else { R1_READY });
+            }
+            (58, _) => {
+                self.respond(idle);
+                let ocr = if self.high_capacity && idle == R1_READY { 0xC0 } else { 0x80 };
+                self.out.extend([ocr, 0xFF, 0x80, 0x00]);
+            }
+            (16 | 17 | 18 | 24 | 25, _) if idle == R1_IDLE => {
+                self.respond(idle | R1_ILLEGAL_COMMAND)
+            }
+            (16, _) => {
+                let valid = !self.high_capacity && arg == Block::LEN as u32;
+                self.respond(if valid { R1_READY } else { R1_PARAMETER_ERROR });
+            }
+            (17 | 18 | 24 | 25, _) => match self.sector(arg) {
+                Err(r1) => self.respond(r1),
+                Ok(sector) => {
+                    self.respond(R1_READY);
+                    self.state = match cmd {
+                        17 => {
+                            self.send_block(sector);
+                            State::Command
+                        }
+                        18 => State::Reading { sector },
+                        _ => State::Writing { sector, multiple: cmd == 25, data: None },
+                    };
+                }
+            },
+            _ => self.respond(idle | R1_ILLEGAL_COMMAND),
+        }
+    }
+
+    /// Queues the R1 response `r1`, after a byte of delay.
+    fn respond(&mut self, r1: u8) {
+        self.out.extend([0xFF, r1]);
+    }
+
+    /// Returns the sector at the address `arg`, or the R1 error flags.
+    const fn sector(&self, arg: u32) -> Result<Addr, u8> {
+        let sector = if self.high_capacity {
+            arg
+        } else if arg.is_multiple_of(Block::LEN as u32) {
+            arg / Block::LEN as u32
+        } else {
+            return Err(R1_ADDRESS_ERROR);
+        };
+        if (sector as usize + 1) * Block::LEN > self.data.len() {
+            return Err(R1_PARAMETER_ERROR);
+        }
+        Ok(sector)
+    }
+
+    fn send_block(&mut self, sector: Addr) {
+        self.out.push_back(0xFF);
+        let start = sector as usize * Block::LEN;
+        if start + Block::LEN > self.data.len() {
+            self.out.push_back(TOKEN_OUT_OF_RANGE);
+            return;
+        }
+        if self.unreadable.contains(&sector) {
+            self.out.push_back(TOKEN_ECC_FAILED);
+            return;
+        }
+        self.out.push_back(0xFE);
+        self.out.extend(&self.data[start..start + Block::LEN]);
+        self.out.extend([0xFF, 0xFF]);
+    }
+
+    fn receive_block(&mut self, sector: Addr, block: &[u8]) {
+        if self.unwritable.contains(&sector) {
+            self.out.extend([DATA_WRITE_ERROR, 0x00]);
+            return;
+        }
+        let start = sector as usize * Block::LEN;
+        self.data[start..start + Block::LEN].copy_from_slice(block);
+        self.out.extend([DATA_ACCEPTED, 0x00, 0x00]);
+    }
+}
+
+/// SD card emulated in memory, answering the SPI mode commands an
+/// [`SdCard`](crate::SdCard) sends through [`MockSdCard::spi`] and [`MockSdCard::cs`].
+///
+/// The card is high capacity, unless built with [`MockSdCard::standard_capacity`].
+#[derive(Debug, Clone)]
+pub struct MockSdCard(Rc<RefCell<Card>>);
+
+impl MockSdCard {
+    #[must_use]
+    pub fn new(sectors: Addr) -> Self {
+        Self(Rc::new(RefCell::new(Card {
+            data: vec![0; sectors as usize * Block::LEN],
+            high_capacity: true,
+            selected: false,
+            state: State::Command,
+            frame: Vec::new(),
+            out: VecDeque::new(),
+            app_command: false,
+            idle_polls: Some(0),
+            unreadable: Vec::new(),
+            unwritable: Vec::new(),
+            commands: Vec::new(),
+        })))
+    }
+
+    /// Addresses the card in bytes, as cards of up to 2 GB are.
+    #[must_use]
+    pub fn standard_capacity(self) -> Self {
+        self.0.borrow_mut().high_capacity = false;
+        self
+    }
+
+    /// Answers `polls` initialization polls as still idle, before being ready.
+    #[must_use]
+    pub fn with_init_polls(self, polls: usize) -> Self {
+        self.0.borrow_mut().idle_polls = Some(polls);
+        self
+    }
+
+    /// Fails reads of `sector` with a data error token.
+    #[must_use]
+    pub fn with_unreadable(self, sector: Addr) -> Self {
+        self.0.borrow_mut().unreadable.push(sector);
+        self
+    }
+
+    /// Rejects writes to `sector` with a write error data response.
+    #[must_use]
+    pub fn with_unwritable(self, sector: Addr) -> Self {
+        self.0.borrow_mut().unwritable.push(sector);
+        self
+    }
+
+    /// Returns the SPI bus the card is connected to.
+    #[must_use]
+    pub fn spi(&self) -> MockSpi {
+        MockSpi(self.clone())
+    }
+
+    /// Returns the chip select pin of the card.
+    #[must_use]
+    pub fn cs(&self) -> MockCs {
+        MockCs(self.clone())
+    }
+
+    /// Returns the index of every command received so far.
+    #[must_use]
+    pub fn commands(&self) -> Vec<u8> {
+        self.0.borrow().commands.clone()
+    }
+
+    pub fn clear_commands(&self) {
+        self.0.borrow_mut().commands.clear();
+    }
+
+    /// Returns the contents of `sector`.
+    #[must_use]
+    pub fn sector(&self, sector: Addr) -> Vec<u8> {
+        let start = sector as usize * Block::LEN;
+        self.0.borrow().data[start..start + Block::LEN].to_vec()
+    }
+}
+
+/// SPI bus of a [`MockSdCard`].
+#[derive(Debug)]
+pub struct MockSpi(MockSdCard);
+
+impl spi::ErrorType for MockSpi {
+    type Error = Infallible;
+}
+
+impl spi::SpiBus for MockSpi {
+    fn read(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+        let mut card = self.0.0.borrow_mut();
+        for word in words {
+            *word = card.exchange(0xFF);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+        let mut card = self.0.0.borrow_mut();
+        for word in words {
+            card.exchange(*word);
+        }
+        Ok(())
+    }
+
+    fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Infallible> {
+        let mut card = self.0.0.borrow_mut();
+        for i in 0..read.len().max(write.len()) {
+            let miso = card.exchange(write.get(i).copied().unwrap_or(0xFF));
+            if let Some(word) = read.get_mut(i) {
+                *word = miso;
+            }
+        }
+        Ok(())
+    }
+
+    fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Infallible> {
+        let mut card = self.0.0.borrow_mut();
+        for word in words {
+            *word = card.exchange(*word);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Chip select pin of a [`MockSdCard`], active low.
+#[derive(Debug)]
+pub struct MockCs(MockSdCard);
+
+impl digital::ErrorType for MockCs {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for MockCs {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.0.borrow_mut().selected = true;
+        Ok(())
+    }
+
+    /// Deselecting drops a partly received command, and the bytes not read yet.
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        let mut card = self.0.0.borrow_mut();
+        card.selected = false;
+        card.frame.clear();
+        card.out.clear();
+        Ok(())
+    }
+}
+
+/// Delay returning right away, the emulated card answering without one.
+#[derive(Debug, Default)]
+pub struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _: u32) {}
+}
@@ -1,10 +1,24 @@
+pub use crash_device::CrashDevice;
+pub use faulty_device::{Fault, FaultyDevice, Operations};
 pub use file_device::FileDevice;
+pub use fsck::{Area, Problem, Report, fsck};
 pub use memory_device::MemoryDevice;
+pub use mock_clock::MockClock;
 pub use mock_device::MockDevice;
+#[cfg(feature = "sd-card")]
+pub use mock_sd_card::{MockCs, MockSdCard, MockSpi, NoDelay};
+pub use tracing_device::{AccessKind, Operation, Region, SectorAccess, Trace, TracingDevice};
 
+mod crash_device;
+mod faulty_device;
 mod file_device;
+mod fsck;
 mod memory_device;
+mod mock_clock;
 mod mock_device;
+#[cfg(feature = "sd-card")]
+mod mock_sd_card;
+mod tracing_device;
 
 #[macro_export]
 macro_rules! test_serde_symmetry {
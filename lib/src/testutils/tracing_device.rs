@@ -0,0 +1,283 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{Addr, BlockDevice, Error, block::Block, device_layout::DeviceLayout};
+
+/// Region of the device layout holding a sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Meta,
+    TreeBitmap,
+    DataBitmap,
+    NodeBitmap,
+    Tree,
+    File,
+    Node,
+    Data,
+}
+
+impl Region {
+    pub const ALL: [Self; 8] = [
+        Self::Meta,
+        Self::TreeBitmap,
+        Self::DataBitmap,
+        Self::NodeBitmap,
+        Self::Tree,
+        Self::File,
+        Self::Node,
+        Self::Data,
+    ];
+
+    /// Returns the region holding `sector`, if it is part of the layout.
+    #[must_use]
+    pub fn of(sector: Addr) -> Option<Self> {
+        Self::ALL.into_iter().find(|region| region.layout().iter_sectors().contains(&sector))
+    }
+
+    /// Returns the sectors spanned by each entry of the region, such as a tree node.
+    #[must_use]
+    pub const fn entry_sectors(self) -> usize {
+        let layout = self.layout();
+        (layout.sector_count() / layout.entries_count()) as usize
+    }
+
+    const fn layout(self) -> DeviceLayout {
+        match self {
+            Self::Meta => DeviceLayout::META,
+            Self::TreeBitmap => DeviceLayout::TREE_BITMAP,
+            Self::DataBitmap => DeviceLayout::DATA_BITMAP,
+            Self::NodeBitmap => DeviceLayout::NODE_BITMAP,
+            Self::Tree => DeviceLayout::TREE,
+            Self::File => DeviceLayout::FILE,
+            Self::Node => DeviceLayout::NODE,
+            Self::Data => DeviceLayout::DATA,
+        }
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Meta => "meta",
+            Self::TreeBitmap => "tree-bmp",
+            Self::DataBitmap => "data-bmp",
+            Self::NodeBitmap => "node-bmp",
+            Self::Tree => "tree",
+            Self::File => "file",
+            Self::Node => "node",
+            Self::Data => "data",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+/// A sector read or written by a [`TracingDevice`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectorAccess {
+    pub kind: AccessKind,
+    pub sector: Addr,
+}
+
+impl SectorAccess {
+    #[must_use]
+    pub fn region(&self) -> Option<Region> {
+        Region::of(self.sector)
+    }
+}
+
+/// Accesses recorded between two calls to [`Trace::begin`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    pub label: String,
+    pub accesses: Vec<SectorAccess>,
+}
+
+impl Operation {
+    /// Counts the accesses of `kind` to sectors of `region`.
+    #[must_use]
+    pub fn count(&self, kind: AccessKind, region: Region) -> usize {
+        let accesses = self.accesses.iter().filter(|access| access.kind == kind);
+        accesses.filter(|access| access.region() == Some(region)).count()
+    }
+
+    #[must_use]
+    pub fn reads(&self, region: Region) -> usize {
+        self.count(AccessKind::Read, region)
+    }
+
+    #[must_use]
+    pub fn writes(&self, region: Region) -> usize {
+        self.count(AccessKind::Write, region)
+    }
+}
+
+/// Log of the accesses of a [`TracingDevice`], grouped by operation. It is shared
+/// with the device, so operations can be labeled while a controller owns it.
+///
+/// Displaying it prints the reads and writes of every operation, per region.
+#[derive(Debug, Clone, Default)]
+pub struct Trace(Rc<RefCell<Vec<Operation>>>);
+
+impl Trace {
+    /// Starts a new operation, the following accesses are recorded under `label`.
+    pub fn begin(&self, label: &str) {
+        let operation = Operation { label: label.to_string(), accesses: Vec::new() };
+        self.0.borrow_mut().push(operation);
+    }
+
+    /// Returns the operations recorded so far, accesses made before the first
+    /// [`Self::begin`] being grouped under an empty label.
+    #[must_use]
+    pub fn operations(&self) -> Vec<Operation> {
+        self.0.borrow().clone()
+    }
+
+    /// Returns the last operation labeled `label`.
+    #[must_use]
+    pub fn operation(&self, label: &str) -> Option<Operation> {
+        self.0.borrow().iter().rev().find(|operation| operation.label == label).cloned()
+    }
+
+    /// Forgets every recorded operation.
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+
+    fn record(&self, access: SectorAccess) {
+        let mut operations = self.0.borrow_mut();
+        if operations.is_empty() {
+            operations.push(Operation { label: String::new(), accesses: Vec::new() });
+        }
+        if let Some(operation) = operations.last_mut() {
+            operation.accesses.push(access);
+        }
+    }
+}
+
+/// Gathers operations recorded by several devices in a single trace.
+impl FromIterator<Operation> for Trace {
+    fn from_iter<I: IntoIterator<Item = Operation>>(iter: I) -> Self {
+        Self(Rc::new(RefCell::new(iter.into_iter().collect())))
+    }
+}
+
+impl fmt::Display for Trace {
+    /// Prints one line per operation, with the reads and writes of each region as
+    /// `reads/writes`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operations = self.0.borrow();
+        let width = operations.iter().map(|operation| operation.label.len()).fold(16, usize::max);
+        write!(f, "{:<width$}", "operation")?;
+        for region in Region::ALL {
+            write!(f, " {:>8}", region.name())?;
+        }
+        writeln!(f)?;
+        for operation in operations.iter() {
+            write!(f, "{:<width$}", operation.label)?;
+            for region in Region::ALL {
+                let cell = std::format!("{}/{}", operation.reads(region), operation.writes(region));
+                write!(f, " {cell:>8}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wraps a [`BlockDevice`] and records every sector it reads or writes in a [`Trace`].
+#[derive(Debug)]
+pub struct TracingDevice<D> {
+    device: D,
+    trace: Trace,
+}
+
+impl<D> TracingDevice<D> {
+    #[must_use]
+    pub fn new(device: D) -> Self {
+        Self { device, trace: Trace::default() }
+    }
+
+    /// Returns the trace the device records its accesses to.
+    #[must_use]
+    pub fn trace(&self) -> Trace {
+        self.trace.clone()
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: BlockDevice> BlockDevice for TracingDevice<D> {
+    fn read(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        self.trace.record(SectorAccess { kind: AccessKind::Read, sector });
+        self.device.read(sector, buf)
+    }
+
+    fn write(&mut self, sector: Addr, buf: &[u8]) -> Result<(), Error> {
+        self.trace.record(SectorAccess { kind: AccessKind::Write, sector });
+        self.device.write(sector, buf)
+    }
+
+    /// Records every sector, but reads them with a single call to the wrapped device.
+    fn read_sectors(&mut self, sector: Addr, buf: &mut [u8]) -> Result<(), Error> {
+        for sector in (sector..).take(buf.len().div_ceil(Block::LEN)) {
+            self.trace.record(SectorAccess { kind: AccessKind::Read, sector });
+        }
+        self.device.read_sectors(sector, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::MemoryDevice;
+
+    #[test]
+    fn test_region_of() {
+        assert_eq!(Some(Region::Meta), Region::of(0));
+        assert_eq!(Some(Region::TreeBitmap), Region::of(DeviceLayout::TREE_BITMAP.begin()));
+        assert_eq!(Some(Region::Tree), Region::of(DeviceLayout::TREE.nth(1) + 1));
+        assert_eq!(Some(Region::Data), Region::of(DeviceLayout::DATA.end() - 1));
+        assert_eq!(None, Region::of(DeviceLayout::DATA.end()));
+        assert_eq!(3, Region::Tree.entry_sectors());
+        assert_eq!(1, Region::Node.entry_sectors());
+    }
+
+    #[test]
+    fn test_records_per_operation() {
+        let mut device = TracingDevice::new(MemoryDevice::fit(DeviceLayout::DATA.nth(1)));
+        let trace = device.trace();
+        let mut block = Block::new();
+
+        device.read(0, &mut block).unwrap();
+        trace.begin("store");
+        device.write(DeviceLayout::NODE.nth(0), &block).unwrap();
+        device.write(DeviceLayout::DATA.nth(0), &block).unwrap();
+        device.read(DeviceLayout::DATA.nth(0), &mut block).unwrap();
+
+        let operations = trace.operations();
+        assert_eq!(
+            ["", "store"],
+            operations.iter().map(|op| op.label.as_str()).collect::<Vec<_>>()[..]
+        );
+        assert_eq!(1, operations[0].reads(Region::Meta));
+        let store = trace.operation("store").unwrap();
+        assert_eq!((0, 1), (store.reads(Region::Node), store.writes(Region::Node)));
+        assert_eq!((1, 1), (store.reads(Region::Data), store.writes(Region::Data)));
+
+        let report = trace.to_string();
+        let store_line = report.lines().nth(2).unwrap();
+        assert!(store_line.starts_with("store "), "{report}");
+        assert!(store_line.ends_with("0/1      1/1"), "{report}");
+    }
+}
@@ -0,0 +1,214 @@
+//! Extended attributes are small key/value pairs attached to a file.
+//!
+//! They live in a short inline area of the [`crate::node::Node`], and spill over to a
+//! dedicated data block referenced from the node once the inline area is full. Both
+//! areas share the same encoding: a list of entries made of a key length (`u8`), a
+//! value length (`u16`), the key and the value, ending at the first zero key length.
+
+use core::iter;
+
+use crate::{
+    Block, Deserializable, DeviceAddr, Error, FixedLen, Serializable,
+    device_layout::DeviceLayout,
+    io::{Read, Write},
+};
+
+/// Bytes used by the lengths preceding each entry.
+const HEADER_LEN: usize = size_of::<u8>() + size_of::<u16>();
+
+/// Attributes stored in a data block of their own.
+pub type XattrBlock = Xattrs<{ Block::LEN }>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xattrs<const N: usize> {
+    buf: [u8; N],
+    /// Number of bytes used by entries.
+    len: usize,
+}
+
+impl<const N: usize> Default for Xattrs<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Xattrs<N> {
+    /// Maximum length of a key in bytes.
+    pub const KEY_LEN: usize = u8::MAX as usize;
+
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { buf: [0; N], len: 0 }
+    }
+
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries().find(|(_, k, _)| *k == key.as_bytes()).map(|(_, _, value)| value)
+    }
+
+    /// Adds the attribute `key`, which must not already be present.
+    ///
+    /// # Errors
+    /// Returns [`Error::XattrTooLarge`] if the attribute does not fit in the remaining space.
+    pub fn insert(&mut self, key: &str, value: &[u8]) -> Result<(), Error> {
+        let entry_len = HEADER_LEN + key.len() + value.len();
+        if self.len + entry_len > N || value.len() > u16::MAX as usize {
+            return Err(Error::XattrTooLarge);
+        }
+
+        let entry = &mut self.buf[self.len..self.len + entry_len];
+        entry[0] = key.len() as u8;
+        entry[1..HEADER_LEN].copy_from_slice(&(value.len() as u16).to_le_bytes());
+        entry[HEADER_LEN..HEADER_LEN + key.len()].copy_from_slice(key.as_bytes());
+        entry[HEADER_LEN + key.len()..].copy_from_slice(value);
+        self.len += entry_len;
+        Ok(())
+    }
+
+    /// Removes the attribute `key`, returning whether it was present.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let Some((offset, k, value)) = self.entries().find(|(_, k, _)| *k == key.as_bytes()) else {
+            return false;
+        };
+        let entry_len = HEADER_LEN + k.len() + value.len();
+        self.buf.copy_within(offset + entry_len..self.len, offset);
+        self.len -= entry_len;
+        self.buf[self.len..].fill(0);
+        true
+    }
+
+    /// Iterates over the keys and values of the attributes, in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.entries().map(|(_, key, value)| (key, value))
+    }
+
+    fn entries(&self) -> impl Iterator<Item = (usize, &[u8], &[u8])> {
+        let mut offset = 0;
+        iter::from_fn(move || {
+            let (key, value) = parse_entry(&self.buf[offset..self.len])?;
+            let entry = (offset, key, value);
+            offset += HEADER_LEN + key.len() + value.len();
+            Some(entry)
+        })
+    }
+}
+
+/// Parses the entry at the beginning of `buf`, if any.
+fn parse_entry(buf: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (&key_len, rest) = buf.split_first()?;
+    if key_len == 0 || rest.len() < HEADER_LEN - 1 {
+        return None;
+    }
+    let value_len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+    let rest = &rest[HEADER_LEN - 1..];
+    let key = rest.get(..key_len as usize)?;
+    let value = rest.get(key_len as usize..key_len as usize + value_len)?;
+    Some((key, value))
+}
+
+/// Validates the key of an extended attribute.
+///
+/// # Errors
+/// - [`Error::InvalidName`] if the key is empty or contains control characters.
+/// - [`Error::NameTooLong`] if the key exceeds [`Xattrs::KEY_LEN`] bytes.
+pub fn validate_key(key: &str) -> Result<(), Error> {
+    if key.is_empty() || key.chars().any(char::is_control) {
+        return Err(Error::InvalidName);
+    }
+    if key.len() > XattrBlock::KEY_LEN {
+        return Err(Error::NameTooLong);
+    }
+    Ok(())
+}
+
+impl DeviceAddr for XattrBlock {
+    const LAYOUT: DeviceLayout = DeviceLayout::DATA;
+}
+
+impl<const N: usize> FixedLen for Xattrs<N> {
+    const BYTES_LEN: usize = N;
+}
+
+impl<const N: usize> Serializable for Xattrs<N> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<usize, Error> {
+        Ok(writer.write(&self.buf)?)
+    }
+}
+
+impl<const N: usize> Deserializable<Self> for Xattrs<N> {
+    fn deserialize<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut result = Self::new();
+        reader.read(&mut result.buf)?;
+        // Entries end at the first one that cannot be parsed, usually a zero key length.
+        while let Some((key, value)) = parse_entry(&result.buf[result.len..]) {
+            result.len += HEADER_LEN + key.len() + value.len();
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::test_serde_symmetry;
+
+    use super::*;
+
+    fn sample() -> Xattrs<64> {
+        let mut sut = Xattrs::new();
+        sut.insert("user.type", b"text/plain").unwrap();
+        sut.insert("user.state", b"done").unwrap();
+        sut
+    }
+
+    test_serde_symmetry!(Xattrs<64>, sample());
+
+    #[test]
+    fn test_get() {
+        let sut = sample();
+        assert_eq!(Some(&b"text/plain"[..]), sut.get("user.type"));
+        assert_eq!(Some(&b"done"[..]), sut.get("user.state"));
+        assert_eq!(None, sut.get("user.missing"));
+    }
+
+    #[test]
+    fn test_insert_when_full() {
+        let mut sut = sample();
+        assert_eq!(Err(Error::XattrTooLarge), sut.insert("user.big", &[0; 64]));
+        assert_eq!(2, sut.iter().count());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut sut = sample();
+        assert!(sut.remove("user.type"));
+        assert!(!sut.remove("user.type"));
+        assert_eq!(None, sut.get("user.type"));
+        assert_eq!(Some(&b"done"[..]), sut.get("user.state"));
+
+        assert!(sut.remove("user.state"));
+        assert!(sut.is_empty());
+        assert_eq!(Xattrs::new(), sut);
+    }
+
+    #[test]
+    fn test_iter() {
+        let sut = sample();
+        let keys: Vec<_> = sut.iter().map(|(key, _)| key).collect();
+        assert_eq!([&b"user.type"[..], b"user.state"], keys[..]);
+    }
+
+    #[test]
+    fn test_validate_key() {
+        assert_eq!(Ok(()), validate_key("user.type"));
+        assert_eq!(Err(Error::InvalidName), validate_key(""));
+        assert_eq!(Err(Error::InvalidName), validate_key("a\0b"));
+        assert_eq!(Err(Error::NameTooLong), validate_key(&"a".repeat(256)));
+    }
+}
@@ -1,10 +1,18 @@
+// Each test crate only uses some of the helpers.
+#![allow(dead_code)]
+
 use ffs_lib::{BlockDevice, Controller, testutils::MemoryDevice};
 
 pub fn run(test: impl FnMut(&mut Controller<MemoryDevice>)) -> MemoryDevice {
     run_on(memory_device(), test)
 }
 
-fn run_on<D>(device: D, mut test: impl FnMut(&mut Controller<D>)) -> D
+/// Returns a formatted device, prepared with `setup`.
+pub fn formatted(setup: impl FnOnce(&mut Controller<MemoryDevice>)) -> MemoryDevice {
+    run_on(memory_device(), setup)
+}
+
+fn run_on<D>(device: D, test: impl FnOnce(&mut Controller<D>)) -> D
 where
     D: BlockDevice,
 {
@@ -13,7 +21,7 @@ where
     ctrl.unmount()
 }
 
-fn memory_device() -> MemoryDevice {
+pub fn memory_device() -> MemoryDevice {
     let mut device = MemoryDevice::new(512, 8 * 1024 * 1024);
     Controller::format(&mut device).expect("should format device");
     device
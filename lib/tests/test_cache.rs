@@ -0,0 +1,97 @@
+use common::*;
+use ffs_lib::{
+    BlockCache, Controller, NoClock, constants,
+    testutils::{MemoryDevice, Region, Trace, TracingDevice},
+};
+
+mod common;
+
+fn traced_device() -> (TracingDevice<MemoryDevice>, Trace) {
+    let device = TracingDevice::new(formatted(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("a/b/c/file.txt", b"contents"));
+    }));
+    let trace = device.trace();
+    (device, trace)
+}
+
+/// Looks the same path up twice, returning the tree sectors read by the second lookup.
+fn second_lookup_reads<const SIZE: usize>() -> usize {
+    let (device, trace) = traced_device();
+    let cache = BlockCache::<_, SIZE>::mount(device);
+    let mut ctrl = Controller::mount_with_cache(cache, NoClock).expect("should mount device");
+    assert!(ctrl.stat("a/b/c/file.txt").is_ok());
+    trace.begin("again");
+    assert!(ctrl.stat("a/b/c/file.txt").is_ok());
+    trace.operation("again").unwrap().reads(Region::Tree)
+}
+
+#[test]
+fn given_cache_size_when_lookup_repeated_then_reads_less_with_larger_cache() {
+    // The root, a, b and c each span 3 sectors.
+    assert_eq!(12, second_lookup_reads::<0>());
+    assert_eq!(9, second_lookup_reads::<8>(), "only the root should stay in the default cache");
+    assert_eq!(0, second_lookup_reads::<16>());
+}
+
+#[test]
+fn given_uncached_controller_then_operations_work() {
+    let (device, _) = traced_device();
+    let cache = BlockCache::<_, 0>::mount(device);
+    let mut ctrl = Controller::mount_with_cache(cache, NoClock).expect("should mount device");
+
+    assert_eq!(Ok(()), ctrl.create("a/b/other.txt", &[7; 1000]));
+    let mut buf = [0; 1000];
+    assert_eq!(Ok(1000), ctrl.open("a/b/other.txt").and_then(|mut file| file.readall(&mut buf)));
+    assert_eq!([7; 1000], buf);
+    assert_eq!(Ok(()), ctrl.delete("a/b/c/file.txt"));
+    assert_eq!(Ok(1), ctrl.count_files());
+}
+
+#[test]
+fn given_large_file_read_then_root_directory_stays_cached() {
+    let (device, trace) = traced_device();
+    let mut ctrl = Controller::mount(device).expect("should mount device");
+    let data = [7; constants::MAX_FILE_SIZE];
+    assert_eq!(Ok(()), ctrl.create("big.bin", &data));
+
+    let mut buf = [0; constants::MAX_FILE_SIZE];
+    assert_eq!(Ok(data.len()), ctrl.open("big.bin").and_then(|mut file| file.readall(&mut buf)));
+    trace.begin("stat");
+    assert!(ctrl.stat("big.bin").is_ok());
+
+    assert_eq!(0, trace.operation("stat").unwrap().reads(Region::Tree));
+    let stats = ctrl.cache_stats();
+    assert!(stats.hits > 0, "{stats:?}");
+    assert!(stats.evictions > 0, "{stats:?}");
+}
+
+/// Reads a file of `MAX_FILE_SIZE` bytes one block at a time, in the given order of
+/// blocks, and returns the device reads made since mounting.
+fn streaming_reads(blocks: impl Iterator<Item = usize>) -> usize {
+    let data: Vec<u8> = (0..constants::MAX_FILE_SIZE).map(|i| (i / 512 + 1) as u8).collect();
+    let mut device = formatted(|ctrl| assert_eq!(Ok(()), ctrl.create("song.wav", &data)));
+    device.reads_count = 0;
+
+    let mut ctrl = Controller::mount(device).expect("should mount device");
+    let mut file = ctrl.open("song.wav").expect("should open file");
+    let mut buf = [0; 512];
+    for block in blocks {
+        file.set_position(block * 512);
+        assert_eq!(Ok(512), file.read(&mut buf));
+        assert_eq!([block as u8 + 1; 512], buf);
+    }
+    ctrl.unmount().reads_count
+}
+
+#[test]
+fn given_sequential_reads_then_data_blocks_are_read_ahead() {
+    let blocks = constants::MAX_FILE_SIZE / 512;
+    let sequential = streaming_reads(0..blocks);
+    let backwards = streaming_reads((0..blocks).rev());
+    // Backwards, every block is read on its own.
+    assert_eq!(
+        backwards - blocks + blocks.div_ceil(4),
+        sequential,
+        "should read 4 blocks at a time"
+    );
+}
@@ -0,0 +1,240 @@
+use ffs_lib::{Controller, testutils::FileDevice};
+use std::{
+    env, fs, io,
+    os::unix::{
+        fs::{MetadataExt, PermissionsExt},
+        net::UnixListener,
+    },
+    path::PathBuf,
+    process::{Command, Output},
+    thread,
+    time::Duration,
+};
+
+/// Image file removed once the test is done.
+struct Image(PathBuf);
+
+impl Image {
+    fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("ffs-cli-{}-{name}.img", std::process::id()));
+        let _ = fs::remove_file(&path);
+        Self(path)
+    }
+
+    fn ffs(&self, command: &str, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_ffs"))
+            .arg(command)
+            .arg(&self.0)
+            .args(args)
+            .output()
+            .expect("should run ffs")
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn given_image_when_commands_then_operates_on_it() {
+    let image = Image::new("commands");
+    let host_file = image.0.with_extension("txt");
+    fs::write(&host_file, b"hello from the host\n").unwrap();
+    let host = host_file.to_str().unwrap();
+
+    stdout(&image.ffs("mkfs", &[]));
+    stdout(&image.ffs("put", &[host, "docs/hello.txt"]));
+    assert_eq!("hello from the host\n", stdout(&image.ffs("cat", &["docs/hello.txt"])));
+
+    stdout(&image.ffs("mv", &["docs/hello.txt", "notes/hello.txt"]));
+    stdout(&image.ffs("mkdir", &["empty"]));
+    assert_eq!("$/\n  empty/\n  notes/\n    hello.txt\n\n", stdout(&image.ffs("tree", &[])));
+    assert_eq!("$/\n  empty/\n  notes/\n\n", stdout(&image.ffs("ls", &[])));
+    assert!(stdout(&image.ffs("stat", &["notes/hello.txt"])).contains("size: 20"));
+    assert!(stdout(&image.ffs("df", &[])).contains("files: 1"));
+
+    stdout(&image.ffs("get", &["notes/hello.txt", host]));
+    assert_eq!(b"hello from the host\n", &fs::read(&host_file).unwrap()[..]);
+
+    stdout(&image.ffs("rm", &["notes/hello.txt"]));
+    assert!(stdout(&image.ffs("df", &[])).contains("files: 0"));
+    let _ = fs::remove_file(host_file);
+}
+
+#[test]
+fn given_failure_then_exits_with_error_code() {
+    let image = Image::new("failures");
+    assert_eq!(Some(74), image.ffs("ls", &[]).status.code(), "missing image");
+
+    stdout(&image.ffs("mkfs", &[]));
+    assert_eq!(Some(2), image.ffs("cat", &["missing.txt"]).status.code());
+    assert_eq!(Some(64), image.ffs("cat", &[]).status.code());
+    assert_eq!(Some(64), image.ffs("unknown", &[]).status.code());
+
+    stdout(&image.ffs("mkdir", &["dir"]));
+    assert_eq!(Some(17), image.ffs("mkdir", &["dir"]).status.code());
+    assert_eq!(Some(21), image.ffs("rm", &["dir"]).status.code());
+}
+
+#[test]
+fn given_mkfs_from_dir_then_imports_tree_and_reports_skipped() {
+    let image = Image::new("import");
+    let host_dir = image.0.with_extension("d");
+    let _ = fs::remove_dir_all(&host_dir);
+    fs::create_dir_all(host_dir.join("etc/conf")).unwrap();
+    fs::create_dir_all(host_dir.join("empty")).unwrap();
+    fs::write(host_dir.join("etc/conf/app.cfg"), b"debug = false\n").unwrap();
+    fs::write(host_dir.join("big.bin"), vec![1; 6000]).unwrap();
+    let _socket = UnixListener::bind(host_dir.join("etc/app.sock")).unwrap();
+    let dir = host_dir.to_str().unwrap();
+
+    let output = image.ffs("mkfs", &["-d", dir]);
+    assert_eq!(Some(27), output.status.code(), "file too large");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("skipping big.bin: file too large"), "{stderr}");
+    assert!(stderr.contains("skipping etc/app.sock: not a regular file"), "{stderr}");
+    assert!(stderr.contains("2 entries skipped"), "{stderr}");
+
+    assert_eq!(
+        "$/\n  empty/\n  etc/\n    conf/\n      app.cfg\n\n",
+        stdout(&image.ffs("tree", &[]))
+    );
+    assert_eq!("debug = false\n", stdout(&image.ffs("cat", &["etc/conf/app.cfg"])));
+    let _ = fs::remove_dir_all(host_dir);
+}
+
+#[test]
+fn given_mkfs_from_dir_with_special_files_then_reports_them_skipped() {
+    let image = Image::new("import-special");
+    let host_dir = image.0.with_extension("d");
+    let _ = fs::remove_dir_all(&host_dir);
+    fs::create_dir_all(&host_dir).unwrap();
+    fs::write(host_dir.join("app.cfg"), b"debug = false\n").unwrap();
+    let _socket = UnixListener::bind(host_dir.join("app.sock")).unwrap();
+
+    let output = image.ffs("mkfs", &["-d", host_dir.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code(), "only special files skipped");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("1 entries skipped"), "{stderr}");
+    assert_eq!("debug = false\n", stdout(&image.ffs("cat", &["app.cfg"])));
+    let _ = fs::remove_dir_all(host_dir);
+}
+
+#[test]
+fn given_export_then_extracts_tree_to_host_dir() {
+    let image = Image::new("export-dir");
+    let host_dir = image.0.with_extension("d");
+    let _ = fs::remove_dir_all(&host_dir);
+    fs::create_dir_all(host_dir.join("src/etc/conf")).unwrap();
+    fs::write(host_dir.join("src/etc/conf/app.cfg"), b"debug = false\n").unwrap();
+    fs::write(host_dir.join("src/big.bin"), vec![1; 2000]).unwrap();
+    std::os::unix::fs::symlink("/etc/conf/app.cfg", host_dir.join("src/app.cfg")).unwrap();
+    let src = host_dir.join("src");
+    let dst = host_dir.join("dst");
+
+    stdout(&image.ffs("mkfs", &["-d", src.to_str().unwrap()]));
+    let before = fs::read(&image.0).unwrap();
+    // Let the clock move on, so that updating access times would change the image.
+    thread::sleep(Duration::from_secs(1));
+    let output = stdout(&image.ffs("export", &[dst.to_str().unwrap()]));
+    assert_eq!(format!("exported 5 entries to {}\n", dst.display()), output);
+    assert_eq!(b"debug = false\n", &fs::read(dst.join("etc/conf/app.cfg")).unwrap()[..]);
+    assert_eq!(vec![1; 2000], fs::read(dst.join("big.bin")).unwrap());
+    assert_eq!(PathBuf::from("/etc/conf/app.cfg"), fs::read_link(dst.join("app.cfg")).unwrap());
+    let mode = fs::metadata(dst.join("big.bin")).unwrap().permissions().mode();
+    assert_eq!(0o644, mode & 0o7777);
+
+    let subtree = host_dir.join("subtree");
+    stdout(&image.ffs("export", &[subtree.to_str().unwrap(), "etc"]));
+    assert!(subtree.join("conf/app.cfg").is_file());
+    stdout(&image.ffs("export", &["--tar", "-"]));
+    stdout(&image.ffs("cat", &["big.bin"]));
+    stdout(&image.ffs("get", &["big.bin", dst.join("copy.bin").to_str().unwrap()]));
+    assert!(fs::read(&image.0).unwrap() == before, "exporting should leave the image unchanged");
+
+    assert_eq!(Some(74), image.ffs("export", &[dst.to_str().unwrap()]).status.code(), "exists");
+    assert_eq!(Some(2), image.ffs("export", &[dst.to_str().unwrap(), "missing"]).status.code());
+    let _ = fs::remove_dir_all(host_dir);
+}
+
+#[test]
+fn given_export_tar_then_writes_archive() {
+    let image = Image::new("export-tar");
+    let host_file = image.0.with_extension("txt");
+    fs::write(&host_file, b"hello").unwrap();
+    let host = host_file.to_str().unwrap();
+
+    stdout(&image.ffs("mkfs", &[]));
+    stdout(&image.ffs("put", &[host, "docs/hello.txt"]));
+    stdout(&image.ffs("mkdir", &["docs/empty"]));
+    let output = image.ffs("export", &["--tar", "-", "docs"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut archive = tar::Archive::new(&output.stdout[..]);
+    let mut entries = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let header = entry.header();
+        let path = entry.path().unwrap().display().to_string();
+        entries.push((path, header.entry_type(), header.mode().unwrap()));
+        assert_ne!(0, header.mtime().unwrap(), "stamped with the system clock");
+        if header.entry_type().is_file() {
+            let mut contents = String::new();
+            io::Read::read_to_string(&mut entry, &mut contents).unwrap();
+            assert_eq!("hello", contents);
+        }
+    }
+    let expected = [
+        ("empty".to_string(), tar::EntryType::Directory, 0o755),
+        ("hello.txt".to_string(), tar::EntryType::Regular, 0o644),
+    ];
+    assert_eq!(expected[..], entries);
+    let _ = fs::remove_file(host_file);
+}
+
+#[test]
+fn given_export_when_hard_links_then_keeps_them_linked() {
+    let image = Image::new("export-links");
+    let host_dir = image.0.with_extension("d");
+    let _ = fs::remove_dir_all(&host_dir);
+    stdout(&image.ffs("mkfs", &[]));
+    let device = FileDevice::new(image.0.to_str().unwrap()).unwrap();
+    let mut ctrl = Controller::mount(device).unwrap();
+    assert_eq!(Ok(()), ctrl.create("music/song.mp3", &[3; 1500]));
+    assert_eq!(Ok(()), ctrl.link("music/song.mp3", "favourites/song.mp3"));
+    ctrl.unmount();
+
+    let dst = host_dir.join("dst");
+    stdout(&image.ffs("export", &[dst.to_str().unwrap()]));
+    let first = fs::metadata(dst.join("favourites/song.mp3")).unwrap();
+    let second = fs::metadata(dst.join("music/song.mp3")).unwrap();
+    assert_eq!(first.ino(), second.ino());
+    assert_eq!(2, second.nlink());
+
+    let output = image.ffs("export", &["--tar", "-"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut archive = tar::Archive::new(&output.stdout[..]);
+    let files: Vec<_> = archive
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| !entry.header().entry_type().is_dir())
+        .map(|entry| {
+            let link = entry.link_name().unwrap().map(|link| link.display().to_string());
+            (entry.path().unwrap().display().to_string(), entry.size(), link)
+        })
+        .collect();
+    let expected = [
+        ("favourites/song.mp3".to_string(), 1500, None),
+        ("music/song.mp3".to_string(), 0, Some("favourites/song.mp3".to_string())),
+    ];
+    assert_eq!(expected[..], files);
+    let _ = fs::remove_dir_all(host_dir);
+}
@@ -0,0 +1,121 @@
+use common::*;
+use ffs_lib::{
+    Controller, Error,
+    testutils::{CrashDevice, MemoryDevice},
+};
+
+mod common;
+
+/// Formats a device, prepares it with `setup`, then records the writes of `operation`.
+fn record<S, O>(setup: S, operation: O) -> CrashDevice
+where
+    S: FnOnce(&mut Controller<MemoryDevice>),
+    O: FnOnce(&mut Controller<CrashDevice>),
+{
+    let mut ctrl = Controller::mount(CrashDevice::new(formatted(setup))).unwrap();
+    operation(&mut ctrl);
+    ctrl.unmount()
+}
+
+fn read(ctrl: &mut Controller<MemoryDevice>, path: &str) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; ffs_lib::constants::MAX_FILE_SIZE];
+    let len = ctrl.open(path)?.readall(&mut buf)?;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+#[test]
+fn given_crash_during_create_then_file_is_missing_or_complete() {
+    let data = vec![7; 3000];
+    let device = record(|_| {}, |ctrl| assert_eq!(Ok(()), ctrl.create("logs/boot.log", &data)));
+    assert!(device.writes.len() > 3);
+
+    device.check_crash_points(None, |writes, ctrl| match read(ctrl, "logs/boot.log") {
+        Ok(actual) => assert_eq!(data, actual, "after {writes} writes"),
+        Err(err) => assert_eq!(Error::FileNotFound, err, "after {writes} writes"),
+    });
+}
+
+#[test]
+fn given_crash_during_delete_then_file_is_complete_or_missing() {
+    let data = vec![7; 3000];
+    let device = record(
+        |ctrl| assert_eq!(Ok(()), ctrl.create("logs/boot.log", &data)),
+        |ctrl| assert_eq!(Ok(()), ctrl.delete("logs/boot.log")),
+    );
+
+    device.check_crash_points(None, |writes, ctrl| match read(ctrl, "logs/boot.log") {
+        Ok(actual) => assert_eq!(data, actual, "after {writes} writes"),
+        Err(err) => assert_eq!(Error::FileNotFound, err, "after {writes} writes"),
+    });
+}
+
+#[test]
+fn given_crash_during_rename_then_file_is_at_old_or_new_path() {
+    let device = record(
+        |ctrl| assert_eq!(Ok(()), ctrl.create("inbox/report.txt", b"contents")),
+        |ctrl| assert_eq!(Ok(()), ctrl.rename("inbox/report.txt", "archive/report.txt")),
+    );
+
+    device.check_crash_points(None, |writes, ctrl| {
+        let old = read(ctrl, "inbox/report.txt");
+        let new = read(ctrl, "archive/report.txt");
+        assert!(
+            old.as_deref() == Ok(b"contents") || new.as_deref() == Ok(b"contents"),
+            "after {writes} writes: {old:?} {new:?}"
+        );
+    });
+}
+
+#[test]
+fn given_crash_during_directory_rename_then_directory_is_never_shared() {
+    let device = record(
+        |ctrl| assert_eq!(Ok(()), ctrl.create("inbox/2024/report.txt", b"contents")),
+        |ctrl| assert_eq!(Ok(()), ctrl.rename("inbox/2024", "archive/2024")),
+    );
+
+    // Power lost between removing the directory and inserting it again leaves it leaked,
+    // it is never reachable from both paths.
+    device.check_crash_points(None, |writes, ctrl| {
+        let old = read(ctrl, "inbox/2024/report.txt");
+        let new = read(ctrl, "archive/2024/report.txt");
+        assert!(old.is_err() || new.is_err(), "after {writes} writes: {old:?} {new:?}");
+        for found in [old, new].into_iter().flatten() {
+            assert_eq!(b"contents".to_vec(), found, "after {writes} writes");
+        }
+    });
+}
+
+#[test]
+fn given_crash_during_link_then_file_is_kept() {
+    let device = record(
+        |ctrl| assert_eq!(Ok(()), ctrl.create("music/song.mp3", &[3; 1500])),
+        |ctrl| assert_eq!(Ok(()), ctrl.link("music/song.mp3", "favourites/song.mp3")),
+    );
+
+    device.check_crash_points(None, |writes, ctrl| {
+        assert_eq!(Ok(vec![3; 1500]), read(ctrl, "music/song.mp3"), "after {writes} writes");
+        if let Ok(linked) = read(ctrl, "favourites/song.mp3") {
+            assert_eq!(vec![3; 1500], linked, "after {writes} writes");
+        }
+    });
+}
+
+#[test]
+fn given_torn_write_during_append_then_mounts_and_keeps_other_files() {
+    let device = record(
+        |ctrl| {
+            assert_eq!(Ok(()), ctrl.create("keep.txt", b"untouched"));
+            assert_eq!(Ok(()), ctrl.create("append.log", &[1; 1000]));
+        },
+        |ctrl| {
+            let mut handle = ctrl.open("append.log").unwrap();
+            handle.set_position(1000);
+            assert_eq!(Ok(2000), handle.write(&[2; 2000]));
+        },
+    );
+
+    device.check_crash_points(Some(256), |writes, ctrl| {
+        assert_eq!(Ok(b"untouched".to_vec()), read(ctrl, "keep.txt"), "after {writes} writes");
+    });
+}
@@ -11,8 +11,8 @@ fn given_create_when_empty_file_then_creates() {
         assert_eq!(Ok(1), ctrl.count_files());
     });
 
-    assert_eq!(16, device.reads_count);
-    assert_eq!(24, device.writes_count);
+    assert_eq!(17, device.reads_count);
+    assert_eq!(26, device.writes_count);
 }
 
 #[test]
@@ -23,7 +23,7 @@ fn given_create_file_with_data_then_creates() {
         assert_eq!(Ok(1), ctrl.count_files());
     });
 
-    assert_eq!(22, device.reads_count);
+    assert_eq!(17, device.reads_count);
     assert_eq!(26, device.writes_count);
 }
 
@@ -52,30 +52,63 @@ fn given_create_when_thousands_of_files_then_creates() {
         }
     });
 
-    assert_eq!(10287, device.reads_count);
-    assert_eq!(7439, device.writes_count);
+    assert_eq!(6152, device.reads_count);
+    assert_eq!(6422, device.writes_count);
 }
 
 #[test]
 fn given_create_when_path_too_long_then_fail() {
     run(|ctrl| {
+        let dir = "a".repeat(constants::NAME_LEN + 1);
         assert_eq!(Ok(0), ctrl.count_files());
         assert_eq!(
             Err(Error::NameTooLong),
-            ctrl.create(
-                "some/thisnameexceedsthevalidlengthandshoudlfailthatishowthisworks/file.txt",
-                &[0u8; 128]
-            )
+            ctrl.create(&format!("some/{dir}/file.txt"), &[0u8; 128])
         );
         assert_eq!(Ok(0), ctrl.count_files());
     });
 }
 
+#[test]
+fn given_create_when_path_too_deep_then_fail() {
+    run(|ctrl| {
+        let path = "d/".repeat(constants::PATH_MAX_DEPTH) + "file.txt";
+        assert_eq!(Err(Error::PathTooDeep), ctrl.create(&path, b"contents"));
+        assert_eq!(Ok(0), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_create_when_long_names_then_creates() {
+    run(|ctrl| {
+        let dir = "timestamp-and-device-id-".repeat(10);
+        let file = format!("{}.log", "f".repeat(constants::NAME_LEN - 4));
+        let file_path = format!("some/{dir}/{file}");
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        assert_eq!(Ok(()), ctrl.create(&file_path, &[7u8; 128]));
+        assert_eq!(Ok(1), ctrl.count_files());
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+
+        let mut buf = [0u8; 128];
+        assert_eq!(Ok(128), ctrl.open(&file_path).unwrap().readall(&mut buf));
+        assert_eq!([7u8; 128], buf);
+
+        assert_eq!(Ok(()), ctrl.delete(&file_path));
+        assert_eq!(Ok(0), ctrl.count_files());
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}
+
 #[test]
 fn given_create_when_file_already_exists_then_fail() {
     run(|ctrl| {
         assert_eq!(Ok(()), ctrl.create("some/path/a.txt", &[0u8; 1]));
         assert_eq!(Err(Error::FileAlreadyExists), ctrl.create("some/path/a.txt", &[0u8; 1]));
+
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        assert_eq!(Err(Error::FileAlreadyExists), ctrl.create("some/path/a.txt", &[1; 2000]));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks(), "should release the data");
     });
 }
 
@@ -88,3 +121,23 @@ fn given_create_when_data_exceeds_max_filesize_then_fail() {
         );
     });
 }
+
+#[test]
+fn given_create_when_path_invalid_then_fail() {
+    run(|ctrl| {
+        assert_eq!(Err(Error::InvalidName), ctrl.create("some//a.txt", &[0u8; 1]));
+        assert_eq!(Err(Error::InvalidName), ctrl.create("../a.txt", &[0u8; 1]));
+        assert_eq!(Err(Error::InvalidName), ctrl.create("some/a\0.txt", &[0u8; 1]));
+        assert_eq!(Err(Error::InvalidName), ctrl.create("/", &[0u8; 1]));
+        assert_eq!(Ok(0), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_create_when_path_has_dots_then_resolves() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("./some/other/../path/a.txt", &[0u8; 1]));
+        assert!(ctrl.open("some/path/a.txt").is_ok());
+        assert_eq!(Ok(2), ctrl.count_dirs());
+    });
+}
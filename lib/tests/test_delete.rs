@@ -13,8 +13,8 @@ fn given_delete_when_file_exists_then_deletes() {
         assert_eq!(Ok(0), ctrl.count_files());
     });
 
-    assert_eq!(50, device.reads_count);
-    assert_eq!(46, device.writes_count);
+    assert_eq!(30, device.reads_count);
+    assert_eq!(40, device.writes_count);
 }
 
 #[test]
@@ -23,6 +23,6 @@ fn given_delete_when_file_not_found_then_fails() {
         assert_eq!(Err(Error::FileNotFound), ctrl.delete("does/not/exist/a.txt"));
     });
 
-    assert_eq!(5, device.reads_count);
-    assert_eq!(5, device.writes_count);
+    assert_eq!(6, device.reads_count);
+    assert_eq!(6, device.writes_count);
 }
@@ -0,0 +1,37 @@
+use common::*;
+use embedded_io::{Read, Seek, SeekFrom};
+use ffs_lib::Error;
+
+mod common;
+
+#[test]
+fn given_file_when_read_exact_then_reads_contents() {
+    run(|ctrl| {
+        let data: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+        assert_eq!(Ok(()), ctrl.create("www/index.html", &data));
+
+        let mut file = ctrl.open("www/index.html").expect("must open");
+        let mut buf = [0; 1500];
+        assert_eq!(Ok(()), file.read_exact(&mut buf));
+        assert_eq!(data[..1500], buf);
+        assert_eq!(Ok(500), Read::read(&mut file, &mut buf));
+        assert_eq!(Ok(0), Read::read(&mut file, &mut buf));
+    });
+}
+
+#[test]
+fn given_file_when_rewind_then_reads_again() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", b"hello world"));
+
+        let mut file = ctrl.open("file.txt").expect("must open");
+        let mut buf = [0; 5];
+        assert_eq!(Ok(()), file.read_exact(&mut buf));
+        assert_eq!(Ok(5), file.stream_position());
+        assert_eq!(Ok(()), file.rewind());
+        assert_eq!(Ok(()), file.read_exact(&mut buf));
+        assert_eq!(b"hello", &buf);
+
+        assert_eq!(Err(Error::InvalidSeek), file.seek(SeekFrom::End(-12)));
+    });
+}
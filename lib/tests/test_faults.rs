@@ -0,0 +1,107 @@
+use common::*;
+use ffs_lib::{
+    BlockDevice, Controller, Error,
+    testutils::{CrashDevice, Fault, FaultyDevice, MemoryDevice, Operations, Report, fsck},
+};
+
+mod common;
+
+type Faulty = FaultyDevice<MemoryDevice>;
+
+/// Runs `operation` on a copy of `device` failing as configured by `faulty`, then
+/// heals the device and checks it, returning the outcome of the operation.
+fn run_faulty<F, O>(device: &MemoryDevice, faulty: F, operation: O) -> (Result<(), Error>, Report)
+where
+    F: FnOnce(Faulty) -> Faulty,
+    O: FnOnce(&mut Controller<Faulty>) -> Result<(), Error>,
+{
+    let mut ctrl = Controller::mount(faulty(FaultyDevice::new(device.clone()))).unwrap();
+    let result = operation(&mut ctrl);
+    let mut device = ctrl.unmount();
+    device.heal();
+    let report = fsck(&mut device).unwrap();
+    assert!(report.is_consistent(), "{result:?}: {report:?}");
+    (result, report)
+}
+
+/// Runs `operation` failing every device operation after the first `n`, for every `n`
+/// until it succeeds, and checks the filesystem is left consistent.
+fn for_each_failure_point<O>(device: &MemoryDevice, mut operation: O)
+where
+    O: FnMut(&mut Controller<Faulty>) -> Result<(), Error>,
+{
+    for n in 1.. {
+        let (result, _) =
+            run_faulty(device, |faulty| faulty.with_fault(Fault::After(n)), &mut operation);
+        assert!(matches!(result, Ok(()) | Err(Error::Io)), "{result:?}");
+        if result.is_ok() {
+            assert!(n > 10, "should fail at several points");
+            return;
+        }
+    }
+}
+
+#[test]
+fn given_failure_during_create_then_stays_consistent() {
+    let device = formatted(|ctrl| assert_eq!(Ok(()), ctrl.create("logs/old.log", b"old")));
+    for_each_failure_point(&device, |ctrl| ctrl.create("logs/new/boot.log", &[7; 3000]));
+}
+
+#[test]
+fn given_failure_during_delete_then_stays_consistent() {
+    let device = formatted(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("logs/boot.log", &[7; 3000]));
+        assert_eq!(Ok(()), ctrl.set_xattr("logs/boot.log", "user.big", &[1; 100]));
+    });
+    for_each_failure_point(&device, |ctrl| ctrl.delete("logs/boot.log"));
+}
+
+#[test]
+fn given_failure_during_rename_then_stays_consistent() {
+    let device = formatted(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("inbox/report.txt", b"report"));
+        assert_eq!(Ok(()), ctrl.symlink("/inbox/report.txt", "inbox/latest"));
+    });
+    for_each_failure_point(&device, |ctrl| ctrl.rename("inbox/report.txt", "archive/report.txt"));
+    for_each_failure_point(&device, |ctrl| ctrl.rename("inbox/latest", "archive/latest"));
+}
+
+#[test]
+fn given_failed_write_when_create_then_rolls_back() {
+    let device = formatted(|ctrl| assert_eq!(Ok(()), ctrl.create("logs/old.log", b"old")));
+    fn create<D: BlockDevice>(ctrl: &mut Controller<D>) -> Result<(), Error> {
+        ctrl.create("logs/new/boot.log", &[7; 3000])
+    }
+
+    // Record the sectors written by a successful create, then fail each one in turn.
+    let mut ctrl = Controller::mount(CrashDevice::new(device.clone())).unwrap();
+    assert_eq!(Ok(()), create(&mut ctrl));
+    let mut sectors: Vec<_> = ctrl.unmount().writes.iter().map(|(sector, _)| *sector).collect();
+    sectors.dedup();
+
+    for sector in sectors {
+        let faulty =
+            |faulty: Faulty| faulty.with_fault(Fault::Sector(sector)).failing(Operations::Writes);
+        let (result, report) = run_faulty(&device, faulty, create);
+        assert_eq!(Err(Error::Io), result, "sector {sector}");
+        assert!(report.is_clean(), "sector {sector}: {report:?}");
+    }
+}
+
+#[test]
+fn given_random_failures_then_stays_consistent() {
+    let device = formatted(|_| {});
+    for seed in 0..20 {
+        let faulty = |faulty: Faulty| faulty.with_fault(Fault::Random { percent: 5, seed });
+        let _ = run_faulty(&device, faulty, |ctrl| {
+            for i in 0..10 {
+                let _ = ctrl.create(&format!("dir{}/file{i}.bin", i % 3), &[i; 1500]);
+                let _ = ctrl.link(&format!("dir{}/file{i}.bin", i % 3), &format!("link{i}"));
+                if i % 2 == 0 {
+                    let _ = ctrl.delete(&format!("dir{}/file{i}.bin", i % 3));
+                }
+            }
+            Ok(())
+        });
+    }
+}
@@ -13,6 +13,6 @@ fn given_formatted_device_then_mounts() {
     let sut = Controller::mount(device).expect("controller must mount");
     let device = sut.unmount();
 
-    assert_eq!(2, device.reads_count);
-    assert_eq!(5, device.writes_count);
+    assert_eq!(3, device.reads_count);
+    assert_eq!(6, device.writes_count);
 }
@@ -0,0 +1,106 @@
+use common::*;
+use ffs_lib::{Error, constants};
+
+mod common;
+
+#[test]
+fn given_small_file_when_create_then_uses_no_data_block() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("config.toml", b"a = 1"));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        assert_eq!(Ok(()), ctrl.create("other.toml", &[1; constants::INLINE_DATA_LEN]));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+
+        assert_eq!(Ok(()), ctrl.create("large.bin", &[1; constants::INLINE_DATA_LEN + 1]));
+        assert_eq!(Ok(free_blocks - 1), ctrl.count_free_data_blocks());
+
+        let mut buf = [0; 16];
+        assert_eq!(Ok(5), ctrl.open("config.toml").unwrap().readall(&mut buf));
+        assert_eq!(b"a = 1", &buf[..5]);
+    });
+}
+
+#[test]
+fn given_inline_file_when_write_grows_past_threshold_then_promotes() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", b"head"));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        let mut handle = ctrl.open("file.txt").unwrap();
+        handle.set_position(4);
+        assert_eq!(Ok(300), handle.write(&[1; 300]));
+        assert_eq!(Ok(600), handle.write(&[2; 600]));
+        assert_eq!(904, handle.file_len());
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        assert_eq!(Ok(904), ctrl.open("file.txt").unwrap().readall(&mut buf));
+        assert_eq!(b"head", &buf[..4]);
+        assert_eq!([1; 300], buf[4..304]);
+        assert_eq!([2; 600], buf[304..904]);
+
+        assert_eq!(Ok(()), ctrl.delete("file.txt"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_write_past_end_then_fills_gap_with_zeros() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", &[9; 1000]));
+
+        let mut handle = ctrl.open("file.bin").unwrap();
+        handle.set_position(2000);
+        assert_eq!(Ok(2), handle.write(&[7; 2]));
+
+        let mut buf = vec![0; 2002];
+        handle.set_position(0);
+        assert_eq!(Ok(2002), handle.read(&mut buf));
+        assert_eq!(Ok(0), handle.read(&mut buf));
+        assert_eq!([9; 1000], buf[..1000]);
+        assert!(buf[1000..2000].iter().all(|byte| *byte == 0));
+        assert_eq!([7; 2], buf[2000..]);
+    });
+}
+
+#[test]
+fn given_seek_past_end_when_read_then_reads_nothing() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("inline.txt", b"hello"));
+        assert_eq!(Ok(()), ctrl.create("blocks.bin", &[5; 1000]));
+
+        let mut buf = [0; 4];
+        for (path, pos) in [("inline.txt", 5), ("inline.txt", 100), ("blocks.bin", 5000)] {
+            let mut handle = ctrl.open(path).unwrap();
+            handle.set_position(pos);
+            assert_eq!(Ok(0), handle.read(&mut buf));
+            assert_eq!(pos, handle.position());
+        }
+    });
+}
+
+#[test]
+fn given_read_when_block_sized_file_then_reads_last_block() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", &[5; 1024]));
+
+        let mut buf = [0; 1024];
+        assert_eq!(Ok(1024), ctrl.open("file.bin").unwrap().readall(&mut buf));
+        assert_eq!([5; 1024], buf);
+    });
+}
+
+#[test]
+fn given_write_when_too_large_then_fails() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", b""));
+
+        let mut handle = ctrl.open("file.bin").unwrap();
+        handle.set_position(constants::MAX_FILE_SIZE);
+        assert_eq!(Err(Error::FileTooLarge), handle.write(b"x"));
+        handle.set_position(usize::MAX);
+        assert_eq!(Err(Error::FileTooLarge), handle.write(b"x"));
+        assert_eq!(0, handle.file_len());
+    });
+}
@@ -0,0 +1,80 @@
+use common::*;
+use ffs_lib::{Error, constants};
+
+mod common;
+
+#[test]
+fn given_link_then_shares_contents() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[123; 256]));
+        assert_eq!(Ok(()), ctrl.link("some/file.txt", "other/link.txt"));
+
+        let mut file_handle = ctrl.open("other/link.txt").expect("must open");
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        assert_eq!(Ok(256), file_handle.readall(&mut buf));
+        assert_eq!([123; 256], &buf[..256]);
+
+        assert_eq!(Ok(2), ctrl.stat("some/file.txt").map(|metadata| metadata.links()));
+        assert_eq!(Ok(2), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_files_in_same_dir_then_have_own_contents() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("a.txt", &[1; 16]));
+        assert_eq!(Ok(()), ctrl.create("b.txt", &[2; 600]));
+
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        assert_eq!(Ok(16), ctrl.open("a.txt").unwrap().readall(&mut buf));
+        assert_eq!([1; 16], &buf[..16]);
+        assert_eq!(Ok(600), ctrl.open("b.txt").unwrap().readall(&mut buf));
+        assert_eq!([2; 600], &buf[..600]);
+        assert_eq!(Ok(2), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_delete_when_linked_then_keeps_data_until_last_link() {
+    run(|ctrl| {
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[123; 1024]));
+        assert_eq!(Ok(()), ctrl.link("some/file.txt", "link.txt"));
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+
+        assert_eq!(Ok(()), ctrl.delete("some/file.txt"));
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+        assert_eq!(Ok(1), ctrl.stat("link.txt").map(|metadata| metadata.links()));
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        assert_eq!(Ok(1024), ctrl.open("link.txt").unwrap().readall(&mut buf));
+
+        assert_eq!(Ok(()), ctrl.delete("link.txt"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+        assert_eq!(Ok(0), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_link_when_invalid_then_fail() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[123; 16]));
+        assert_eq!(Ok(()), ctrl.create("other.txt", &[123; 16]));
+
+        assert_eq!(Err(Error::FileNotFound), ctrl.link("missing.txt", "link.txt"));
+        assert_eq!(Err(Error::IsADirectory), ctrl.link("some", "link"));
+        assert_eq!(Err(Error::FileAlreadyExists), ctrl.link("some/file.txt", "other.txt"));
+        assert_eq!(Ok(1), ctrl.stat("some/file.txt").map(|metadata| metadata.links()));
+    });
+}
+
+#[test]
+fn given_link_when_symlink_then_links_target() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[123; 16]));
+        assert_eq!(Ok(()), ctrl.symlink("file.txt", "some/symlink"));
+        assert_eq!(Ok(()), ctrl.link("some/symlink", "link.txt"));
+
+        assert_eq!(Ok(()), ctrl.delete("some/symlink"));
+        assert_eq!(Ok(2), ctrl.stat("link.txt").map(|metadata| metadata.links()));
+    });
+}
@@ -0,0 +1,269 @@
+//! Runs random sequences of operations against the controller and a simple reference
+//! model, checking after each one that both agree, before and after remounting.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use common::*;
+use ffs_lib::{Controller, constants, testutils::MemoryDevice};
+use proptest::{collection::vec, prelude::*, sample::select};
+
+mod common;
+
+const BLOCK_SIZE: usize = constants::BLOCK_SIZE;
+
+/// File contents, described rather than listed so failing cases stay readable.
+#[derive(Clone, Debug)]
+struct Contents {
+    len: usize,
+    seed: u8,
+    /// Blocks left filled with zeros, bit `i` standing for the `i`-th block.
+    zero_blocks: u16,
+}
+
+impl Contents {
+    fn bytes(&self) -> Vec<u8> {
+        (0..self.len)
+            .map(|i| match self.zero_blocks & (1 << (i / BLOCK_SIZE)) {
+                0 => self.seed.wrapping_add(i as u8) | 1,
+                _ => 0,
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Create(String, Contents),
+    Mkdir(String),
+    Delete(String),
+    Rename(String, String),
+    /// Opens the file and writes the contents at the given offset.
+    Write(String, usize, Contents),
+}
+
+/// Paths are made of a few short names, so operations often hit existing entries,
+/// and files get in the way of directories.
+fn path() -> impl Strategy<Value = String> {
+    vec(select(&["a", "b", "c", "d"][..]), 1..=3).prop_map(|components| components.join("/"))
+}
+
+fn contents(len: impl Strategy<Value = usize>) -> impl Strategy<Value = Contents> {
+    (len, any::<u8>(), any::<u16>()).prop_map(|(len, seed, zero_blocks)| Contents {
+        len,
+        seed,
+        zero_blocks,
+    })
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let len = prop_oneof![0..=constants::INLINE_DATA_LEN, 0..=constants::MAX_FILE_SIZE + 1];
+    prop_oneof![
+        3 => (path(), contents(len)).prop_map(|(path, contents)| Op::Create(path, contents)),
+        1 => path().prop_map(Op::Mkdir),
+        2 => path().prop_map(Op::Delete),
+        2 => (path(), path()).prop_map(|(from, to)| Op::Rename(from, to)),
+        2 => (path(), 0..=constants::MAX_FILE_SIZE, contents(1..=2 * BLOCK_SIZE))
+            .prop_map(|(path, offset, contents)| Op::Write(path, offset, contents)),
+    ]
+}
+
+#[derive(Clone, Debug)]
+struct ModelFile {
+    data: Vec<u8>,
+    /// Indices of the data blocks in use, `None` while the data is inline.
+    blocks: Option<BTreeSet<usize>>,
+}
+
+/// Reference model of the filesystem, paths being relative to the root.
+#[derive(Debug, Default)]
+struct Model {
+    files: BTreeMap<String, ModelFile>,
+    dirs: BTreeSet<String>,
+    /// Directories made by mkdir, which are kept once empty.
+    pinned: BTreeSet<String>,
+}
+
+/// Returns the paths of the parents of `path`, outermost first.
+fn parents(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices('/').map(|(index, _)| &path[..index])
+}
+
+impl Model {
+    fn exists(&self, path: &str) -> bool {
+        self.files.contains_key(path) || self.dirs.contains(path)
+    }
+
+    /// Whether a new entry can be inserted at `path`, creating its missing parents.
+    fn can_insert(&self, path: &str) -> bool {
+        !self.exists(path) && parents(path).all(|parent| !self.files.contains_key(parent))
+    }
+
+    fn insert_parents(&mut self, path: &str) {
+        self.dirs.extend(parents(path).map(str::to_string));
+    }
+
+    /// Removes the directories left without any file or pinned directory, unless they
+    /// are pinned, as the controller does after deleting or moving entries.
+    fn prune(&mut self) {
+        let (files, pinned) = (&self.files, &self.pinned);
+        self.dirs.retain(|dir| {
+            let mut kept = files.keys().chain(pinned);
+            pinned.contains(dir) || kept.any(|path| parents(path).any(|parent| parent == dir))
+        });
+    }
+
+    fn used_blocks(&self) -> usize {
+        self.files.values().filter_map(|file| file.blocks.as_ref()).map(BTreeSet::len).sum()
+    }
+
+    /// Applies `op`, returning whether it should succeed.
+    fn apply(&mut self, op: &Op) -> bool {
+        match op {
+            Op::Create(path, contents) => {
+                if !self.can_insert(path) || contents.len > constants::MAX_FILE_SIZE {
+                    return false;
+                }
+                let data = contents.bytes();
+                // Blocks made only of zeros are left as holes.
+                let blocks = (data.len() > constants::INLINE_DATA_LEN).then(|| {
+                    let chunks = data.chunks(BLOCK_SIZE).enumerate();
+                    let chunks = chunks.filter(|(_, chunk)| chunk.iter().any(|byte| *byte != 0));
+                    chunks.map(|(i, _)| i).collect()
+                });
+                self.insert_parents(path);
+                let file = ModelFile { data, blocks };
+                self.files.insert(path.clone(), file);
+            }
+            Op::Mkdir(path) => {
+                if !self.can_insert(path) {
+                    return false;
+                }
+                self.insert_parents(path);
+                self.dirs.insert(path.clone());
+                self.pinned.insert(path.clone());
+            }
+            Op::Delete(path) => {
+                if self.files.remove(path).is_none() {
+                    return false;
+                }
+                self.prune();
+            }
+            Op::Rename(from, to) => {
+                if !self.exists(from) || !self.can_insert(to) {
+                    return false;
+                }
+                if let Some(file) = self.files.remove(from) {
+                    self.insert_parents(to);
+                    self.files.insert(to.clone(), file);
+                } else {
+                    if parents(to).any(|parent| parent == from) {
+                        return false;
+                    }
+                    let moved = |path: &str| {
+                        let rest = path.strip_prefix(from.as_str())?;
+                        rest.starts_with('/').then(|| format!("{to}{rest}"))
+                    };
+                    self.insert_parents(to);
+                    self.dirs.remove(from);
+                    self.dirs.insert(to.clone());
+                    self.dirs =
+                        self.dirs.iter().map(|dir| moved(dir).unwrap_or(dir.clone())).collect();
+                    if self.pinned.remove(from) {
+                        self.pinned.insert(to.clone());
+                    }
+                    self.pinned =
+                        self.pinned.iter().map(|dir| moved(dir).unwrap_or(dir.clone())).collect();
+                    let files = std::mem::take(&mut self.files);
+                    self.files = files
+                        .into_iter()
+                        .map(|(path, file)| (moved(&path).unwrap_or(path), file))
+                        .collect();
+                }
+                self.prune();
+            }
+            Op::Write(path, offset, contents) => {
+                let end = offset + contents.len;
+                let Some(file) = self.files.get_mut(path) else {
+                    return false;
+                };
+                if end > constants::MAX_FILE_SIZE {
+                    return false;
+                }
+                if file.blocks.is_none() && end > constants::INLINE_DATA_LEN {
+                    // Inline data is moved to data blocks, every block it covers included.
+                    file.blocks = Some((0..file.data.len().div_ceil(BLOCK_SIZE)).collect());
+                }
+                if let Some(blocks) = &mut file.blocks {
+                    blocks.extend(offset / BLOCK_SIZE..end.div_ceil(BLOCK_SIZE));
+                }
+                if file.data.len() < end {
+                    file.data.resize(end, 0);
+                }
+                file.data[*offset..end].copy_from_slice(&contents.bytes());
+            }
+        }
+        true
+    }
+}
+
+fn run(ctrl: &mut Controller<MemoryDevice>, op: &Op) -> bool {
+    match op {
+        Op::Create(path, contents) => ctrl.create(path, &contents.bytes()).is_ok(),
+        Op::Mkdir(path) => ctrl.mkdir(path).is_ok(),
+        Op::Delete(path) => ctrl.delete(path).is_ok(),
+        Op::Rename(from, to) => ctrl.rename(from, to).is_ok(),
+        Op::Write(path, offset, contents) => ctrl.open(path).is_ok_and(|mut handle| {
+            handle.set_position(*offset);
+            handle.write(&contents.bytes()).is_ok()
+        }),
+    }
+}
+
+/// Checks the controller holds the same files and directories as `model`.
+fn check(
+    ctrl: &mut Controller<MemoryDevice>,
+    model: &Model,
+    free_blocks: usize,
+) -> Result<(), TestCaseError> {
+    let (mut files, mut dirs) = (BTreeSet::new(), BTreeSet::new());
+    let walked = ctrl.walk("", |path, metadata| {
+        let paths = if metadata.is_dir() { &mut dirs } else { &mut files };
+        paths.insert(path.to_string());
+        Ok(())
+    });
+    prop_assert_eq!(Ok(()), walked);
+    prop_assert_eq!(&model.files.keys().cloned().collect::<BTreeSet<_>>(), &files);
+    prop_assert_eq!(&model.dirs, &dirs);
+
+    for (path, file) in &model.files {
+        let mut out = vec![0; constants::MAX_FILE_SIZE];
+        let len = ctrl.open(path).and_then(|mut handle| handle.readall(&mut out));
+        prop_assert_eq!(Ok(file.data.len()), len, "{}", path);
+        prop_assert!(file.data == out[..file.data.len()], "contents of {} differ", path);
+    }
+
+    prop_assert_eq!(Ok(model.files.len()), ctrl.count_files());
+    prop_assert_eq!(Ok(model.dirs.len()), ctrl.count_dirs());
+    prop_assert_eq!(Ok(free_blocks - model.used_blocks()), ctrl.count_free_data_blocks());
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn given_random_operations_then_matches_model(ops in vec(op(), 1..40)) {
+        let mut ctrl = Controller::mount(memory_device()).expect("should mount device");
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        let mut model = Model::default();
+
+        for op in &ops {
+            let expected = model.apply(op);
+            prop_assert_eq!(expected, run(&mut ctrl, op), "{:?}", op);
+            check(&mut ctrl, &model, free_blocks)?;
+
+            ctrl = Controller::mount(ctrl.unmount()).expect("should remount device");
+            check(&mut ctrl, &model, free_blocks)?;
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use common::*;
+use ffs_lib::{Controller, Error, Identity, Permissions, testutils::MemoryDevice};
+
+mod common;
+
+const ALICE: Identity = Identity::new(1000, 100);
+const BOB: Identity = Identity::new(1001, 100);
+const EVE: Identity = Identity::new(1002, 200);
+
+/// Runs `setup` without identity, then remounts the device to run `test` as `identity`.
+fn run_as(
+    identity: Identity,
+    setup: impl FnOnce(&mut Controller<MemoryDevice>),
+    test: impl FnOnce(&mut Controller<MemoryDevice>),
+) {
+    let device = formatted(setup);
+    let mut ctrl = Controller::mount(device).expect("should mount device").with_identity(identity);
+    test(&mut ctrl);
+}
+
+fn setup_home(ctrl: &mut Controller<MemoryDevice>) {
+    assert_eq!(Ok(()), ctrl.create("home/alice/notes.txt", &[1; 16]));
+    assert_eq!(Ok(()), ctrl.chown("home/alice", ALICE.uid(), ALICE.gid()));
+    assert_eq!(Ok(()), ctrl.chown("home/alice/notes.txt", ALICE.uid(), ALICE.gid()));
+}
+
+#[test]
+fn given_create_without_identity_then_owned_by_root() {
+    run_as(Identity::ROOT, setup_home, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("etc/config", &[1; 16]));
+        let metadata = ctrl.stat("etc/config").unwrap();
+        assert_eq!(&Permissions::new(Permissions::FILE_MODE, 0, 0), metadata.permissions());
+        let metadata = ctrl.stat("etc").unwrap();
+        assert_eq!(&Permissions::new(Permissions::DIR_MODE, 0, 0), metadata.permissions());
+    });
+}
+
+#[test]
+fn given_create_with_identity_then_owned_by_caller() {
+    run_as(ALICE, setup_home, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("home/alice/docs/todo.txt", &[1; 16]));
+        let metadata = ctrl.stat("home/alice/docs/todo.txt").unwrap();
+        assert_eq!((1000, 100), (metadata.permissions().uid(), metadata.permissions().gid()));
+        let metadata = ctrl.stat("home/alice/docs").unwrap();
+        assert_eq!(1000, metadata.permissions().uid());
+    });
+}
+
+#[test]
+fn given_create_when_dir_not_writable_then_fail() {
+    run_as(BOB, setup_home, |ctrl| {
+        assert_eq!(Err(Error::PermissionDenied), ctrl.create("home/alice/bob.txt", &[1; 16]));
+        assert_eq!(Err(Error::PermissionDenied), ctrl.create("home/bob/bob.txt", &[1; 16]));
+        assert_eq!(Err(Error::PermissionDenied), ctrl.symlink("alice", "home/link"));
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("home/bob").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_delete_when_dir_not_writable_then_fail() {
+    run_as(BOB, setup_home, |ctrl| {
+        assert_eq!(Err(Error::PermissionDenied), ctrl.delete("home/alice/notes.txt"));
+        assert_eq!(Ok(1), ctrl.stat("home/alice/notes.txt").map(|metadata| metadata.links()));
+    });
+    run_as(ALICE, setup_home, |ctrl| {
+        assert_eq!(Ok(()), ctrl.delete("home/alice/notes.txt"));
+    });
+}
+
+#[test]
+fn given_open_then_checks_read_access() {
+    run_as(BOB, setup_home, |ctrl| {
+        let _ = ctrl.open("home/alice/notes.txt").expect("group can read");
+    });
+    run_as(EVE, setup_home, |ctrl| {
+        let _ = ctrl.open("home/alice/notes.txt").expect("others can read");
+    });
+    run_as(
+        EVE,
+        |ctrl| {
+            setup_home(ctrl);
+            assert_eq!(Ok(()), ctrl.set_permissions("home/alice/notes.txt", 0o640));
+        },
+        |ctrl| {
+            assert_eq!(Err(Error::PermissionDenied), ctrl.open("home/alice/notes.txt").map(|_| ()));
+        },
+    );
+}
+
+#[test]
+fn given_private_dir_then_checks_search_access() {
+    let setup = |ctrl: &mut Controller<MemoryDevice>| {
+        setup_home(ctrl);
+        assert_eq!(Ok(()), ctrl.create("home/alice/secret.txt", b"secret"));
+        assert_eq!(Ok(()), ctrl.set_permissions("home/alice", 0o700));
+    };
+    run_as(EVE, setup, |ctrl| {
+        assert_eq!(Err(Error::PermissionDenied), ctrl.open("home/alice/secret.txt").map(|_| ()));
+        assert_eq!(Err(Error::PermissionDenied), ctrl.stat("home/alice/secret.txt").map(|_| ()));
+        assert_eq!(Ok(0o700), ctrl.stat("home/alice").map(|m| m.permissions().mode()));
+    });
+    run_as(ALICE, setup, |ctrl| {
+        let _ = ctrl.open("home/alice/secret.txt").expect("owner can search");
+    });
+}
+
+#[test]
+fn given_write_then_checks_write_access() {
+    run_as(BOB, setup_home, |ctrl| {
+        let mut handle = ctrl.open("home/alice/notes.txt").expect("group can read");
+        assert_eq!(Err(Error::PermissionDenied), handle.write(b"bob"));
+    });
+    run_as(ALICE, setup_home, |ctrl| {
+        let mut handle = ctrl.open("home/alice/notes.txt").expect("owner can read");
+        assert_eq!(Ok(5), handle.write(b"alice"));
+    });
+}
+
+#[test]
+fn given_set_permissions_then_only_owner_or_root() {
+    run_as(ALICE, setup_home, |ctrl| {
+        assert_eq!(Ok(()), ctrl.set_permissions("home/alice/notes.txt", 0o100_600));
+        let metadata = ctrl.stat("home/alice/notes.txt").unwrap();
+        assert_eq!(0o600, metadata.permissions().mode());
+
+        assert_eq!(Err(Error::PermissionDenied), ctrl.set_permissions("home", 0o777));
+        assert_eq!(Err(Error::PermissionDenied), ctrl.chown("home/alice/notes.txt", 1001, 100));
+    });
+    run_as(Identity::ROOT, setup_home, |ctrl| {
+        assert_eq!(Ok(()), ctrl.set_permissions("/", 0o777));
+        assert_eq!(Ok(()), ctrl.chown("home/alice/notes.txt", 1001, 100));
+        assert_eq!(Ok(0o777), ctrl.stat("").map(|metadata| metadata.permissions().mode()));
+    });
+}
@@ -0,0 +1,78 @@
+use common::*;
+use ffs_lib::{Error, constants};
+
+mod common;
+
+const BLOCK: usize = 512;
+
+#[test]
+fn given_preallocate_then_reserves_blocks_without_changing_len() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("ring.log", &[1; 600]));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        let mut handle = ctrl.open("ring.log").unwrap();
+        assert_eq!(Ok(()), handle.preallocate(4 * BLOCK));
+        assert_eq!(600, handle.file_len());
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+
+        let mut buf = [0; 600];
+        assert_eq!(Ok(600), ctrl.open("ring.log").unwrap().readall(&mut buf));
+        assert_eq!([1; 600], buf);
+
+        assert_eq!(Ok(()), ctrl.delete("ring.log"));
+        assert_eq!(Ok(free_blocks + 2), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_preallocated_file_when_append_then_allocates_nothing() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("ring.log", b""));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        let mut handle = ctrl.open("ring.log").unwrap();
+        assert_eq!(Ok(()), handle.preallocate(constants::MAX_FILE_SIZE));
+        for _ in 0..10 {
+            assert_eq!(Ok(BLOCK), handle.write(&[7; BLOCK]));
+        }
+        assert_eq!(Err(Error::FileTooLarge), handle.write(b"x"));
+        assert_eq!(Ok(free_blocks - 10), ctrl.count_free_data_blocks());
+
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        assert_eq!(Ok(constants::MAX_FILE_SIZE), ctrl.open("ring.log").unwrap().readall(&mut buf));
+        assert!(buf.iter().all(|byte| *byte == 7));
+    });
+}
+
+#[test]
+fn given_preallocated_blocks_when_write_past_end_then_gap_reads_zeros() {
+    run(|ctrl| {
+        // Leave stale data in free blocks, so preallocation reuses them.
+        assert_eq!(Ok(()), ctrl.create("stale.bin", &[0xee; 4 * BLOCK]));
+        assert_eq!(Ok(()), ctrl.delete("stale.bin"));
+        assert_eq!(Ok(()), ctrl.create("file.bin", &[1; 400]));
+
+        let mut handle = ctrl.open("file.bin").unwrap();
+        assert_eq!(Ok(()), handle.preallocate(4 * BLOCK));
+        handle.set_position(3 * BLOCK);
+        assert_eq!(Ok(2), handle.write(&[2; 2]));
+
+        let mut buf = vec![0xff; 3 * BLOCK + 2];
+        assert_eq!(Ok(3 * BLOCK + 2), ctrl.open("file.bin").unwrap().readall(&mut buf));
+        assert_eq!([1; 400], buf[..400]);
+        assert!(buf[400..3 * BLOCK].iter().all(|byte| *byte == 0));
+        assert_eq!([2; 2], buf[3 * BLOCK..]);
+    });
+}
+
+#[test]
+fn given_preallocate_when_too_large_then_fail() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", b"small"));
+        let mut handle = ctrl.open("file.bin").unwrap();
+        assert_eq!(Ok(()), handle.preallocate(constants::INLINE_DATA_LEN));
+        assert_eq!(Err(Error::FileTooLarge), handle.preallocate(constants::MAX_FILE_SIZE + 1));
+        assert_eq!(5, handle.file_len());
+    });
+}
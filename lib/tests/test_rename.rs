@@ -0,0 +1,129 @@
+use common::*;
+use ffs_lib::{Error, constants, testutils::fsck};
+
+mod common;
+
+#[test]
+fn given_mkdir_then_creates_empty_dir() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.mkdir("var/log"));
+        assert_eq!(Ok(2), ctrl.count_dirs());
+        assert_eq!(Ok(true), ctrl.stat("var/log").map(|metadata| metadata.is_dir()));
+        assert_eq!(Err(Error::FileAlreadyExists), ctrl.mkdir("var/log"));
+
+        assert_eq!(Ok(()), ctrl.create("var/log/boot.log", b"ok"));
+        assert_eq!(Ok(1), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_mkdir_then_kept_once_empty() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.mkdir("empty"));
+        assert_eq!(Ok(()), ctrl.mkdir("parent/made"));
+        assert_eq!(Ok(()), ctrl.create("x.txt", b"x"));
+        assert_eq!(Ok(()), ctrl.delete("x.txt"));
+        assert_eq!(Ok(3), ctrl.count_dirs());
+
+        assert_eq!(Ok(()), ctrl.rename("parent/made", "moved"));
+        assert_eq!(Ok(2), ctrl.count_dirs(), "only the implicit parent is pruned");
+        assert_eq!(Ok(true), ctrl.stat("moved").map(|metadata| metadata.is_dir()));
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("parent").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_rename_file_then_moves_entry() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("inbox/report.txt", b"contents"));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        assert_eq!(Ok(()), ctrl.rename("inbox/report.txt", "archive/2024/report.txt"));
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("inbox/report.txt").map(|_| ()));
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("inbox").map(|_| ()), "pruned once empty");
+
+        let mut buf = [0; 8];
+        assert_eq!(Ok(8), ctrl.open("archive/2024/report.txt").unwrap().readall(&mut buf));
+        assert_eq!(b"contents", &buf);
+        assert_eq!(Ok(1), ctrl.stat("archive/2024/report.txt").map(|metadata| metadata.links()));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_rename_dir_then_moves_subtree() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("src/a.txt", b"a"));
+        assert_eq!(Ok(()), ctrl.create("src/nested/b.txt", b"b"));
+
+        assert_eq!(Ok(()), ctrl.rename("src", "dst/moved"));
+        assert_eq!(Ok(2), ctrl.count_files());
+        assert_eq!(Ok(1), ctrl.stat("dst/moved/a.txt").map(|metadata| metadata.len()));
+        assert_eq!(Ok(1), ctrl.stat("dst/moved/nested/b.txt").map(|metadata| metadata.len()));
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("src").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_rename_symlink_then_moves_link() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("data/file.txt", b"abc"));
+        assert_eq!(Ok(()), ctrl.symlink("/data/file.txt", "links/current"));
+
+        assert_eq!(Ok(()), ctrl.rename("links/current", "latest"));
+        let mut buf = [0; 32];
+        assert_eq!(Ok(14), ctrl.read_link("latest", &mut buf));
+        assert_eq!(b"/data/file.txt", &buf[..14]);
+        assert_eq!(Ok(3), ctrl.stat("latest").map(|metadata| metadata.len()));
+    });
+}
+
+#[test]
+fn given_rename_when_invalid_then_fails() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("a/file.txt", b"a"));
+        assert_eq!(Ok(()), ctrl.create("b/file.txt", b"b"));
+
+        assert_eq!(Err(Error::FileNotFound), ctrl.rename("a/missing.txt", "c.txt"));
+        assert_eq!(Err(Error::FileAlreadyExists), ctrl.rename("a/file.txt", "b/file.txt"));
+        assert_eq!(Err(Error::InvalidName), ctrl.rename("a", "a/inner"));
+        assert_eq!(Ok(2), ctrl.count_files());
+    });
+}
+
+#[test]
+fn given_rename_dir_into_full_dir_then_stays_in_place() {
+    let mut device = run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("src/a.txt", b"a"));
+        for i in 0..constants::TREE_NODE_ENTRY_LEN {
+            assert_eq!(Ok(()), ctrl.create(&format!("full/{i}.txt"), b"f"));
+        }
+
+        assert_eq!(Err(Error::DirectoryFull), ctrl.rename("src", "full/src"));
+        assert_eq!(Ok(1), ctrl.stat("src/a.txt").map(|metadata| metadata.len()));
+    });
+    assert!(fsck(&mut device).unwrap().is_clean());
+}
+
+#[test]
+fn given_rename_into_itself_through_symlink_then_fails() {
+    let mut device = run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.mkdir("a"));
+        assert_eq!(Ok(()), ctrl.create("a/f.txt", b"f"));
+        assert_eq!(Ok(()), ctrl.symlink("a", "l"));
+
+        assert_eq!(Err(Error::InvalidName), ctrl.rename("a", "l/b"));
+        assert_eq!(Err(Error::InvalidName), ctrl.rename("a", "l/new/b"));
+        assert_eq!(Ok(1), ctrl.stat("a/f.txt").map(|metadata| metadata.len()));
+    });
+    assert!(fsck(&mut device).unwrap().is_consistent());
+}
+
+#[test]
+fn given_delete_when_dir_then_fails() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("a/file.txt", b"a"));
+        assert_eq!(Err(Error::IsADirectory), ctrl.delete("a"));
+        assert_eq!(Ok(1), ctrl.count_files());
+    });
+}
@@ -0,0 +1,51 @@
+use ffs_lib::{
+    Addr, Controller, SdCard, constants,
+    testutils::{MockSdCard, NoDelay},
+};
+
+/// READ_MULTIPLE_BLOCK and STOP_TRANSMISSION.
+const CMD18: u8 = 18;
+const CMD12: u8 = 12;
+
+fn card() -> MockSdCard {
+    MockSdCard::new((constants::DEVICE_LEN / 512) as Addr)
+}
+
+#[test]
+fn given_sd_card_when_formatted_then_stores_files() {
+    let mock = card();
+    let mut device = SdCard::new(mock.spi(), mock.cs(), NoDelay).expect("should init card");
+    Controller::format(&mut device).expect("should format card");
+    let mut ctrl = Controller::mount(device).expect("should mount card");
+    assert_eq!(Ok(()), ctrl.create("music/track.raw", &[7; 3000]));
+
+    // Remount on a newly initialized card.
+    let (spi, cs, delay) = ctrl.unmount().release();
+    let device = SdCard::new(spi, cs, delay).expect("should init card");
+    let mut ctrl = Controller::mount(device).expect("should mount card");
+    let mut buf = [0; constants::MAX_FILE_SIZE];
+    assert_eq!(Ok(3000), ctrl.open("music/track.raw").and_then(|mut file| file.readall(&mut buf)));
+    assert_eq!([7; 3000], buf[..3000]);
+}
+
+#[test]
+fn given_sequential_read_when_on_sd_card_then_uses_multiple_block_reads() {
+    let mock = card().standard_capacity();
+    let mut device = SdCard::new(mock.spi(), mock.cs(), NoDelay).expect("should init card");
+    Controller::format(&mut device).expect("should format card");
+    let mut ctrl = Controller::mount(device).expect("should mount card");
+    let data: Vec<u8> = (0..constants::MAX_FILE_SIZE).map(|i| (i / 512 + 1) as u8).collect();
+    assert_eq!(Ok(()), ctrl.create("song.raw", &data));
+
+    mock.clear_commands();
+    let mut file = ctrl.open("song.raw").expect("should open file");
+    let mut buf = [0; 512];
+    for block in 0..data.len() / 512 {
+        assert_eq!(Ok(512), file.read(&mut buf));
+        assert_eq!([block as u8 + 1; 512], buf);
+    }
+    let commands = mock.commands();
+    // Ten blocks read ahead four at a time, the data blocks being contiguous.
+    assert_eq!(3, commands.iter().filter(|cmd| **cmd == CMD18).count(), "{commands:?}");
+    assert_eq!(3, commands.iter().filter(|cmd| **cmd == CMD12).count(), "{commands:?}");
+}
@@ -0,0 +1,101 @@
+use common::*;
+use ffs_lib::{Error, constants};
+
+mod common;
+
+const BLOCK: usize = 512;
+
+#[test]
+fn given_create_with_zero_blocks_then_leaves_holes() {
+    run(|ctrl| {
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        let mut data = vec![0; 4 * BLOCK];
+        data[0] = 1;
+        data[4 * BLOCK - 1] = 2;
+
+        assert_eq!(Ok(()), ctrl.create("sparse.bin", &data));
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+
+        let mut buf = vec![0xff; 4 * BLOCK];
+        assert_eq!(Ok(4 * BLOCK), ctrl.open("sparse.bin").unwrap().readall(&mut buf));
+        assert_eq!(data, buf);
+
+        assert_eq!(Ok(()), ctrl.delete("sparse.bin"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_write_past_end_then_leaves_hole() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", &[9; 1000]));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        let mut handle = ctrl.open("file.bin").unwrap();
+        handle.set_position(4 * BLOCK);
+        assert_eq!(Ok(1), handle.write(&[7]));
+        assert_eq!(Ok(free_blocks - 1), ctrl.count_free_data_blocks());
+
+        let mut buf = vec![0xff; 4 * BLOCK + 1];
+        assert_eq!(Ok(4 * BLOCK + 1), ctrl.open("file.bin").unwrap().readall(&mut buf));
+        assert_eq!([9; 1000], buf[..1000]);
+        assert!(buf[1000..4 * BLOCK].iter().all(|byte| *byte == 0));
+        assert_eq!(7, buf[4 * BLOCK]);
+    });
+}
+
+#[test]
+fn given_write_into_hole_then_allocates_block() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", &[0; 3 * BLOCK]));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        let mut handle = ctrl.open("file.bin").unwrap();
+        handle.set_position(BLOCK + 10);
+        assert_eq!(Ok(3), handle.write(b"abc"));
+        assert_eq!(Ok(free_blocks - 1), ctrl.count_free_data_blocks());
+
+        let mut buf = vec![0xff; 3 * BLOCK];
+        assert_eq!(Ok(3 * BLOCK), ctrl.open("file.bin").unwrap().readall(&mut buf));
+        assert_eq!(b"abc", &buf[BLOCK + 10..BLOCK + 13]);
+        assert_eq!(3, buf.iter().filter(|byte| **byte != 0).count());
+    });
+}
+
+#[test]
+fn given_punch_hole_then_releases_covered_blocks() {
+    run(|ctrl| {
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        assert_eq!(Ok(()), ctrl.create("file.bin", &[1; 4 * BLOCK]));
+        assert_eq!(Ok(free_blocks - 4), ctrl.count_free_data_blocks());
+
+        // Covers the second and third blocks, and part of the first and fourth.
+        assert_eq!(Ok(()), ctrl.punch_hole("file.bin", 500..3 * BLOCK + 10));
+        assert_eq!(Ok(free_blocks - 2), ctrl.count_free_data_blocks());
+        assert_eq!(Ok(4 * BLOCK as u16), ctrl.stat("file.bin").map(|metadata| metadata.len()));
+
+        let mut buf = vec![0xff; 4 * BLOCK];
+        assert_eq!(Ok(4 * BLOCK), ctrl.open("file.bin").unwrap().readall(&mut buf));
+        assert!(buf[..500].iter().all(|byte| *byte == 1));
+        assert!(buf[500..3 * BLOCK + 10].iter().all(|byte| *byte == 0));
+        assert!(buf[3 * BLOCK + 10..].iter().all(|byte| *byte == 1));
+
+        assert_eq!(Ok(()), ctrl.delete("file.bin"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_punch_hole_when_inline_or_past_end_then_zeroes() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("small.txt", b"hello world"));
+        assert_eq!(Ok(()), ctrl.punch_hole("small.txt", 5..constants::MAX_FILE_SIZE));
+
+        let mut buf = [0xff; 11];
+        assert_eq!(Ok(11), ctrl.open("small.txt").unwrap().readall(&mut buf));
+        assert_eq!(b"hello\0\0\0\0\0\0", &buf);
+
+        assert_eq!(Ok(()), ctrl.punch_hole("small.txt", 20..30));
+        assert_eq!(Err(Error::FileNotFound), ctrl.punch_hole("missing.txt", 0..1));
+    });
+}
@@ -0,0 +1,109 @@
+use common::*;
+use ffs_lib::{
+    Controller, Error,
+    testutils::{MemoryDevice, MockClock},
+};
+
+mod common;
+
+fn run(clock: &MockClock, test: impl FnOnce(&mut Controller<MemoryDevice, &MockClock>)) {
+    let mut ctrl =
+        Controller::mount_with_clock(memory_device(), clock).expect("should mount device");
+    test(&mut ctrl);
+}
+
+#[test]
+fn given_stat_when_file_created_then_has_times() {
+    let clock = MockClock::new(100);
+    run(&clock, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/dir/file.txt", &[1; 64]));
+
+        let metadata = ctrl.stat("some/dir/file.txt").unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(64, metadata.len());
+        assert_eq!(100, metadata.created());
+        assert_eq!(100, metadata.modified());
+        assert_eq!(100, metadata.accessed());
+
+        let metadata = ctrl.stat("some/dir").unwrap();
+        assert!(metadata.is_dir());
+        assert_eq!(100, metadata.modified());
+        assert_eq!(Ok(100), ctrl.stat("/").map(|metadata| metadata.modified()));
+    });
+}
+
+#[test]
+fn given_stat_when_file_opened_then_updates_accessed() {
+    let clock = MockClock::new(100);
+    run(&clock, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[1; 64]));
+
+        clock.set(200);
+        let _ = ctrl.open("some/file.txt").unwrap();
+
+        let metadata = ctrl.stat("some/file.txt").unwrap();
+        assert_eq!(100, metadata.created());
+        assert_eq!(100, metadata.modified());
+        assert_eq!(200, metadata.accessed());
+        assert_eq!(Ok(100), ctrl.stat("some").map(|metadata| metadata.modified()));
+    });
+}
+
+#[test]
+fn given_stat_when_file_opened_again_then_keeps_accessed_until_modified() {
+    let clock = MockClock::new(100);
+    run(&clock, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", &[1; 64]));
+        clock.set(200);
+        let _ = ctrl.open("file.txt").unwrap();
+
+        clock.set(300);
+        let _ = ctrl.open("file.txt").unwrap();
+        assert_eq!(Ok(200), ctrl.stat("file.txt").map(|metadata| metadata.accessed()));
+
+        clock.set(400);
+        assert_eq!(Ok(2), ctrl.open("file.txt").unwrap().write(b"ok"));
+        clock.set(500);
+        let _ = ctrl.open("file.txt").unwrap();
+        assert_eq!(Ok(500), ctrl.stat("file.txt").map(|metadata| metadata.accessed()));
+
+        clock.set(500 + 24 * 60 * 60);
+        let _ = ctrl.open("file.txt").unwrap();
+        assert_eq!(Ok(500 + 24 * 60 * 60), ctrl.stat("file.txt").map(|m| m.accessed()));
+    });
+}
+
+#[test]
+fn given_stat_when_entry_removed_then_updates_dir_modified() {
+    let clock = MockClock::new(100);
+    run(&clock, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/a.txt", &[1; 64]));
+        assert_eq!(Ok(()), ctrl.create("some/dir/b.txt", &[1; 64]));
+
+        clock.set(300);
+        assert_eq!(Ok(()), ctrl.delete("some/a.txt"));
+        assert_eq!(Ok(300), ctrl.stat("some").map(|metadata| metadata.modified()));
+        assert_eq!(Ok(100), ctrl.stat("").map(|metadata| metadata.modified()));
+    });
+}
+
+#[test]
+fn given_stat_when_not_found_then_fails() {
+    let clock = MockClock::new(100);
+    run(&clock, |ctrl| {
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("missing.txt"));
+    });
+}
+
+#[test]
+fn given_stat_when_file_opened_without_atime_then_keeps_accessed() {
+    let clock = MockClock::new(100);
+    run(&clock, |ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", &[1; 64]));
+
+        clock.set(200);
+        let mut buf = [0; 64];
+        assert_eq!(Ok(64), ctrl.open_noatime("file.txt").unwrap().readall(&mut buf));
+        assert_eq!(Ok(100), ctrl.stat("file.txt").map(|metadata| metadata.accessed()));
+    });
+}
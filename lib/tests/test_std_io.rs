@@ -0,0 +1,122 @@
+use std::io::{self, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+
+use common::*;
+use ffs_lib::{
+    Controller, Error, constants,
+    testutils::{Fault, FaultyDevice},
+};
+
+mod common;
+
+/// Reader failing once `len` bytes were read.
+struct FailingReader {
+    len: usize,
+}
+
+impl Read for FailingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.len == 0 {
+            return Err(io::Error::other("disconnected"));
+        }
+        let len = buf.len().min(self.len);
+        buf[..len].fill(7);
+        self.len -= len;
+        Ok(len)
+    }
+}
+
+#[test]
+fn given_file_when_io_copy_then_copies_contents() {
+    run(|ctrl| {
+        let data: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+        assert_eq!(Ok(()), ctrl.create("audio/track.raw", &data));
+
+        let mut file = ctrl.open("audio/track.raw").expect("must open");
+        let mut out = Vec::new();
+        assert_eq!(2000, io::copy(&mut file, &mut out).expect("must copy"));
+        assert_eq!(data, out);
+    });
+}
+
+#[test]
+fn given_file_when_seek_then_reads_from_position() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", b"hello world"));
+
+        let mut file = ctrl.open("file.txt").expect("must open");
+        let mut buf = [0; 5];
+        assert_eq!(6, file.seek(SeekFrom::End(-5)).unwrap());
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(b"world", &buf);
+        assert_eq!(0, file.seek(SeekFrom::Current(-11)).unwrap());
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(b"hello", &buf);
+
+        let err = file.seek(SeekFrom::Current(-6)).unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+        assert_eq!(Error::InvalidSeek, Error::from(err));
+    });
+}
+
+#[test]
+fn given_file_when_write_all_then_grows_file() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.bin", b""));
+        let mut file = ctrl.open("file.bin").expect("must open");
+        file.write_all(&[1; 1000]).unwrap();
+        file.flush().unwrap();
+
+        let err = file.write_all(&[2; constants::MAX_FILE_SIZE]).unwrap_err();
+        assert_eq!(ErrorKind::FileTooLarge, err.kind());
+        assert_eq!(Error::FileTooLarge, Error::from(err));
+        assert_eq!(1000, file.file_len());
+    });
+}
+
+#[test]
+fn given_reader_when_create_from_reader_then_stores_contents() {
+    run(|ctrl| {
+        let data: Vec<u8> = (0..constants::MAX_FILE_SIZE).map(|i| (i / 7) as u8).collect();
+        assert_eq!(Ok(()), ctrl.create_from_reader("big.bin", Cursor::new(&data)));
+        assert_eq!(Ok(()), ctrl.create_from_reader("small.bin", &b"small"[..]));
+
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        let file = ctrl.open("big.bin").and_then(|mut file| file.readall(&mut buf));
+        assert_eq!(Ok(data.len()), file);
+        assert_eq!(data, buf);
+        let file = ctrl.open("small.bin").and_then(|mut file| file.readall(&mut buf));
+        assert_eq!(Ok(5), file);
+        assert_eq!(b"small", &buf[..5]);
+    });
+}
+
+#[test]
+fn given_failing_reader_when_create_from_reader_then_deletes_file() {
+    run(|ctrl| {
+        let free = ctrl.count_free_data_blocks().unwrap();
+
+        let reader = FailingReader { len: 1000 };
+        assert_eq!(Err(Error::Io), ctrl.create_from_reader("file.bin", reader));
+        let reader = Cursor::new(vec![0; constants::MAX_FILE_SIZE + 1]);
+        assert_eq!(Err(Error::FileTooLarge), ctrl.create_from_reader("file.bin", reader));
+
+        assert_eq!(Some(Error::FileNotFound), ctrl.stat("file.bin").err());
+        assert_eq!(Ok(free), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_delete_fails_when_create_from_reader_fails_then_returns_copy_error() {
+    // Count the operations of a copy that just fits, the same copy with one more byte
+    // then fails, along with every operation of the delete that follows.
+    let mut ctrl = Controller::mount(FaultyDevice::new(memory_device())).unwrap();
+    let data = vec![7; constants::MAX_FILE_SIZE];
+    assert_eq!(Ok(()), ctrl.create_from_reader("file.bin", Cursor::new(&data)));
+    let copied = ctrl.unmount().succeeded;
+
+    let device = FaultyDevice::new(memory_device()).with_fault(Fault::After(copied));
+    let mut ctrl = Controller::mount(device).unwrap();
+    let reader = Cursor::new(vec![7; constants::MAX_FILE_SIZE + 1]);
+    assert_eq!(Err(Error::FileTooLarge), ctrl.create_from_reader("file.bin", reader));
+    assert!(ctrl.unmount().failed > 0, "the delete should have failed");
+}
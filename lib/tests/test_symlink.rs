@@ -0,0 +1,82 @@
+use common::*;
+use ffs_lib::{Error, constants};
+
+mod common;
+
+#[test]
+fn given_symlink_when_opened_then_reads_target() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/folder/file.txt", &[123; 256]));
+        assert_eq!(Ok(()), ctrl.symlink("folder/file.txt", "some/link"));
+
+        let mut file_handle = ctrl.open("some/link").expect("must open");
+        let mut buf = vec![0; constants::MAX_FILE_SIZE];
+        assert_eq!(Ok(256), file_handle.readall(&mut buf));
+        assert_eq!([123; 256], &buf[..256]);
+
+        let metadata = ctrl.stat("some/link").unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(256, metadata.len());
+    });
+}
+
+#[test]
+fn given_symlink_when_dir_target_then_resolves_through() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/folder/file.txt", &[123; 256]));
+        assert_eq!(Ok(()), ctrl.symlink("/some/folder", "other/link"));
+
+        let _ = ctrl.open("other/link/file.txt").expect("must open");
+        assert!(ctrl.stat("other/link").unwrap().is_dir());
+        assert_eq!(Err(Error::FileNotFound), ctrl.open("other/link").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_read_link_then_returns_target() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.symlink("../missing.txt", "some/link"));
+
+        let mut buf = [0; 64];
+        assert_eq!(Ok(14), ctrl.read_link("some/link", &mut buf));
+        assert_eq!(b"../missing.txt", &buf[..14]);
+        assert_eq!(
+            Err(Error::BufferTooSmall { expected: 14, found: 4 }),
+            ctrl.read_link("some/link", &mut buf[..4])
+        );
+        assert_eq!(Err(Error::FileNotFound), ctrl.open("some/link").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_symlink_when_invalid_target_then_fail() {
+    run(|ctrl| {
+        assert_eq!(Err(Error::InvalidName), ctrl.symlink("", "link"));
+        assert_eq!(Err(Error::NameTooLong), ctrl.symlink(&"a/".repeat(512), "link"));
+        assert_eq!(Err(Error::FileNotFound), ctrl.stat("link").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_symlink_when_loop_then_fail() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.symlink("b", "a"));
+        assert_eq!(Ok(()), ctrl.symlink("a", "b"));
+        assert_eq!(Err(Error::TooManyLinks), ctrl.open("a").map(|_| ()));
+    });
+}
+
+#[test]
+fn given_delete_when_symlink_then_keeps_target() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[123; 256]));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        assert_eq!(Ok(()), ctrl.symlink("some/file.txt", "link"));
+        assert_eq!(Ok(free_blocks - 1), ctrl.count_free_data_blocks());
+
+        assert_eq!(Ok(()), ctrl.delete("link"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+        assert_eq!(Err(Error::FileNotFound), ctrl.read_link("link", &mut [0; 64]));
+        let _ = ctrl.open("some/file.txt").expect("must open");
+    });
+}
@@ -0,0 +1,86 @@
+use common::*;
+use ffs_lib::{
+    Controller,
+    testutils::{MemoryDevice, Region, Trace, TracingDevice},
+};
+
+mod common;
+
+/// Sectors spanned by a directory tree node.
+const TREE_NODE_SECTORS: usize = Region::Tree.entry_sectors();
+
+/// Mounts a formatted device holding `paths`, and traces the operations run on it
+/// from a cold cache.
+fn mount(paths: &[&str]) -> (Controller<TracingDevice<MemoryDevice>>, Trace) {
+    let device = TracingDevice::new(formatted(|ctrl| {
+        for path in paths {
+            assert_eq!(Ok(()), ctrl.create(path, b"contents"));
+        }
+    }));
+    let trace = device.trace();
+    trace.begin("mount");
+    (Controller::mount(device).expect("should mount device"), trace)
+}
+
+#[test]
+fn given_deep_path_when_stat_then_reads_each_tree_node() {
+    let (mut ctrl, trace) = mount(&["a/b/c/file.txt", "a/other.txt"]);
+
+    trace.begin("stat");
+    assert!(ctrl.stat("a/b/c/file.txt").is_ok());
+    trace.begin("stat again");
+    assert!(ctrl.stat("a/b/c/file.txt").is_ok());
+    trace.begin("stat sibling");
+    assert!(ctrl.stat("a/other.txt").is_ok());
+
+    let stat = trace.operation("stat").unwrap();
+    // The root, a, b and c, then the node of the file.
+    assert_eq!(4 * TREE_NODE_SECTORS, stat.reads(Region::Tree));
+    assert_eq!(1, stat.reads(Region::Node));
+    assert_eq!(stat.accesses.len(), 4 * TREE_NODE_SECTORS + 1, "should only read the path");
+
+    // The cache is too small to keep the whole path between two lookups, only the root
+    // directory is kept.
+    let again = trace.operation("stat again").unwrap();
+    assert_eq!(3 * TREE_NODE_SECTORS, again.reads(Region::Tree));
+
+    let sibling = trace.operation("stat sibling").unwrap();
+    assert_eq!(TREE_NODE_SECTORS, sibling.reads(Region::Tree));
+}
+
+#[test]
+fn given_create_when_traced_then_reports_writes_per_region() {
+    let (mut ctrl, trace) = mount(&["logs/old.log"]);
+
+    trace.begin("create");
+    assert_eq!(Ok(()), ctrl.create("logs/new.log", &[7; 1000]));
+
+    let create = trace.operation("create").unwrap();
+    assert_eq!(1, create.writes(Region::NodeBitmap));
+    // Data blocks are allocated one at a time.
+    assert_eq!(2, create.writes(Region::DataBitmap));
+    assert_eq!(0, create.writes(Region::TreeBitmap), "logs already exists");
+    assert_eq!(TREE_NODE_SECTORS, create.writes(Region::Tree));
+    assert_eq!(2, create.writes(Region::Data));
+    assert_eq!(1, create.writes(Region::Node));
+    assert_eq!(1, create.writes(Region::File));
+    assert_eq!(0, create.reads(Region::Data));
+}
+
+#[test]
+fn given_long_names_when_stat_then_reads_only_matching_overflow_blocks() {
+    let prefix = "long-file-name-".repeat(4);
+    let (first, second) = (format!("logs/{prefix}1.log"), format!("logs/{prefix}22.log"));
+    let (mut ctrl, trace) = mount(&[&first, &second, "logs/short.log"]);
+
+    trace.begin("stat short");
+    assert!(ctrl.stat("logs/short.log").is_ok());
+    trace.begin("stat long");
+    assert!(ctrl.stat(&second).is_ok());
+
+    let short = trace.operation("stat short").unwrap();
+    assert_eq!(0, short.reads(Region::Data), "short names need no overflow block");
+    // Both names share their inline prefix, only the one of the same length is read.
+    let long = trace.operation("stat long").unwrap();
+    assert_eq!(1, long.reads(Region::Data));
+}
@@ -0,0 +1,59 @@
+use common::*;
+use ffs_lib::{Controller, Error, testutils::MemoryDevice};
+
+mod common;
+
+fn walk(ctrl: &mut Controller<MemoryDevice>, base: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let result = ctrl.walk(base, |path, metadata| {
+        let kind = if metadata.is_dir() {
+            "dir"
+        } else if metadata.is_symlink() {
+            "link"
+        } else {
+            "file"
+        };
+        paths.push(format!("{kind} {path} {}", metadata.len()));
+        Ok(())
+    });
+    assert_eq!(Ok(()), result);
+    paths
+}
+
+#[test]
+fn given_tree_when_walk_then_visits_parents_first() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("etc/conf/app.cfg", b"debug"));
+        assert_eq!(Ok(()), ctrl.create("etc/hosts", b"localhost"));
+        assert_eq!(Ok(()), ctrl.symlink("/etc/hosts", "hosts"));
+        assert_eq!(Ok(()), ctrl.mkdir("var"));
+
+        let expected = [
+            "dir etc 0",
+            "dir etc/conf 0",
+            "file etc/conf/app.cfg 5",
+            "file etc/hosts 9",
+            "link hosts 10",
+            "dir var 0",
+        ];
+        assert_eq!(expected[..], walk(ctrl, ""));
+        assert_eq!(["dir conf 0", "file conf/app.cfg 5", "file hosts 9"][..], walk(ctrl, "etc"));
+    });
+}
+
+#[test]
+fn given_walk_when_callback_fails_then_stops() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("a.txt", b"a"));
+        assert_eq!(Ok(()), ctrl.create("b.txt", b"b"));
+
+        let mut visited = 0;
+        let result = ctrl.walk("", |_, _| {
+            visited += 1;
+            Err(Error::Unexpected)
+        });
+        assert_eq!(Err(Error::Unexpected), result);
+        assert_eq!(1, visited);
+        assert_eq!(Err(Error::DirectoryNotFound), ctrl.walk("a.txt", |_, _| Ok(())));
+    });
+}
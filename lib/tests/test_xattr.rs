@@ -0,0 +1,94 @@
+use common::*;
+use ffs_lib::Error;
+
+mod common;
+
+#[test]
+fn given_set_xattr_when_small_then_kept_inline() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[1; 16]));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        assert_eq!(Ok(()), ctrl.set_xattr("some/file.txt", "user.type", b"text/plain"));
+        let mut buf = [0; 64];
+        assert_eq!(Ok(10), ctrl.get_xattr("some/file.txt", "user.type", &mut buf));
+        assert_eq!(b"text/plain", &buf[..10]);
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}
+
+#[test]
+fn given_set_xattr_when_large_then_uses_block() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("some/file.txt", &[1; 16]));
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+
+        assert_eq!(Ok(()), ctrl.set_xattr("some/file.txt", "user.state", b"uploading"));
+        assert_eq!(Ok(()), ctrl.set_xattr("some/file.txt", "user.digest", &[7; 200]));
+        assert_eq!(Ok(free_blocks - 1), ctrl.count_free_data_blocks());
+
+        let mut buf = [0; 256];
+        assert_eq!(Ok(200), ctrl.get_xattr("some/file.txt", "user.digest", &mut buf));
+        assert_eq!([7; 200], buf[..200]);
+
+        assert_eq!(Ok(()), ctrl.remove_xattr("some/file.txt", "user.digest"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+        assert_eq!(Ok(9), ctrl.get_xattr("some/file.txt", "user.state", &mut buf));
+    });
+}
+
+#[test]
+fn given_set_xattr_when_existing_then_replaces() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", &[1; 16]));
+        assert_eq!(Ok(()), ctrl.set_xattr("file.txt", "user.state", b"uploading"));
+        assert_eq!(Ok(()), ctrl.set_xattr("file.txt", "user.state", b"done"));
+
+        let mut buf = [0; 64];
+        assert_eq!(Ok(4), ctrl.get_xattr("file.txt", "user.state", &mut buf));
+        assert_eq!(b"done", &buf[..4]);
+        assert_eq!(Ok(11), ctrl.list_xattrs("file.txt", &mut buf));
+    });
+}
+
+#[test]
+fn given_list_xattrs_then_returns_keys() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", &[1; 16]));
+        let mut buf = [0; 64];
+        assert_eq!(Ok(0), ctrl.list_xattrs("file.txt", &mut buf));
+
+        assert_eq!(Ok(()), ctrl.set_xattr("file.txt", "user.type", b"text/plain"));
+        assert_eq!(Ok(()), ctrl.set_xattr("file.txt", "user.big", &[0; 100]));
+        assert_eq!(Ok(19), ctrl.list_xattrs("file.txt", &mut buf));
+        assert_eq!(b"user.type\0user.big\0", &buf[..19]);
+        assert!(matches!(
+            ctrl.list_xattrs("file.txt", &mut buf[..14]),
+            Err(Error::BufferTooSmall { .. })
+        ));
+    });
+}
+
+#[test]
+fn given_xattr_when_invalid_then_fail() {
+    run(|ctrl| {
+        assert_eq!(Ok(()), ctrl.create("file.txt", &[1; 16]));
+        let mut buf = [0; 4];
+        assert_eq!(Err(Error::InvalidName), ctrl.set_xattr("file.txt", "", b"value"));
+        assert_eq!(Err(Error::XattrTooLarge), ctrl.set_xattr("file.txt", "user.big", &[0; 600]));
+        assert_eq!(Err(Error::XattrNotFound), ctrl.get_xattr("file.txt", "user.big", &mut buf));
+        assert_eq!(Err(Error::XattrNotFound), ctrl.remove_xattr("file.txt", "user.big"));
+        assert_eq!(Err(Error::FileNotFound), ctrl.set_xattr("missing.txt", "user.a", b"value"));
+    });
+}
+
+#[test]
+fn given_delete_when_xattr_block_then_releases_it() {
+    run(|ctrl| {
+        let free_blocks = ctrl.count_free_data_blocks().unwrap();
+        assert_eq!(Ok(()), ctrl.create("file.txt", &[1; 16]));
+        assert_eq!(Ok(()), ctrl.set_xattr("file.txt", "user.big", &[0; 100]));
+        assert_eq!(Ok(()), ctrl.delete("file.txt"));
+        assert_eq!(Ok(free_blocks), ctrl.count_free_data_blocks());
+    });
+}